@@ -1,72 +1,261 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
 use crate::utils::{
-    config::{get_config, ConfigError, Workspace},
-    file::{self, FileError},
+    archive::{self, is_compressed, ArchiveError},
+    config::{get_config, ConfigError},
+    encrypted::{decrypt_files, passphrase, EncryptionError},
+    error::{ErrorKind, Report},
+    file::{self, Conflict, CopyOptions, FileError, Symlinks},
+    json::Json,
+    meta::CloupMeta,
+    name::{CloupName, NameError},
+    output,
+    store::{is_packed, Checkout, StoreError},
 };
 
 #[derive(Debug)]
 pub enum ApplyError {
     NotFound,
+    CloupNotFound(String, String, PathBuf),
+    NotInSearchPath(String, Vec<String>),
+    WorkspaceMismatch(String, String),
+    InvalidName(NameError),
     ConfigError(ConfigError),
     FileError(FileError),
+    EncryptionError(EncryptionError),
+    StoreError(StoreError),
+    ArchiveError(ArchiveError),
 }
 
 impl std::fmt::Display for ApplyError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             ApplyError::NotFound => write!(f, "Workspace not found"),
+            ApplyError::CloupNotFound(name, workspace, _) => write!(
+                f,
+                "Cloup '{}' does not exist in workspace '{}'",
+                name, workspace
+            ),
+            ApplyError::NotInSearchPath(name, workspaces) => write!(
+                f,
+                "Cloup '{}' does not exist in any of the workspaces {}",
+                name,
+                workspaces.join(", ")
+            ),
+            ApplyError::WorkspaceMismatch(name, workspace) => write!(
+                f,
+                "Cloup '{}' names a different workspace than --workspace '{}'",
+                name, workspace
+            ),
+            ApplyError::InvalidName(e) => write!(f, "{}", e),
             ApplyError::ConfigError(e) => write!(f, "Config error: {}", e),
             ApplyError::FileError(e) => write!(f, "File error: {}", e),
+            ApplyError::EncryptionError(e) => write!(f, "{}", e),
+            ApplyError::StoreError(e) => write!(f, "{}", e),
+            ApplyError::ArchiveError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ApplyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApplyError::FileError(e) => e.source(),
+            ApplyError::EncryptionError(e) => e.source(),
+            ApplyError::StoreError(e) => e.source(),
+            ApplyError::ArchiveError(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl Report for ApplyError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ApplyError::NotFound
+            | ApplyError::CloupNotFound(..)
+            | ApplyError::NotInSearchPath(..) => ErrorKind::NotFound,
+            ApplyError::WorkspaceMismatch(..) => ErrorKind::Usage,
+            ApplyError::InvalidName(e) => e.kind(),
+            ApplyError::ConfigError(e) => e.kind(),
+            ApplyError::FileError(e) => e.kind(),
+            ApplyError::EncryptionError(e) => e.kind(),
+            ApplyError::StoreError(e) => e.kind(),
+            ApplyError::ArchiveError(e) => e.kind(),
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            ApplyError::CloupNotFound(_, _, path) => Some(path),
+            ApplyError::FileError(e) => e.path(),
+            ApplyError::EncryptionError(e) => e.path(),
+            ApplyError::StoreError(e) => e.path(),
+            ApplyError::ArchiveError(e) => e.path(),
+            _ => None,
         }
     }
 }
-impl std::error::Error for ApplyError {}
 
 #[derive(Debug)]
 pub struct ApplyOpts {
-    // Name of cloup
+    // Name of cloup, optionally qualified as `workspace/name`
     pub name: String,
 
-    // Workspace to create cloup in
+    // Workspace to apply the cloup from
     pub workspace: Option<String>,
+
+    // What to do with files that already exist, defaults to the conflict setting
+    pub conflict: Option<Conflict>,
+
+    // What to do with symlinks, defaults to the symlinks setting
+    pub symlinks: Option<Symlinks>,
+
+    // Keep permissions and, with the preserve_mtime setting, modification times
+    pub preserve: bool,
+
+    // File holding the passphrase of encrypted files
+    pub key_file: Option<PathBuf>,
 }
 
 pub fn run(opts: ApplyOpts) -> Result<(), ApplyError> {
     let config = get_config().map_err(ApplyError::ConfigError)?;
 
-    if let Some(workspace) = find_workspace(&opts, &config.data.workspaces) {
-        let cloup_path = workspace.location.join(format!("cl_{}", &opts.name));
+    // `workspace/name` picks the workspace explicitly
+    let (workspace, name) = match opts.name.split_once('/') {
+        Some((workspace, name)) => {
+            if opts.workspace.as_ref().is_some_and(|w| w != workspace) {
+                return Err(ApplyError::WorkspaceMismatch(
+                    opts.name.clone(),
+                    opts.workspace.unwrap_or_default(),
+                ));
+            }
+            (Some(workspace.to_string()), name.to_string())
+        }
+        None => (opts.workspace.clone(), opts.name.clone()),
+    };
+    let name = CloupName::new(&name).map_err(ApplyError::InvalidName)?;
 
-        // we want to take all files in the cloup path and copy them to the current directory
-        if cloup_path.exists() {
-            println!(
-                "\x1b[1;32m»\x1b[0m Applied cloup \x1b[1m{}\x1b[0m to \x1b[1m{}\x1b[0m",
-                &opts.name,
-                config.current_dir.to_string_lossy()
-            );
+    let search_order = match &workspace {
+        Some(workspace) => vec![config
+            .data
+            .workspaces
+            .iter()
+            .find(|w| &w.name == workspace)
+            .ok_or(ApplyError::NotFound)?],
+        None => config.data.search_order(),
+    };
 
-            // there has to be some mechanism in case some files already exist and it overwrites, essentially you have to be asked yes or no whether you want to overwrite each file. So we prompt the user for each file that already exists and ask if they want to overwrite it.
+    let mut found = search_order
+        .iter()
+        .filter(|w| w.location.join(name.dir_name()).exists());
 
-            return file::copy_recursive(&cloup_path, &config.current_dir, &[])
-                .map_err(ApplyError::FileError);
-        }
+    let Some(workspace) = found.next() else {
+        return Err(match search_order.as_slice() {
+            [] => ApplyError::NotFound,
+            [workspace] => ApplyError::CloupNotFound(
+                name.to_string(),
+                workspace.name.clone(),
+                workspace.location.join(name.dir_name()),
+            ),
+            workspaces => ApplyError::NotInSearchPath(
+                name.to_string(),
+                workspaces.iter().map(|w| w.name.clone()).collect(),
+            ),
+        });
+    };
 
-        println!(
-            "\x1b[1;33m»\x1b[0m Cloup \x1b[1m{}\x1b[0m does not exist in workspace '{}'",
-            &opts.name, workspace.name
-        );
+    // cloups with the same name further down the search path
+    let shadowed: Vec<String> = found.map(|w| format!("{}/{}", w.name, name)).collect();
 
-        Ok(())
+    let cloup_path = workspace.location.join(name.dir_name());
+
+    // there has to be some mechanism in case some files already exist and it overwrites, essentially you have to be asked yes or no whether you want to overwrite each file. So we prompt the user for each file that already exists and ask if they want to overwrite it.
+
+    // cloups kept in the store or compressed are applied from a checkout,
+    // removed once done
+    let checkout = if is_packed(&cloup_path) {
+        Some(Checkout::new(&cloup_path).map_err(ApplyError::StoreError)?)
+    } else if is_compressed(&cloup_path) {
+        Some(archive::checkout(&cloup_path).map_err(ApplyError::ArchiveError)?)
     } else {
-        Err(ApplyError::NotFound)
+        None
+    };
+    let source = checkout.as_ref().map_or(cloup_path.as_path(), |c| &c.path);
+
+    // decrypted up front, a wrong passphrase leaves the destination untouched
+    let meta = CloupMeta::read(source).map_err(ApplyError::FileError)?;
+    let encrypted: Vec<PathBuf> = meta.encrypted.iter().map(|f| source.join(f)).collect();
+    let contents = if encrypted.is_empty() {
+        HashMap::new()
+    } else {
+        passphrase(opts.key_file.as_deref(), false)
+            .and_then(|passphrase| decrypt_files(&encrypted, &passphrase))
+            .map_err(ApplyError::EncryptionError)?
+    };
+
+    let copy_options = CopyOptions {
+        conflict: opts.conflict.unwrap_or(config.settings.conflict),
+        symlinks: opts.symlinks.unwrap_or(config.settings.symlinks),
+        confine: true,
+        preserve_mode: opts.preserve,
+        preserve_mtime: opts.preserve && config.settings.preserve_mtime,
+        skip_meta: true,
+        unescape: true,
+        contents,
+        ..CopyOptions::default()
+    };
+    let copied = file::copy_recursive(source, &config.current_dir, &copy_options)
+        .map_err(ApplyError::FileError)?;
+
+    // the workspace may have lost permissions the cloup was created with
+    if opts.preserve {
+        meta.restore_modes(&config.current_dir, &copied.files)
+            .map_err(ApplyError::FileError)?;
     }
-}
 
-fn find_workspace<'a>(opts: &'a ApplyOpts, workspaces: &'a [Workspace]) -> Option<&'a Workspace> {
-    if opts.workspace.is_none() {
-        workspaces.iter().find(|w| w.active)
+    if output::is_json() {
+        output::json(
+            Json::object()
+                .with("cloup", name.as_str())
+                .with("workspace", &workspace.name)
+                .with("destination", &config.current_dir)
+                .with(
+                    "files",
+                    copied.files.iter().map(Json::from).collect::<Vec<_>>(),
+                )
+                .with(
+                    "skipped",
+                    copied.skipped.iter().map(Json::from).collect::<Vec<_>>(),
+                )
+                .with("shadowed", shadowed),
+        );
     } else {
-        workspaces
-            .iter()
-            .find(|w| w.name == opts.workspace.clone().unwrap())
+        output::println(format!(
+            "\x1b[1;32m»\x1b[0m Applied cloup \x1b[1m{}\x1b[0m from workspace \x1b[1m{}\x1b[0m to \x1b[1m{}\x1b[0m",
+            &name,
+            workspace.name,
+            config.current_dir.to_string_lossy()
+        ));
+
+        if !copied.skipped.is_empty() {
+            output::println(format!(
+                "\x1b[1;33m»\x1b[0m Kept {} existing file(s)",
+                copied.skipped.len()
+            ));
+        }
+
+        if !shadowed.is_empty() {
+            output::eprintln(format!(
+                "\x1b[1;33m»\x1b[0m '{}' is ambiguous, it also exists as {}",
+                name,
+                shadowed.join(", ")
+            ));
+        }
     }
+
+    Ok(())
 }
@@ -0,0 +1,392 @@
+use crate::{
+    commands::list::cloup_names,
+    utils::{
+        config::{config_path, get_config},
+        error::{ErrorKind, Report},
+        parse::{find_command, find_in, CommandSpec, Complete, FlagSpec, COMMANDS, GLOBAL_FLAGS},
+        settings::SETTINGS,
+    },
+};
+
+#[derive(Debug)]
+pub enum CompletionsError {
+    UnknownShell(String),
+}
+
+impl std::fmt::Display for CompletionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CompletionsError::UnknownShell(shell) => write!(
+                f,
+                "Unknown shell '{}', expected one of: {}",
+                shell,
+                SHELLS.join(", ")
+            ),
+        }
+    }
+}
+impl std::error::Error for CompletionsError {}
+
+impl Report for CompletionsError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Usage
+    }
+}
+
+/// Shells a completion script can be generated for
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish", "elvish", "powershell"];
+
+#[derive(Debug)]
+pub struct CompletionsOpts {
+    // Shell to generate the completion script for
+    pub shell: String,
+}
+
+pub fn run(opts: CompletionsOpts) -> Result<(), CompletionsError> {
+    let script = match opts.shell.as_str() {
+        "bash" => BASH,
+        "zsh" => ZSH,
+        "fish" => FISH,
+        "elvish" => ELVISH,
+        "powershell" => POWERSHELL,
+        _ => return Err(CompletionsError::UnknownShell(opts.shell)),
+    };
+
+    print!("{}", script);
+    Ok(())
+}
+
+/// Print the completion candidates for the given words, the last word being
+/// the one under the cursor. Used by the completion scripts via `cloup __complete`.
+pub fn complete(words: Vec<String>) {
+    for (value, description) in candidates(&words) {
+        match description {
+            Some(description) => println!("{}\t{}", value, description),
+            None => println!("{}", value),
+        }
+    }
+}
+
+/// Find the flag a word refers to, along with its inline value (`--flag=value` or `-fvalue`)
+fn find_flag<'a>(
+    word: &'a str,
+    flags: &[&'static FlagSpec],
+) -> Option<(&'static FlagSpec, Option<&'a str>)> {
+    if let Some(long) = word.strip_prefix("--") {
+        let (name, inline) = match long.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (long, None),
+        };
+        return flags.iter().find(|f| f.long == name).map(|f| (*f, inline));
+    }
+
+    // The first short flag in a cluster taking a value consumes the rest of the word
+    let cluster = word.strip_prefix('-')?;
+    for (pos, c) in cluster.char_indices() {
+        let flag = flags.iter().find(|f| f.short == Some(c))?;
+        if flag.value.is_some() {
+            let rest = &cluster[pos + c.len_utf8()..];
+            let rest = rest.strip_prefix('=').unwrap_or(rest);
+            return Some((flag, Some(rest).filter(|r| !r.is_empty())));
+        }
+    }
+
+    None
+}
+
+fn candidates(words: &[String]) -> Vec<(String, Option<&'static str>)> {
+    let Some((current, previous)) = words.split_last() else {
+        return vec![];
+    };
+
+    let mut spec: Option<&'static CommandSpec> = None;
+    let mut pending: Option<&'static FlagSpec> = None;
+    let mut workspace: Option<String> = None;
+    let mut positionals = 0;
+    let mut only_args = false;
+
+    for word in previous {
+        let flags = flags_for(spec);
+
+        if let Some(flag) = pending {
+            if !word.starts_with('-') {
+                if flag.long == "workspace" {
+                    workspace = Some(word.clone());
+                }
+                if !flag.multiple {
+                    pending = None;
+                }
+                continue;
+            }
+            pending = None;
+        }
+
+        if !only_args && word == "--" {
+            only_args = true;
+        } else if !only_args && word.starts_with('-') && word != "-" {
+            match find_flag(word, &flags) {
+                Some((flag, None)) if flag.value.is_some() => pending = Some(flag),
+                Some((flag, Some(value))) if flag.long == "workspace" => {
+                    workspace = Some(value.to_string())
+                }
+                _ => (),
+            }
+        } else if let Some(current) = spec {
+            match find_in(current.subcommands, word) {
+                Some(subcommand) if positionals == 0 => spec = Some(subcommand),
+                _ => positionals += 1,
+            }
+        } else {
+            spec = Some(match find_command(word) {
+                Some(spec) => spec,
+                None => return vec![],
+            });
+        }
+    }
+
+    let flags = flags_for(spec);
+    let filter = |values: Vec<(String, Option<&'static str>)>| {
+        values
+            .into_iter()
+            .filter(|(v, _)| v.starts_with(current.as_str()))
+            .collect::<Vec<_>>()
+    };
+
+    // Value of a flag given as `--flag=value`
+    if !only_args && current.starts_with("--") && current.contains('=') {
+        let (name, value) = current.split_once('=').unwrap_or_default();
+        return match find_flag(name, &flags) {
+            Some((flag, _)) => complete_kind(&flag.complete, workspace.as_deref())
+                .into_iter()
+                .filter(|(v, _)| v.starts_with(value))
+                .map(|(v, d)| (format!("{}={}", name, v), d))
+                .collect(),
+            None => vec![],
+        };
+    }
+
+    if let Some(flag) = pending {
+        if !(flag.multiple && current.starts_with('-')) {
+            return filter(complete_kind(&flag.complete, workspace.as_deref()));
+        }
+    }
+
+    if !only_args && current.starts_with('-') {
+        let mut values = vec![];
+        for flag in &flags {
+            values.push((format!("--{}", flag.long), Some(flag.help)));
+            if let Some(short) = flag.short {
+                values.push((format!("-{}", short), Some(flag.help)));
+            }
+        }
+        return filter(values);
+    }
+
+    match spec {
+        None => filter(
+            COMMANDS
+                .iter()
+                .map(|c| (c.name.to_string(), Some(c.about)))
+                .collect(),
+        ),
+        Some(spec) => {
+            let arg = spec
+                .args
+                .get(positionals)
+                .or(spec.args.last().filter(|a| a.multiple));
+
+            let mut values = match arg {
+                Some(arg) => complete_kind(&arg.complete, workspace.as_deref()),
+                None => vec![],
+            };
+
+            if positionals == 0 {
+                values.extend(
+                    spec.subcommands
+                        .iter()
+                        .map(|c| (c.word().to_string(), Some(c.about))),
+                );
+            }
+
+            filter(values)
+        }
+    }
+}
+
+/// Flags available for a command, including global flags
+fn flags_for(spec: Option<&'static CommandSpec>) -> Vec<&'static FlagSpec> {
+    spec.map(|s| s.flags.iter().collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .chain(GLOBAL_FLAGS)
+        .collect()
+}
+
+/// Candidates for a kind of value. Files are left to the shell, which falls
+/// back to file completion when no candidates are printed.
+fn complete_kind(kind: &Complete, workspace: Option<&str>) -> Vec<(String, Option<&'static str>)> {
+    match kind {
+        Complete::Nothing | Complete::Files => vec![],
+        Complete::Values(values) => values.iter().map(|v| (v.to_string(), None)).collect(),
+        Complete::Commands => COMMANDS
+            .iter()
+            .map(|c| (c.name.to_string(), Some(c.about)))
+            .collect(),
+        Complete::Settings | Complete::Workspaces | Complete::Cloups => {
+            // Never create a config just because the user pressed tab
+            if !config_path().is_ok_and(|p| p.exists()) {
+                return vec![];
+            }
+            let Ok(config) = get_config() else {
+                return vec![];
+            };
+
+            let workspaces = config.data.workspaces.iter();
+            if *kind == Complete::Settings {
+                return SETTINGS
+                    .iter()
+                    .map(|s| (s.key.to_string(), Some(s.help)))
+                    .chain(
+                        workspaces
+                            .filter(|w| !w.local)
+                            .map(|w| (format!("workspaces.{}", w.name), None)),
+                    )
+                    .collect();
+            }
+            if *kind == Complete::Workspaces {
+                return workspaces.map(|w| (w.name.clone(), None)).collect();
+            }
+
+            let searched = match workspace {
+                Some(name) => workspaces.clone().filter(|w| w.name == name).collect(),
+                None => config.data.search_order(),
+            };
+
+            let mut names: Vec<String> = searched
+                .iter()
+                .flat_map(|w| cloup_names(&w.location).unwrap_or_default())
+                .collect();
+            names.sort();
+            names.dedup();
+
+            names.into_iter().map(|name| (name, None)).collect()
+        }
+    }
+}
+
+const BASH: &str = r#"# bash completion for cloup
+_cloup() {
+    local IFS=$'\n'
+    local candidates
+    candidates=($(cloup __complete -- "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null | cut -f1))
+    COMPREPLY=($(compgen -W "${candidates[*]}" -- "${COMP_WORDS[COMP_CWORD]}"))
+}
+
+complete -o default -F _cloup cloup
+"#;
+
+const ZSH: &str = r#"#compdef cloup
+
+_cloup() {
+    local -a candidates described
+    local line
+
+    candidates=("${(@f)$(cloup __complete -- "${(@)words[2,CURRENT]}" 2>/dev/null)}")
+    candidates=("${(@)candidates:#}")
+
+    if (( ${#candidates} == 0 )); then
+        _files
+        return
+    fi
+
+    for line in "${candidates[@]}"; do
+        if [[ $line == *$'\t'* ]]; then
+            described+=("${${line%%$'\t'*}//:/\\:}:${line#*$'\t'}")
+        else
+            described+=("${line//:/\\:}")
+        fi
+    done
+
+    _describe 'cloup' described
+}
+
+if [ "$funcstack[1]" = "_cloup" ]; then
+    _cloup "$@"
+else
+    compdef _cloup cloup
+fi
+"#;
+
+const FISH: &str = r#"# fish completion for cloup
+function __cloup_complete
+    set -l words (commandline -opc)
+    set -e words[1]
+    set -l candidates (cloup __complete -- $words (commandline -ct) 2>/dev/null)
+
+    if test (count $candidates) -eq 0
+        __fish_complete_path (commandline -ct)
+    else
+        printf '%s\n' $candidates
+    end
+end
+
+complete -c cloup -f -a '(__cloup_complete)'
+"#;
+
+const ELVISH: &str = r#"# elvish completion for cloup
+use str
+
+set edit:completion:arg-completer[cloup] = {|@words|
+    var candidates = [(cloup __complete -- $@words[1..] 2>/dev/null)]
+
+    if (== (count $candidates) 0) {
+        edit:complete-filename $words[-1]
+    } else {
+        for candidate $candidates {
+            put (str:split "\t" $candidate | take 1)
+        }
+    }
+}
+"#;
+
+const POWERSHELL: &str = r#"# powershell completion for cloup
+Register-ArgumentCompleter -Native -CommandName cloup -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    $words = @($commandAst.CommandElements | Select-Object -Skip 1 | ForEach-Object { $_.ToString() })
+    if ($wordToComplete -eq '') {
+        $words += ''
+    }
+
+    cloup __complete -- @words 2>$null | ForEach-Object {
+        $value, $description = $_ -split "`t", 2
+        if (-not $description) {
+            $description = $value
+        }
+        [System.Management.Automation.CompletionResult]::new($value, $value, 'ParameterValue', $description)
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(words: &[&str]) -> Vec<String> {
+        let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+        candidates(&words).into_iter().map(|(v, _)| v).collect()
+    }
+
+    #[test]
+    fn complete_static_candidates() {
+        assert_eq!(values(&["ap"]), vec!["apply"]);
+        assert_eq!(values(&["--json", "compl"]), vec!["completions"]);
+        assert_eq!(values(&["apply", "x", "--work"]), vec!["--workspace"]);
+        assert_eq!(values(&["completions", "f"]), vec!["fish"]);
+        assert_eq!(values(&["list", "--format", ""]), vec!["text", "json"]);
+        assert_eq!(values(&["list", "--format=j"]), vec!["--format=json"]);
+        assert!(values(&["create", "x", "-f", "a", ""]).is_empty());
+        assert_eq!(values(&["workspace", "ren"]), vec!["rename"]);
+        assert_eq!(values(&["workspace", "move", "-"])[0], "--move-cloups");
+    }
+}
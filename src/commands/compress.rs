@@ -0,0 +1,165 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    commands::list::{calculate_size, format_size},
+    utils::{
+        archive::{compress, decompress, is_compressed, ArchiveError},
+        config::{get_config, ConfigError},
+        error::{ErrorKind, Report},
+        json::Json,
+        name::{CloupName, NameError},
+        output,
+        store::{is_dedup, is_packed, pack, unpack_in_place, StoreError},
+    },
+};
+
+#[derive(Debug)]
+pub enum CompressError {
+    NotFound,
+    CloupNotFound(String, String, PathBuf),
+    InvalidName(NameError),
+    ConfigError(ConfigError),
+    StoreError(StoreError),
+    ArchiveError(ArchiveError),
+}
+
+impl std::fmt::Display for CompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CompressError::NotFound => write!(f, "Workspace not found"),
+            CompressError::CloupNotFound(name, workspace, _) => write!(
+                f,
+                "Cloup '{}' does not exist in workspace '{}'",
+                name, workspace
+            ),
+            CompressError::InvalidName(e) => write!(f, "{}", e),
+            CompressError::ConfigError(e) => write!(f, "Config error: {}", e),
+            CompressError::StoreError(e) => write!(f, "{}", e),
+            CompressError::ArchiveError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CompressError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompressError::StoreError(e) => e.source(),
+            CompressError::ArchiveError(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl Report for CompressError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            CompressError::NotFound | CompressError::CloupNotFound(..) => ErrorKind::NotFound,
+            CompressError::InvalidName(e) => e.kind(),
+            CompressError::ConfigError(e) => e.kind(),
+            CompressError::StoreError(e) => e.kind(),
+            CompressError::ArchiveError(e) => e.kind(),
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            CompressError::CloupNotFound(_, _, path) => Some(path),
+            CompressError::StoreError(e) => e.path(),
+            CompressError::ArchiveError(e) => e.path(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CompressOpts {
+    // Names of the cloups to compress or decompress
+    pub names: Vec<String>,
+
+    // Workspace of the cloups, defaults to the active one
+    pub workspace: Option<String>,
+
+    // Turn compressed cloups back into directories
+    pub decompress: bool,
+}
+
+pub fn run(opts: CompressOpts) -> Result<(), CompressError> {
+    let config = get_config().map_err(CompressError::ConfigError)?;
+
+    let workspace = config
+        .data
+        .workspaces
+        .iter()
+        .find(|w| match &opts.workspace {
+            Some(name) => &w.name == name,
+            None => w.active,
+        })
+        .ok_or(CompressError::NotFound)?;
+
+    // every name is checked before anything is converted
+    let mut cloups = vec![];
+    for name in &opts.names {
+        let name = CloupName::new(name).map_err(CompressError::InvalidName)?;
+        let cloup_path = workspace.location.join(name.dir_name());
+        if !cloup_path.is_dir() {
+            return Err(CompressError::CloupNotFound(
+                name.to_string(),
+                workspace.name.clone(),
+                cloup_path,
+            ));
+        }
+        cloups.push((name, cloup_path));
+    }
+
+    let mut converted = vec![];
+    for (name, cloup_path) in &cloups {
+        if opts.decompress == is_compressed(cloup_path) {
+            if opts.decompress {
+                decompress(cloup_path).map_err(CompressError::ArchiveError)?;
+                if is_dedup(&workspace.location) {
+                    pack(cloup_path).map_err(CompressError::StoreError)?;
+                }
+            } else {
+                if is_packed(cloup_path) {
+                    unpack_in_place(cloup_path).map_err(CompressError::StoreError)?;
+                }
+                compress(cloup_path).map_err(CompressError::ArchiveError)?;
+            }
+            converted.push(name.to_string());
+        }
+    }
+
+    if output::is_json() {
+        output::json(
+            Json::object()
+                .with("workspace", &workspace.name)
+                .with("compressed", !opts.decompress)
+                .with("converted", converted),
+        );
+        return Ok(());
+    }
+
+    let action = if opts.decompress {
+        "Decompressed"
+    } else {
+        "Compressed"
+    };
+    for (name, cloup_path) in &cloups {
+        if converted.iter().any(|c| c == name.as_str()) {
+            output::println(format!(
+                "\x1b[1;32m»\x1b[0m {} cloup '{}' ({})",
+                action,
+                name,
+                format_size(calculate_size(cloup_path))
+            ));
+        } else {
+            output::println(format!(
+                "\x1b[1;33m»\x1b[0m Cloup '{}' is already {}",
+                name,
+                action.to_lowercase()
+            ));
+        }
+    }
+
+    Ok(())
+}
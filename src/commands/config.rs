@@ -0,0 +1,288 @@
+use std::process;
+
+use crate::{
+    toml::data::{Toml, TomlValueKind},
+    utils::{
+        config::{config_path, get_config, update_config, ConfigError},
+        error::{ErrorKind, Report},
+        json::Json,
+        output,
+        settings::{find_setting, parse_value, validate, SettingsError},
+    },
+};
+
+#[derive(Debug)]
+pub enum ConfigCommandError {
+    NotSet(String),
+    Managed(String),
+    NoWorkspace(String),
+    Editor(String, Option<std::io::Error>),
+    ConfigError(ConfigError),
+    SettingsError(SettingsError),
+}
+
+impl std::fmt::Display for ConfigCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigCommandError::NotSet(key) => write!(f, "Config key '{}' is not set", key),
+            ConfigCommandError::Managed(key) => {
+                write!(f, "Config key '{}' is managed with 'cloup workspace'", key)
+            }
+            ConfigCommandError::NoWorkspace(name) => {
+                write!(f, "Workspace '{}' does not exist", name)
+            }
+            ConfigCommandError::Editor(editor, Some(e)) => {
+                write!(f, "Could not run editor '{}': {}", editor, e)
+            }
+            ConfigCommandError::Editor(editor, None) => {
+                write!(f, "Editor '{}' exited with an error", editor)
+            }
+            ConfigCommandError::ConfigError(e) => write!(f, "Config error: {}", e),
+            ConfigCommandError::SettingsError(e) => write!(f, "{}", e),
+        }
+    }
+}
+impl std::error::Error for ConfigCommandError {}
+
+impl Report for ConfigCommandError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ConfigCommandError::NotSet(_) | ConfigCommandError::NoWorkspace(_) => {
+                ErrorKind::NotFound
+            }
+            ConfigCommandError::Managed(_) => ErrorKind::Usage,
+            ConfigCommandError::Editor(..) => ErrorKind::Io,
+            ConfigCommandError::ConfigError(e) => e.kind(),
+            ConfigCommandError::SettingsError(e) => e.kind(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigAction {
+    Get { key: String },
+    Set { key: String, values: Vec<String> },
+    Unset { key: String },
+    List,
+    Edit,
+    Path,
+}
+
+#[derive(Debug)]
+pub struct ConfigOpts {
+    // What to do with the config
+    pub action: ConfigAction,
+}
+
+pub fn run(opts: ConfigOpts) -> Result<(), ConfigCommandError> {
+    // Printing the path shouldn't create the config
+    if let ConfigAction::Path = opts.action {
+        let path = config_path().map_err(ConfigCommandError::ConfigError)?;
+        if output::is_json() {
+            output::json(Json::object().with("path", &path));
+        } else {
+            println!("{}", path.to_string_lossy());
+        }
+        return Ok(());
+    }
+
+    let config = get_config().map_err(ConfigCommandError::ConfigError)?;
+    let toml = config.toml;
+
+    match opts.action {
+        ConfigAction::Get { key } => {
+            let value = lookup(&toml, &key)?.ok_or(ConfigCommandError::NotSet(key.clone()))?;
+
+            if output::is_json() {
+                output::json(
+                    Json::object()
+                        .with("key", &key)
+                        .with("value", to_json(value)),
+                );
+            } else {
+                match value {
+                    TomlValueKind::Array(values) => {
+                        values.iter().for_each(|v| println!("{}", to_text(v)))
+                    }
+                    value => println!("{}", to_text(value)),
+                }
+            }
+        }
+        ConfigAction::Set { key, values } => {
+            if key.starts_with("workspaces.") {
+                return Err(ConfigCommandError::Managed(key));
+            }
+
+            let setting = find_setting(&key).map_err(ConfigCommandError::SettingsError)?;
+            let value = parse_value(setting, &values).map_err(ConfigCommandError::SettingsError)?;
+
+            if let TomlValueKind::String(name) = &value {
+                if setting.key == "active_workspace"
+                    && !config
+                        .data
+                        .workspaces
+                        .iter()
+                        .any(|w| &w.name == name && !w.local)
+                {
+                    return Err(ConfigCommandError::NoWorkspace(name.clone()));
+                }
+            }
+
+            let json = to_json(&value);
+            update_config(&config.config_path, |toml| match setting.path() {
+                ("", key) => toml.set(key.to_string(), value),
+                (table, key) => toml.set_in(table, key.to_string(), value),
+            })
+            .map_err(ConfigCommandError::ConfigError)?;
+
+            if output::is_json() {
+                output::json(Json::object().with("key", &key).with("value", json));
+            } else {
+                output::println(format!("\x1b[1;32m»\x1b[0m Set \x1b[1m{}\x1b[0m", key));
+            }
+        }
+        ConfigAction::Unset { key } => {
+            if key.starts_with("workspaces.") {
+                return Err(ConfigCommandError::Managed(key));
+            }
+
+            let setting = find_setting(&key).map_err(ConfigCommandError::SettingsError)?;
+            if setting.read_only || setting.key == "active_workspace" {
+                return Err(ConfigCommandError::SettingsError(SettingsError::ReadOnly(
+                    key,
+                )));
+            }
+
+            let mut removed = false;
+            update_config(&config.config_path, |toml| {
+                removed = match setting.path() {
+                    ("", key) => toml.delete(key),
+                    (table, key) => toml.delete_in(table, key),
+                };
+                Ok(())
+            })
+            .map_err(ConfigCommandError::ConfigError)?;
+
+            if output::is_json() {
+                output::json(Json::object().with("key", &key).with("removed", removed));
+            } else if removed {
+                output::println(format!("\x1b[1;32m»\x1b[0m Unset \x1b[1m{}\x1b[0m", key));
+            } else {
+                output::println(format!(
+                    "\x1b[1;33m»\x1b[0m \x1b[1m{}\x1b[0m was not set",
+                    key
+                ));
+            }
+        }
+        ConfigAction::List => {
+            let entries = entries(&toml);
+
+            if output::is_json() {
+                output::json(Json::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key, to_json(value)))
+                        .collect(),
+                ));
+            } else {
+                for (key, value) in entries {
+                    println!(
+                        "{} = {}",
+                        key,
+                        Toml::format_value(value).unwrap_or_default()
+                    );
+                }
+            }
+        }
+        ConfigAction::Edit => {
+            let editor = config.settings.editor.unwrap_or_else(default_editor);
+            let mut words = editor.split_whitespace();
+            let program = words.next().unwrap_or("vi");
+
+            let status = process::Command::new(program)
+                .args(words)
+                .arg(&config.config_path)
+                .status()
+                .map_err(|e| ConfigCommandError::Editor(editor.clone(), Some(e)))?;
+            if !status.success() {
+                return Err(ConfigCommandError::Editor(editor, None));
+            }
+
+            // The edit is kept either way, but a broken config is reported right away
+            let config = get_config().map_err(ConfigCommandError::ConfigError)?;
+            validate(&config.toml).map_err(ConfigCommandError::SettingsError)?;
+
+            if output::is_json() {
+                output::json(
+                    Json::object()
+                        .with("path", &config.config_path)
+                        .with("valid", true),
+                );
+            }
+        }
+        ConfigAction::Path => (),
+    }
+
+    Ok(())
+}
+
+/// Value of a dotted key, `workspaces.<name>` included
+fn lookup<'a>(toml: &'a Toml, key: &str) -> Result<Option<&'a TomlValueKind>, ConfigCommandError> {
+    if let Some(name) = key.strip_prefix("workspaces.") {
+        return Ok(toml.get_in("workspaces", name));
+    }
+
+    let setting = find_setting(key).map_err(ConfigCommandError::SettingsError)?;
+    Ok(match setting.path() {
+        ("", key) => toml.get(key),
+        (table, key) => toml.get_in(table, key),
+    })
+}
+
+/// Every value of the config as dotted keys
+fn entries(toml: &Toml) -> Vec<(String, &TomlValueKind)> {
+    let mut entries = vec![];
+
+    for value in &toml.data {
+        match &value.kind {
+            TomlValueKind::Table(key_values) => {
+                for kv in key_values {
+                    entries.push((format!("{}.{}", value.key, kv.key), &kv.kind));
+                }
+            }
+            kind => entries.push((value.key.clone(), kind)),
+        }
+    }
+
+    entries
+}
+
+/// Editor from the environment, falling back to vi (notepad on Windows)
+fn default_editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|key| std::env::var(key).ok().filter(|v| !v.trim().is_empty()))
+        .unwrap_or(if cfg!(windows) { "notepad" } else { "vi" }.to_string())
+}
+
+fn to_text(value: &TomlValueKind) -> String {
+    match value {
+        TomlValueKind::String(value) => value.clone(),
+        value => Toml::format_value(value).unwrap_or_default(),
+    }
+}
+
+fn to_json(value: &TomlValueKind) -> Json {
+    match value {
+        TomlValueKind::String(value) => Json::from(value),
+        TomlValueKind::Integer(value) => Json::Integer(*value),
+        TomlValueKind::Boolean(value) => Json::Bool(*value),
+        TomlValueKind::Array(values) => Json::Array(values.iter().map(to_json).collect()),
+        TomlValueKind::Table(key_values) => Json::Object(
+            key_values
+                .iter()
+                .map(|kv| (kv.key.clone(), to_json(&kv.kind)))
+                .collect(),
+        ),
+    }
+}
@@ -4,17 +4,34 @@ use std::{
 };
 
 use crate::utils::{
+    archive::{compress, ArchiveError},
     config::{get_config, ConfigError, Workspace},
-    file::{copy_recursive, FileError},
+    encrypted::{files_to_encrypt, passphrase, seal_files, EncryptionError},
+    error::{ErrorKind, Report},
+    file::{
+        copy_path, copy_recursive, escape_name, is_excluded, Copied, CopyOptions, FileError,
+        Symlinks,
+    },
+    json::Json,
+    meta::{keep_empty_dirs, CloupMeta},
+    name::{CloupName, NameError},
+    output,
+    secrets::{find_secrets, Secret, SecretPolicy},
+    store::{is_dedup, pack, StoreError},
 };
 
 #[derive(Debug)]
 pub enum CreateError {
     DestinationNotFound,
     NameExists,
+    InvalidName(NameError),
+    Secrets(Vec<Secret>),
     Error(std::io::Error),
     ConfigError(ConfigError),
     FileError(FileError),
+    EncryptionError(EncryptionError),
+    StoreError(StoreError),
+    ArchiveError(ArchiveError),
 }
 
 impl std::fmt::Display for CreateError {
@@ -22,13 +39,64 @@ impl std::fmt::Display for CreateError {
         match self {
             CreateError::DestinationNotFound => write!(f, "Destination not found"),
             CreateError::NameExists => write!(f, "Name already exists"),
+            CreateError::InvalidName(e) => write!(f, "{}", e),
+            CreateError::Secrets(secrets) => {
+                write!(f, "Files look like they hold secrets, nothing was stored:")?;
+                for secret in secrets {
+                    write!(f, "\n    {}", secret)?;
+                }
+                write!(
+                    f,
+                    "\nPass --secrets warn to store them anyway, or add them to settings.allow_secrets"
+                )
+            }
             CreateError::Error(e) => write!(f, "Error: {}", e),
             CreateError::ConfigError(e) => write!(f, "Config error: {}", e),
             CreateError::FileError(e) => write!(f, "File error: {}", e),
+            CreateError::EncryptionError(e) => write!(f, "{}", e),
+            CreateError::StoreError(e) => write!(f, "{}", e),
+            CreateError::ArchiveError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CreateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CreateError::FileError(e) => e.source(),
+            CreateError::EncryptionError(e) => e.source(),
+            CreateError::StoreError(e) => e.source(),
+            CreateError::ArchiveError(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl Report for CreateError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            CreateError::DestinationNotFound => ErrorKind::NotFound,
+            CreateError::NameExists | CreateError::Secrets(_) => ErrorKind::Conflict,
+            CreateError::InvalidName(e) => e.kind(),
+            CreateError::Error(_) => ErrorKind::Io,
+            CreateError::ConfigError(e) => e.kind(),
+            CreateError::FileError(e) => e.kind(),
+            CreateError::EncryptionError(e) => e.kind(),
+            CreateError::StoreError(e) => e.kind(),
+            CreateError::ArchiveError(e) => e.kind(),
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            CreateError::FileError(e) => e.path(),
+            CreateError::EncryptionError(e) => e.path(),
+            CreateError::StoreError(e) => e.path(),
+            CreateError::ArchiveError(e) => e.path(),
+            _ => None,
         }
     }
 }
-impl std::error::Error for CreateError {}
 
 #[derive(Debug)]
 pub struct CreateOpts {
@@ -43,9 +111,28 @@ pub struct CreateOpts {
 
     // Workspace to create cloup in
     pub workspace: Option<String>,
+
+    // What to do with symlinks, defaults to the symlinks setting
+    pub symlinks: Option<Symlinks>,
+
+    // Keep permissions and, with the preserve_mtime setting, modification times
+    pub preserve: bool,
+
+    // What to do with likely secrets, defaults to the secrets setting
+    pub secrets: Option<SecretPolicy>,
+
+    // Files to encrypt, as names, paths or glob patterns
+    pub encrypt: Vec<String>,
+
+    // File holding the passphrase of encrypted files
+    pub key_file: Option<PathBuf>,
+
+    // Keep the cloup as a compressed archive
+    pub compress: bool,
 }
 
 pub fn run(opts: CreateOpts) -> Result<(), CreateError> {
+    let name = CloupName::new(&opts.name).map_err(CreateError::InvalidName)?;
     let config = get_config().map_err(CreateError::ConfigError)?;
 
     if let Some(workspace) = find_workspace(&opts, &config.data.workspaces) {
@@ -54,34 +141,168 @@ pub fn run(opts: CreateOpts) -> Result<(), CreateError> {
             .iter()
             .map(|f| config.current_dir.join(f))
             .collect();
-        let exclude: Vec<PathBuf> = opts
-            .exclude
-            .iter()
-            .map(|f| config.current_dir.join(f))
-            .collect();
 
-        let cloup_path = workspace.location.join(format!("cl_{}", &opts.name));
+        // patterns from the command line come on top of the exclude setting
+        let mut copy_options = CopyOptions {
+            exclude: opts
+                .exclude
+                .iter()
+                .map(|f| f.to_string_lossy().to_string())
+                .chain(config.settings.exclude.iter().cloned())
+                .collect(),
+            symlinks: opts.symlinks.unwrap_or(config.settings.symlinks),
+            preserve_mode: opts.preserve,
+            preserve_mtime: opts.preserve && config.settings.preserve_mtime,
+            skip_meta: true,
+            escape: config.settings.escape.clone(),
+            dry_run: true,
+            ..CopyOptions::default()
+        };
+
+        let cloup_path = workspace.location.join(name.dir_name());
         if cloup_path.exists() {
             return Err(CreateError::NameExists);
         } else {
             fs::create_dir_all(&cloup_path).map_err(CreateError::Error)?;
         }
 
-        if copy_files(&files, &exclude, &cloup_path).is_err() {
-            fs::remove_dir_all(&cloup_path).map_err(CreateError::Error)?;
+        // files are listed before anything is copied, so secrets are found
+        // before they're stored, and files to encrypt are encrypted on the way
+        // so their plaintext never reaches the workspace
+        let planned = match copy_files(&files, &copy_options, &cloup_path) {
+            Ok(planned) => planned,
+            Err(e) => {
+                fs::remove_dir_all(&cloup_path).map_err(CreateError::Error)?;
+                return Err(e);
+            }
+        };
+
+        let follow = copy_options.symlinks == Symlinks::Follow;
+        let to_encrypt = files_to_encrypt(&cloup_path, &planned, &opts.encrypt, follow);
+        let encrypted: Vec<PathBuf> = to_encrypt.iter().map(|(_, file)| file.clone()).collect();
+
+        let secrets = match opts.secrets.unwrap_or(config.settings.secrets) {
+            SecretPolicy::Ignore => vec![],
+            policy => {
+                let secrets = find_secrets(
+                    &cloup_path,
+                    &planned,
+                    &config.settings.allow_secrets,
+                    &encrypted,
+                    follow,
+                );
+                if policy == SecretPolicy::Abort && !secrets.is_empty() {
+                    fs::remove_dir_all(&cloup_path).map_err(CreateError::Error)?;
+                    return Err(CreateError::Secrets(secrets));
+                }
+                secrets
+            }
+        };
+
+        if !to_encrypt.is_empty() {
+            let sources: Vec<PathBuf> = to_encrypt
+                .iter()
+                .map(|(source, _)| source.clone())
+                .collect();
+            match passphrase(opts.key_file.as_deref(), true)
+                .and_then(|passphrase| seal_files(&sources, &passphrase))
+            {
+                Ok(sealed) => copy_options.contents = sealed,
+                Err(e) => {
+                    fs::remove_dir_all(&cloup_path).map_err(CreateError::Error)?;
+                    return Err(CreateError::EncryptionError(e));
+                }
+            }
         }
 
+        copy_options.dry_run = false;
+        let copied = match copy_files(&files, &copy_options, &cloup_path) {
+            Ok(copied) => copied.files,
+            Err(e) => {
+                fs::remove_dir_all(&cloup_path).map_err(CreateError::Error)?;
+                return Err(e);
+            }
+        };
+
         // if the created cloup's folder size is 0, remove it
         if cloup_path.read_dir().map_err(CreateError::Error)?.count() == 0 {
             fs::remove_dir_all(&cloup_path).map_err(CreateError::Error)?;
-            println!("No files to copy, removing cloup");
+
+            if output::is_json() {
+                output::json(
+                    Json::object()
+                        .with("cloup", &opts.name)
+                        .with("workspace", &workspace.name)
+                        .with("created", false)
+                        .with("files", Vec::<Json>::new()),
+                );
+            } else {
+                println!("No files to copy, removing cloup");
+            }
             return Ok(());
         }
 
-        println!(
-            "\x1b[1;32m»\x1b[0m Created cloup '{}' in workspace '{}'",
-            opts.name, workspace.name,
-        );
+        if let Err(e) = write_meta(&cloup_path, &copied, opts.preserve, &encrypted) {
+            fs::remove_dir_all(&cloup_path).map_err(CreateError::Error)?;
+            return Err(CreateError::FileError(e));
+        }
+
+        // workspaces with a store keep the contents of their cloups there,
+        // except for compressed cloups which have a single archive
+        let stored = if opts.compress {
+            compress(&cloup_path).map_err(CreateError::ArchiveError)
+        } else if is_dedup(&workspace.location) {
+            pack(&cloup_path).map_err(CreateError::StoreError)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = stored {
+            fs::remove_dir_all(&cloup_path).map_err(CreateError::Error)?;
+            return Err(e);
+        }
+
+        if output::is_json() {
+            output::json(
+                Json::object()
+                    .with("cloup", &opts.name)
+                    .with("workspace", &workspace.name)
+                    .with("created", true)
+                    .with("path", &cloup_path)
+                    .with("compressed", opts.compress)
+                    .with("files", copied.iter().map(Json::from).collect::<Vec<_>>())
+                    .with(
+                        "encrypted",
+                        encrypted.iter().map(Json::from).collect::<Vec<_>>(),
+                    )
+                    .with(
+                        "secrets",
+                        secrets.iter().map(Json::from).collect::<Vec<_>>(),
+                    ),
+            );
+        } else {
+            output::println(format!(
+                "\x1b[1;32m»\x1b[0m Created cloup '{}' in workspace '{}'",
+                opts.name, workspace.name,
+            ));
+
+            if !encrypted.is_empty() {
+                output::println(format!(
+                    "\x1b[1;32m»\x1b[0m Encrypted {} file(s)",
+                    encrypted.len()
+                ));
+            } else if !opts.encrypt.is_empty() {
+                output::eprintln("\x1b[1;33m»\x1b[0m No files matched --encrypt");
+            }
+
+            if !secrets.is_empty() {
+                output::eprintln(
+                    "\x1b[1;33m»\x1b[0m Stored files that look like they hold secrets:",
+                );
+                for secret in &secrets {
+                    output::eprintln(format!("    {}", secret));
+                }
+            }
+        }
 
         Ok(())
     } else {
@@ -91,41 +312,70 @@ pub fn run(opts: CreateOpts) -> Result<(), CreateError> {
 
 fn copy_files(
     files: &[PathBuf],
-    exclude: &[PathBuf],
+    copy_options: &CopyOptions,
     destination: &Path,
-) -> Result<(), CreateError> {
+) -> Result<Copied, CreateError> {
     if files.is_empty() {
-        copy_recursive(
+        return copy_recursive(
             &std::env::current_dir().map_err(CreateError::Error)?,
             destination,
-            exclude,
+            copy_options,
         )
-        .map_err(CreateError::FileError)?;
-        return Ok(());
+        .map_err(CreateError::FileError);
     }
 
+    let mut copied = Copied::default();
+
     for file in files {
         // if file is in exclude, skip
-        if exclude.contains(file) {
+        if is_excluded(file, &copy_options.exclude) {
             continue;
         }
 
-        let destination = destination.join(
-            file.file_name()
-                .ok_or(CreateError::Error(std::io::ErrorKind::InvalidInput.into()))?,
-        );
-
-        println!("\x1b[1;32mCopying {:?} to {:?}\x1b[0m", file, &destination);
+        let name = file
+            .file_name()
+            .ok_or(CreateError::Error(std::io::ErrorKind::InvalidInput.into()))?;
+        let destination = destination.join(escape_name(name, &copy_options.escape));
 
-        if file.is_dir() {
-            fs::create_dir_all(&destination).map_err(CreateError::Error)?;
-            copy_recursive(file, &destination, exclude).map_err(CreateError::FileError)?;
-        } else {
-            fs::copy(file, &destination).map_err(CreateError::Error)?;
+        if !output::is_json() && !copy_options.dry_run {
+            output::println(format!(
+                "\x1b[1;32mCopying {:?} to {:?}\x1b[0m",
+                file, &destination
+            ));
         }
+
+        let file_copied =
+            copy_path(file, &destination, copy_options).map_err(CreateError::FileError)?;
+        copied.files.extend(file_copied.files);
+        copied.sources.extend(file_copied.sources);
+        copied.skipped.extend(file_copied.skipped);
     }
 
-    Ok(())
+    Ok(copied)
+}
+
+/// Record what the storage of the workspace may lose, so apply can restore
+/// it, and which files apply has to decrypt
+fn write_meta(
+    cloup_path: &Path,
+    copied: &[PathBuf],
+    preserve: bool,
+    encrypted: &[PathBuf],
+) -> Result<(), FileError> {
+    keep_empty_dirs(cloup_path)?;
+
+    let mut meta = if preserve {
+        CloupMeta::from_files(cloup_path, copied)?
+    } else {
+        CloupMeta::default()
+    };
+    meta.encrypted = encrypted
+        .iter()
+        .filter_map(|file| file.strip_prefix(cloup_path).ok())
+        .map(Path::to_path_buf)
+        .collect();
+
+    meta.write(cloup_path)
 }
 
 fn find_workspace<'a>(opts: &'a CreateOpts, workspaces: &'a [Workspace]) -> Option<&'a Workspace> {
@@ -0,0 +1,560 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    dirs,
+    toml::data::{Toml, TomlValueKind},
+    utils::{
+        archive::{is_compressed, read_entries},
+        config::{
+            config_path, config_version, get_config, update_config, ConfigError, CONFIG_VERSION,
+            WORKSPACE_FILENAME,
+        },
+        error::{ErrorKind, Report},
+        file::{is_excluded, is_writable},
+        json::Json,
+        name::{validate_name, CloupName, NameKind},
+        output,
+        settings::{read_settings, validate, Settings},
+        store::{is_packed, missing, STORE_DIR},
+    },
+};
+
+#[derive(Debug)]
+pub enum DoctorError {
+    Problems(usize),
+    ConfigError(ConfigError),
+}
+
+impl std::fmt::Display for DoctorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DoctorError::Problems(count) => write!(f, "{} problem(s) need attention", count),
+            DoctorError::ConfigError(e) => write!(f, "Config error: {}", e),
+        }
+    }
+}
+impl std::error::Error for DoctorError {}
+
+impl Report for DoctorError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DoctorError::Problems(_) => ErrorKind::Config,
+            DoctorError::ConfigError(e) => e.kind(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DoctorOpts {
+    // Repair what can be repaired safely
+    pub fix: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+    Fixed,
+}
+
+/// Repairs that never lose anything
+#[derive(Debug)]
+enum Fix {
+    CreateDir(PathBuf),
+    Migrate,
+    ResetActiveWorkspace(String),
+    PruneSearchPath(Vec<String>),
+    RemoveFile(PathBuf),
+}
+
+#[derive(Debug)]
+struct Check {
+    status: Status,
+    message: String,
+    fix: Option<Fix>,
+}
+
+impl Check {
+    fn ok(message: impl Into<String>) -> Self {
+        Check {
+            status: Status::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Check {
+            status: Status::Warning,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Check {
+            status: Status::Error,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+pub fn run(opts: DoctorOpts) -> Result<(), DoctorError> {
+    // Cloup.toml is read as is, get_config would create or migrate it
+    let config_path = config_path().map_err(DoctorError::ConfigError)?;
+    let mut checks = check_config_dir(&config_path);
+
+    if let Some(toml) = checks
+        .last()
+        .filter(|c| c.status == Status::Ok)
+        .and_then(|_| fs::read_to_string(&config_path).ok().map(Toml::from))
+    {
+        let settings = check_config(&toml, &mut checks);
+        for (name, location) in workspaces(&toml) {
+            check_workspace(&name, &location, &settings.exclude, &mut checks);
+        }
+    }
+
+    if opts.fix {
+        for check in checks.iter_mut().filter(|c| c.fix.is_some()) {
+            if let Some(fix) = check.fix.take() {
+                apply_fix(&config_path, fix).map_err(DoctorError::ConfigError)?;
+                check.status = Status::Fixed;
+            }
+        }
+    }
+
+    let count = |status| checks.iter().filter(|c| c.status == status).count();
+    let (errors, warnings, fixed) = (
+        count(Status::Error),
+        count(Status::Warning),
+        count(Status::Fixed),
+    );
+    let fixable = checks.iter().filter(|c| c.fix.is_some()).count();
+
+    if output::is_json() {
+        output::json(
+            Json::object()
+                .with("config", &config_path)
+                .with(
+                    "checks",
+                    checks
+                        .iter()
+                        .map(|c| {
+                            Json::object()
+                                .with("status", status_name(c.status))
+                                .with("message", &c.message)
+                                .with("fixable", c.fix.is_some())
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .with("errors", errors)
+                .with("warnings", warnings)
+                .with("fixed", fixed),
+        );
+    } else {
+        output::println(format!(
+            "\x1b[1;32m»\x1b[0m Checking {}",
+            config_path.to_string_lossy()
+        ));
+        for check in &checks {
+            let mark = match check.status {
+                Status::Ok | Status::Fixed => "\x1b[1;32m✓\x1b[0m",
+                Status::Warning => "\x1b[1;33m!\x1b[0m",
+                Status::Error => "\x1b[1;31m✗\x1b[0m",
+            };
+            let note = match (check.status, &check.fix) {
+                (Status::Fixed, _) => " \x1b[2m(fixed)\x1b[0m",
+                (_, Some(_)) => " \x1b[2m(fixable)\x1b[0m",
+                _ => "",
+            };
+            output::println(format!("    {} {}{}", mark, check.message, note));
+        }
+
+        output::println(format!(
+            "\n\x1b[1m{} error(s), {} warning(s)\x1b[0m{}",
+            errors,
+            warnings,
+            if fixed > 0 {
+                format!(", {} fixed", fixed)
+            } else {
+                String::new()
+            }
+        ));
+        if fixable > 0 {
+            output::println(format!(
+                "Run 'cloup doctor --fix' to repair {} of them",
+                fixable
+            ));
+        }
+    }
+
+    if errors > 0 {
+        return Err(DoctorError::Problems(errors));
+    }
+
+    Ok(())
+}
+
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Ok => "ok",
+        Status::Warning => "warning",
+        Status::Error => "error",
+        Status::Fixed => "fixed",
+    }
+}
+
+/// Check the platform config directory and the directory holding Cloup.toml,
+/// the last check being ok only if Cloup.toml can be read
+fn check_config_dir(config_path: &Path) -> Vec<Check> {
+    let mut checks = vec![];
+
+    match dirs::config_dir() {
+        Ok(dir) if dir.is_dir() => checks.push(Check::ok(format!(
+            "Config directory {} exists",
+            dir.to_string_lossy()
+        ))),
+        Ok(dir) => checks.push(
+            Check::warning(format!(
+                "Config directory {} does not exist",
+                dir.to_string_lossy()
+            ))
+            .fix(Fix::CreateDir(dir)),
+        ),
+        Err(e) => checks.push(Check::error(format!("Config directory not found: {}", e))),
+    }
+
+    let Some(dir) = config_path.parent() else {
+        checks.push(Check::error("Config path has no parent directory"));
+        return checks;
+    };
+
+    // leftovers of writes that were interrupted
+    if let Ok(entries) = fs::read_dir(dir) {
+        let name = config_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        for entry in entries.flatten() {
+            let entry_name = entry.file_name().to_string_lossy().to_string();
+            if entry_name.starts_with(&format!("{}.", name)) && entry_name.ends_with(".tmp") {
+                checks.push(
+                    Check::warning(format!("Leftover temporary file {}", entry_name))
+                        .fix(Fix::RemoveFile(entry.path())),
+                );
+            }
+        }
+    }
+
+    match fs::read_to_string(config_path) {
+        Ok(_) => checks.push(Check::ok("Cloup.toml can be read")),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            checks.push(Check::error("Cloup.toml does not exist, run 'cloup init'"))
+        }
+        Err(e) => checks.push(Check::error(format!("Cloup.toml can't be read: {}", e))),
+    }
+
+    checks
+}
+
+/// Check the contents of Cloup.toml, returning the settings to use for the
+/// rest of the checks
+fn check_config(toml: &Toml, checks: &mut Vec<Check>) -> Settings {
+    if toml.data.is_empty() {
+        checks.push(Check::error("Cloup.toml is empty or can't be parsed"));
+        return Settings::default();
+    }
+
+    match config_version(toml) {
+        Ok(version) if version == CONFIG_VERSION => {
+            checks.push(Check::ok(format!("Config version {}", version)))
+        }
+        Ok(version) => checks.push(
+            Check::warning(format!(
+                "Config version {} is older than {}",
+                version, CONFIG_VERSION
+            ))
+            .fix(Fix::Migrate),
+        ),
+        Err(e) => checks.push(Check::error(e.to_string())),
+    }
+
+    let settings = match validate(toml).and_then(|_| read_settings(toml)) {
+        Ok(settings) => {
+            checks.push(Check::ok("Settings are valid"));
+            settings
+        }
+        Err(e) => {
+            checks.push(Check::error(e.to_string()));
+            Settings::default()
+        }
+    };
+
+    let names: Vec<String> = workspaces(toml).into_iter().map(|(name, _)| name).collect();
+    match toml.get("workspaces") {
+        Some(TomlValueKind::Table(key_values)) => {
+            for kv in key_values {
+                if let Err(e) = validate_name(NameKind::Workspace, &kv.key) {
+                    checks.push(Check::warning(e.to_string()));
+                }
+                if !matches!(kv.kind, TomlValueKind::String(_)) {
+                    checks.push(Check::error(format!(
+                        "Location of workspace '{}' is not a string",
+                        kv.key
+                    )));
+                }
+            }
+        }
+        _ => checks.push(Check::error("Cloup.toml has no [workspaces] table")),
+    }
+
+    match toml.get("active_workspace") {
+        Some(TomlValueKind::String(name)) if names.contains(name) => {
+            checks.push(Check::ok(format!("Active workspace '{}' exists", name)))
+        }
+        active => {
+            let message = match active {
+                Some(TomlValueKind::String(name)) => {
+                    format!("Active workspace '{}' does not exist", name)
+                }
+                _ => "No active workspace is set".to_string(),
+            };
+
+            // prefer the workspace created by init
+            let replacement = names
+                .iter()
+                .find(|n| *n == "default")
+                .or(names.first())
+                .cloned();
+            checks.push(match replacement {
+                Some(name) => Check::error(message).fix(Fix::ResetActiveWorkspace(name)),
+                None => Check::error(message),
+            });
+        }
+    }
+
+    if let Some(TomlValueKind::Array(values)) = toml.get("search_path") {
+        let unknown: Vec<String> = values
+            .iter()
+            .filter_map(|v| match v {
+                TomlValueKind::String(name) if !names.contains(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+
+        if !unknown.is_empty() {
+            checks.push(
+                Check::warning(format!(
+                    "Search path lists unknown workspaces: {}",
+                    unknown.join(", ")
+                ))
+                .fix(Fix::PruneSearchPath(unknown)),
+            );
+        }
+    }
+
+    settings
+}
+
+/// Registered workspaces with a valid location
+fn workspaces(toml: &Toml) -> Vec<(String, PathBuf)> {
+    match toml.get("workspaces") {
+        Some(TomlValueKind::Table(key_values)) => key_values
+            .iter()
+            .filter_map(|kv| match &kv.kind {
+                TomlValueKind::String(location) => Some((kv.key.clone(), PathBuf::from(location))),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn check_workspace(name: &str, location: &Path, exclude: &[String], checks: &mut Vec<Check>) {
+    let entries = match fs::read_dir(location) {
+        Ok(entries) => entries,
+        Err(_) if !location.exists() => {
+            checks.push(
+                Check::error(format!(
+                    "Location of workspace '{}' does not exist ({})",
+                    name,
+                    location.to_string_lossy()
+                ))
+                .fix(Fix::CreateDir(location.to_path_buf())),
+            );
+            return;
+        }
+        Err(e) => {
+            checks.push(Check::error(format!(
+                "Location of workspace '{}' can't be read: {}",
+                name, e
+            )));
+            return;
+        }
+    };
+
+    if !is_writable(location) {
+        checks.push(Check::error(format!(
+            "Location of workspace '{}' is not writable",
+            name
+        )));
+    }
+
+    let mut cloups = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        if is_excluded(&path, exclude) || file_name == WORKSPACE_FILENAME {
+            continue;
+        }
+        if file_name == STORE_DIR && path.is_dir() {
+            continue;
+        }
+
+        match file_name.strip_prefix("cl_") {
+            Some(cloup) if !cloup.is_empty() && path.is_dir() => {
+                cloups += 1;
+                if let Err(e) = CloupName::new(cloup) {
+                    checks.push(Check::warning(format!("{} (in workspace '{}')", e, name)));
+                }
+                if is_packed(&path) {
+                    check_packed(name, cloup, &path, checks);
+                } else if is_compressed(&path) {
+                    if let Err(e) = read_entries(&path) {
+                        checks.push(Check::error(format!(
+                            "Cloup '{}/{}' can't be read: {}",
+                            name, cloup, e
+                        )));
+                    }
+                } else {
+                    check_cloup(name, cloup, &path, &path, checks);
+                }
+            }
+            _ => checks.push(Check::warning(format!(
+                "'{}' in workspace '{}' is not a cloup, cloups are cl_<name> directories",
+                file_name, name
+            ))),
+        }
+    }
+
+    checks.push(Check::ok(format!(
+        "Workspace '{}' ({} cloup(s))",
+        name, cloups
+    )));
+}
+
+/// Look for contents of a cloup kept in the store that are gone from it
+fn check_packed(workspace: &str, cloup: &str, cloup_path: &Path, checks: &mut Vec<Check>) {
+    match missing(cloup_path) {
+        Ok(paths) => {
+            for path in paths {
+                checks.push(Check::error(format!(
+                    "The content of '{}' in cloup '{}/{}' is missing from the store",
+                    path.to_string_lossy(),
+                    workspace,
+                    cloup
+                )));
+            }
+        }
+        Err(e) => checks.push(Check::error(format!(
+            "Cloup '{}/{}' can't be read: {}",
+            workspace, cloup, e
+        ))),
+    }
+}
+
+/// Look for broken symlinks and unreadable files in a cloup
+fn check_cloup(workspace: &str, cloup: &str, root: &Path, dir: &Path, checks: &mut Vec<Check>) {
+    let relative = |path: &Path| {
+        path.strip_prefix(root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    };
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            checks.push(Check::error(format!(
+                "Directory '{}' of cloup '{}/{}' can't be read: {}",
+                relative(dir),
+                workspace,
+                cloup,
+                e
+            )));
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+
+        if metadata.file_type().is_symlink() {
+            if fs::metadata(&path).is_err() {
+                checks.push(Check::warning(format!(
+                    "Broken symlink '{}' in cloup '{}/{}'",
+                    relative(&path),
+                    workspace,
+                    cloup
+                )));
+            }
+        } else if metadata.is_dir() {
+            check_cloup(workspace, cloup, root, &path, checks);
+        } else if let Err(e) = fs::File::open(&path) {
+            checks.push(Check::error(format!(
+                "File '{}' of cloup '{}/{}' can't be read: {}",
+                relative(&path),
+                workspace,
+                cloup,
+                e
+            )));
+        }
+    }
+}
+
+fn apply_fix(config_path: &Path, fix: Fix) -> Result<(), ConfigError> {
+    match fix {
+        Fix::CreateDir(dir) => {
+            fs::create_dir_all(&dir).map_err(|e| ConfigError::Write(dir, e))?;
+        }
+        // reading the config migrates it, keeping a backup
+        Fix::Migrate => {
+            get_config()?;
+        }
+        Fix::ResetActiveWorkspace(name) => {
+            update_config(config_path, |toml| {
+                toml.set("active_workspace".to_string(), TomlValueKind::String(name))
+            })?;
+        }
+        Fix::PruneSearchPath(unknown) => {
+            update_config(config_path, |toml| {
+                if let Some(TomlValueKind::Array(values)) = toml.get_mut("search_path") {
+                    values
+                        .retain(|v| !matches!(v, TomlValueKind::String(n) if unknown.contains(n)));
+                }
+                Ok(())
+            })?;
+        }
+        Fix::RemoveFile(path) => {
+            fs::remove_file(&path).map_err(|e| ConfigError::Write(path, e))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::{
+    archive::{self, is_compressed, ArchiveError},
+    bundle::write_bundle,
+    config::{get_config, ConfigError},
+    error::{ErrorKind, Report},
+    json::Json,
+    name::{CloupName, NameError},
+    output,
+    store::{is_packed, Checkout, StoreError},
+};
+
+#[derive(Debug)]
+pub enum ExportError {
+    NotFound,
+    CloupNotFound(String, String, PathBuf),
+    InvalidName(NameError),
+    ConfigError(ConfigError),
+    StoreError(StoreError),
+    ArchiveError(ArchiveError),
+    Error(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExportError::NotFound => write!(f, "Workspace not found"),
+            ExportError::CloupNotFound(name, workspace, _) => write!(
+                f,
+                "Cloup '{}' does not exist in workspace '{}'",
+                name, workspace
+            ),
+            ExportError::InvalidName(e) => write!(f, "{}", e),
+            ExportError::ConfigError(e) => write!(f, "Config error: {}", e),
+            ExportError::StoreError(e) => write!(f, "{}", e),
+            ExportError::ArchiveError(e) => write!(f, "{}", e),
+            ExportError::Error(path, _) => {
+                write!(f, "Could not read or write '{}'", path.to_string_lossy())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExportError::StoreError(e) => e.source(),
+            ExportError::ArchiveError(e) => e.source(),
+            ExportError::Error(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Report for ExportError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ExportError::NotFound | ExportError::CloupNotFound(..) => ErrorKind::NotFound,
+            ExportError::InvalidName(e) => e.kind(),
+            ExportError::ConfigError(e) => e.kind(),
+            ExportError::StoreError(e) => e.kind(),
+            ExportError::ArchiveError(e) => e.kind(),
+            ExportError::Error(..) => ErrorKind::Io,
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            ExportError::CloupNotFound(_, _, path) | ExportError::Error(path, _) => Some(path),
+            ExportError::StoreError(e) => e.path(),
+            ExportError::ArchiveError(e) => e.path(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ExportOpts {
+    // Name of the cloup to export
+    pub name: String,
+
+    // Archive to write, defaults to <name>.tar.gz in the current directory
+    pub output: Option<PathBuf>,
+
+    // Workspace of the cloup, defaults to the active one
+    pub workspace: Option<String>,
+
+    // Description shown when the cloup is imported
+    pub description: Option<String>,
+}
+
+pub fn run(opts: ExportOpts) -> Result<(), ExportError> {
+    let config = get_config().map_err(ExportError::ConfigError)?;
+
+    let workspace = config
+        .data
+        .workspaces
+        .iter()
+        .find(|w| match &opts.workspace {
+            Some(name) => &w.name == name,
+            None => w.active,
+        })
+        .ok_or(ExportError::NotFound)?;
+
+    let name = CloupName::new(&opts.name).map_err(ExportError::InvalidName)?;
+    let cloup_path = workspace.location.join(name.dir_name());
+    if !cloup_path.is_dir() {
+        return Err(ExportError::CloupNotFound(
+            name.to_string(),
+            workspace.name.clone(),
+            cloup_path,
+        ));
+    }
+
+    // the files are exported as stored, encrypted files stay encrypted
+    let checkout = if is_compressed(&cloup_path) {
+        Some(archive::checkout(&cloup_path).map_err(ExportError::ArchiveError)?)
+    } else if is_packed(&cloup_path) {
+        Some(Checkout::new(&cloup_path).map_err(ExportError::StoreError)?)
+    } else {
+        None
+    };
+    let source = checkout.as_ref().map_or(&cloup_path, |c| &c.path);
+
+    let data = write_bundle(name.as_str(), opts.description.as_deref(), source)
+        .map_err(|e| ExportError::Error(source.clone(), e))?;
+
+    let path = config.current_dir.join(
+        opts.output
+            .unwrap_or_else(|| PathBuf::from(format!("{}.tar.gz", name))),
+    );
+    fs::write(&path, &data).map_err(|e| ExportError::Error(path.clone(), e))?;
+
+    if output::is_json() {
+        output::json(
+            Json::object()
+                .with("name", name.as_str())
+                .with("workspace", &workspace.name)
+                .with("path", &path)
+                .with("size", data.len() as u64),
+        );
+        return Ok(());
+    }
+
+    output::println(format!(
+        "\x1b[1;32m»\x1b[0m Exported cloup '{}' to {}",
+        name,
+        path.to_string_lossy()
+    ));
+
+    Ok(())
+}
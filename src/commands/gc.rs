@@ -0,0 +1,121 @@
+use std::path::Path;
+
+use crate::{
+    commands::list::format_size,
+    utils::{
+        config::{get_config, ConfigError},
+        error::{ErrorKind, Report},
+        json::Json,
+        output,
+        store::{collect_garbage, is_dedup, StoreError},
+    },
+};
+
+#[derive(Debug)]
+pub enum GcError {
+    NotFound,
+    ConfigError(ConfigError),
+    StoreError(StoreError),
+}
+
+impl std::fmt::Display for GcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GcError::NotFound => write!(f, "Workspace not found"),
+            GcError::ConfigError(e) => write!(f, "Config error: {}", e),
+            GcError::StoreError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for GcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GcError::StoreError(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl Report for GcError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            GcError::NotFound => ErrorKind::NotFound,
+            GcError::ConfigError(e) => e.kind(),
+            GcError::StoreError(e) => e.kind(),
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            GcError::StoreError(e) => e.path(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GcOpts {
+    // Workspace to clean up, defaults to the active one
+    pub workspace: Option<String>,
+
+    // Only report what would be removed
+    pub dry_run: bool,
+}
+
+pub fn run(opts: GcOpts) -> Result<(), GcError> {
+    let config = get_config().map_err(GcError::ConfigError)?;
+
+    let workspace = config
+        .data
+        .workspaces
+        .iter()
+        .find(|w| match &opts.workspace {
+            Some(name) => &w.name == name,
+            None => w.active,
+        })
+        .ok_or(GcError::NotFound)?;
+
+    let garbage =
+        collect_garbage(&workspace.location, opts.dry_run).map_err(GcError::StoreError)?;
+
+    if output::is_json() {
+        output::json(
+            Json::object()
+                .with("workspace", &workspace.name)
+                .with("dry_run", opts.dry_run)
+                .with("blobs", garbage.blobs)
+                .with("bytes", garbage.bytes)
+                .with("archives", garbage.archives),
+        );
+        return Ok(());
+    }
+
+    let verb = if opts.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
+    };
+    if is_dedup(&workspace.location) {
+        output::println(format!(
+            "\x1b[1;32m»\x1b[0m {} {} unreferenced blob(s) ({}) and {} partial archive(s) from workspace '{}'",
+            verb,
+            garbage.blobs,
+            format_size(garbage.bytes),
+            garbage.archives,
+            workspace.name
+        ));
+    } else if garbage.archives > 0 {
+        output::println(format!(
+            "\x1b[1;32m»\x1b[0m {} {} partial archive(s) from workspace '{}'",
+            verb, garbage.archives, workspace.name
+        ));
+    } else {
+        output::println(format!(
+            "\x1b[1;33m»\x1b[0m Workspace '{}' doesn't use the store, nothing to collect",
+            workspace.name
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::{
+    bundle::{read_bundle, Bundle, BundleError},
+    config::{get_config, ConfigError, Workspace},
+    error::{ErrorKind, Report},
+    json::Json,
+    name::{CloupName, NameError},
+    output,
+    store::{is_dedup, pack, StoreError},
+    tar::{extract, TarKind},
+};
+
+#[derive(Debug)]
+pub enum ImportError {
+    NotFound,
+    NameExists(String, String, PathBuf),
+    InvalidName(NameError),
+    ConfigError(ConfigError),
+    StoreError(StoreError),
+    BundleError(PathBuf, BundleError),
+    Error(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImportError::NotFound => write!(f, "Workspace not found"),
+            ImportError::NameExists(name, workspace, _) => write!(
+                f,
+                "Cloup '{}' already exists in workspace '{}', use --as to import it under another name",
+                name, workspace
+            ),
+            ImportError::InvalidName(e) => write!(f, "{}", e),
+            ImportError::ConfigError(e) => write!(f, "Config error: {}", e),
+            ImportError::StoreError(e) => write!(f, "{}", e),
+            ImportError::BundleError(path, e) => {
+                write!(f, "'{}' can't be imported: {}", path.to_string_lossy(), e)
+            }
+            ImportError::Error(path, _) => {
+                write!(f, "Could not read or write '{}'", path.to_string_lossy())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::StoreError(e) => e.source(),
+            ImportError::Error(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Report for ImportError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ImportError::NotFound => ErrorKind::NotFound,
+            ImportError::NameExists(..) => ErrorKind::Conflict,
+            ImportError::InvalidName(e) => e.kind(),
+            ImportError::ConfigError(e) => e.kind(),
+            ImportError::StoreError(e) => e.kind(),
+            ImportError::BundleError(..) | ImportError::Error(..) => ErrorKind::Io,
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            ImportError::NameExists(_, _, path)
+            | ImportError::BundleError(path, _)
+            | ImportError::Error(path, _) => Some(path),
+            ImportError::StoreError(e) => e.path(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ImportOpts {
+    // Archive made by cloup export
+    pub file: PathBuf,
+
+    // Name to import the cloup under, defaults to the name it was exported with
+    pub name: Option<String>,
+
+    // Workspace to import the cloup into, defaults to the active one
+    pub workspace: Option<String>,
+}
+
+pub fn run(opts: ImportOpts) -> Result<(), ImportError> {
+    let config = get_config().map_err(ImportError::ConfigError)?;
+
+    let workspace = config
+        .data
+        .workspaces
+        .iter()
+        .find(|w| match &opts.workspace {
+            Some(name) => &w.name == name,
+            None => w.active,
+        })
+        .ok_or(ImportError::NotFound)?;
+
+    let file = config.current_dir.join(&opts.file);
+    let (name, bundle) = import(&file, opts.name.as_deref(), workspace)?;
+
+    if output::is_json() {
+        output::json(
+            Json::object()
+                .with("name", name.as_str())
+                .with("workspace", &workspace.name)
+                .with("description", bundle.description)
+                .with(
+                    "files",
+                    bundle
+                        .entries
+                        .iter()
+                        .filter(|e| e.kind == TarKind::File)
+                        .count(),
+                ),
+        );
+        return Ok(());
+    }
+
+    output::println(format!(
+        "\x1b[1;32m»\x1b[0m Imported cloup '{}' into workspace '{}'",
+        name, workspace.name
+    ));
+    if let Some(description) = bundle.description {
+        output::println(format!("  {}", description));
+    }
+
+    Ok(())
+}
+
+/// Check the archive and unpack it as a new cloup of the workspace, under the
+/// name it was exported with unless another one is given
+fn import(
+    file: &Path,
+    name: Option<&str>,
+    workspace: &Workspace,
+) -> Result<(CloupName, Bundle), ImportError> {
+    let data = fs::read(file).map_err(|e| ImportError::Error(file.to_path_buf(), e))?;
+    let bundle = read_bundle(&data).map_err(|e| ImportError::BundleError(file.to_path_buf(), e))?;
+
+    let name = name.unwrap_or(&bundle.name);
+    let name = CloupName::new(name).map_err(ImportError::InvalidName)?;
+    let cloup_path = workspace.location.join(name.dir_name());
+    if cloup_path.exists() {
+        return Err(ImportError::NameExists(
+            name.to_string(),
+            workspace.name.clone(),
+            cloup_path,
+        ));
+    }
+
+    // a partly imported cloup is removed rather than left behind
+    let imported = fs::create_dir(&cloup_path)
+        .and_then(|_| extract(&bundle.entries, &cloup_path))
+        .map_err(|e| ImportError::Error(cloup_path.clone(), e))
+        .and_then(|_| {
+            if is_dedup(&workspace.location) {
+                pack(&cloup_path).map_err(ImportError::StoreError)
+            } else {
+                Ok(())
+            }
+        });
+    if let Err(e) = imported {
+        let _ = fs::remove_dir_all(&cloup_path);
+        return Err(e);
+    }
+
+    Ok((name, bundle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{
+        bundle::write_bundle,
+        testing::{write_tree, TempDir},
+    };
+
+    // damaged on the way, the archive is refused and nothing is imported
+    #[test]
+    fn damaged_archives() {
+        let dir = TempDir::new("import");
+        write_tree(
+            &dir,
+            &[("cl_api/src/main.rs", "fn main() {}\n"), ("workspace/", "")],
+        );
+        let workspace = Workspace {
+            name: "default".to_string(),
+            location: dir.join("workspace"),
+            active: true,
+            local: false,
+        };
+        let data = write_bundle("api", None, &dir.join("cl_api")).unwrap();
+        let file = dir.join("api.tar.gz");
+
+        let mut damaged = vec![data[..data.len() / 2].to_vec(), data[..20].to_vec()];
+        for at in [3, 12, data.len() / 2, data.len() - 5] {
+            let mut corrupt = data.clone();
+            corrupt[at] ^= 0x40;
+            damaged.push(corrupt);
+        }
+        for data in damaged {
+            fs::write(&file, data).unwrap();
+            assert!(matches!(
+                import(&file, None, &workspace),
+                Err(ImportError::BundleError(..))
+            ));
+            assert_eq!(fs::read_dir(&workspace.location).unwrap().count(), 0);
+        }
+
+        fs::write(&file, &data).unwrap();
+        let (name, _) = import(&file, None, &workspace).unwrap();
+        assert_eq!(name.as_str(), "api");
+        assert!(workspace.location.join("cl_api/src/main.rs").is_file());
+    }
+}
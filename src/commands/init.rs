@@ -1,25 +1,42 @@
 use crate::{
-    toml::data::{TomlValue, TomlValueKind},
-    utils::config::{get_config, ConfigError},
+    toml::data::TomlValueKind,
+    utils::{
+        config::{get_config, update_config, ConfigError},
+        error::{ErrorKind, Report},
+        json::Json,
+        name::{validate_name, NameError, NameKind},
+        output,
+    },
 };
-use std::{fs, io};
 
 #[derive(Debug)]
 pub enum InitError {
-    Error(io::Error),
+    NoActiveWorkspace,
+    InvalidName(NameError),
     ConfigError(ConfigError),
 }
 
 impl std::fmt::Display for InitError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            InitError::Error(e) => write!(f, "Error: {}", e),
+            InitError::NoActiveWorkspace => write!(f, "Active workspace does not exist"),
+            InitError::InvalidName(e) => write!(f, "{}", e),
             InitError::ConfigError(e) => write!(f, "Config error: {}", e),
         }
     }
 }
 impl std::error::Error for InitError {}
 
+impl Report for InitError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            InitError::NoActiveWorkspace => ErrorKind::Config,
+            InitError::InvalidName(e) => e.kind(),
+            InitError::ConfigError(e) => e.kind(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct InitOpts {
     // Overwrite path
@@ -31,95 +48,129 @@ pub struct InitOpts {
 
 pub fn run(opts: InitOpts) -> Result<(), InitError> {
     let mut config = get_config().map_err(InitError::ConfigError)?;
-    let mut toml = config.toml;
+    let toml = config.toml;
 
     // unless user is making a custom workspace
     if config.initial_run && opts.workspace.is_none() {
-        println!(
-            "\x1b[1;32m»\x1b[0m Created new workspace for storing cloups: default ({}).",
-            config.current_dir.to_string_lossy()
-        );
+        if output::is_json() {
+            output::json(
+                Json::object()
+                    .with("workspace", "default")
+                    .with("location", &config.current_dir)
+                    .with("created", true),
+            );
+        } else {
+            output::println(format!(
+                "\x1b[1;32m»\x1b[0m Created new workspace for storing cloups: default ({}).",
+                config.current_dir.to_string_lossy()
+            ));
+        }
         return Ok(());
     }
 
-    // todo: don't panic, send error back
     let active_workspace = config
         .data
         .find(|w| w.active)
-        .expect("Active workspace does not exist");
+        .ok_or(InitError::NoActiveWorkspace)?;
 
     if opts.workspace.is_none() {
         if !opts.overwrite {
-            println!(
-                "\x1b[1;33m»\x1b[0m Overwrite existing location? (\x1b[1m{}: {}\x1b[0m)\n\nPass the '-o' flag to overwrite",
-                active_workspace.name,
-                active_workspace.location.to_string_lossy(),
-            );
+            if output::is_json() {
+                output::json(
+                    Json::object()
+                        .with("workspace", &active_workspace.name)
+                        .with("location", &active_workspace.location)
+                        .with("updated", false),
+                );
+            } else {
+                output::println(format!(
+                    "\x1b[1;33m»\x1b[0m Overwrite existing location? (\x1b[1m{}: {}\x1b[0m)\n\nPass the '-o' flag to overwrite",
+                    active_workspace.name,
+                    active_workspace.location.to_string_lossy(),
+                ));
+            }
             return Ok(());
         }
 
-        if let Some(TomlValueKind::Table(key_values)) = toml.get_mut("workspaces") {
-            key_values
-                .iter_mut()
-                .find(|w| w.key == active_workspace.name)
-                .map(|w| {
-                    if let TomlValueKind::String(value) = &mut w.kind {
-                        *value = config.current_dir.to_string_lossy().to_string();
-                    }
-                    w
-                });
+        let location = TomlValueKind::String(config.current_dir.to_string_lossy().to_string());
+        update_config(&config.config_path, |toml| {
+            toml.set_in("workspaces", active_workspace.name.clone(), location)
+        })
+        .map_err(InitError::ConfigError)?;
+
+        if output::is_json() {
+            output::json(
+                Json::object()
+                    .with("workspace", &active_workspace.name)
+                    .with("location", &config.current_dir)
+                    .with("updated", true),
+            );
+        } else {
+            output::println(format!(
+                "\x1b[1;32m»\x1b[0m New location for workspace {}: ({})",
+                active_workspace.name,
+                config.current_dir.to_string_lossy()
+            ));
         }
 
-        println!(
-            "\x1b[1;32m»\x1b[0m New location for workspace {}: ({})",
-            active_workspace.name,
-            config.current_dir.to_string_lossy()
-        );
-
-        return fs::write(config.config_path, toml.to_toml()).map_err(InitError::Error);
+        return Ok(());
     }
 
     // workspace MUST have been passed
     let name = opts.workspace.unwrap();
-    if let Some(TomlValueKind::Table(key_values)) = toml.get_mut("workspaces") {
-        let workspace = key_values.iter_mut().find(|w| w.key == name);
-
-        // check if workspace exists
-        // if does NOT exist, prompt to CREATE new workspace
-        // if does exist, "to overwrite htis..."
-
-        // create workspace (it doesn't exist)
-        if workspace.is_none() {
-            println!(
-                "\x1b[1;32m»\x1b[0m Created new workspace for storing cloups: {name}\n\nTo change to this workspace, use 'cloup workspace {name}'",
-            );
-
-            key_values.push(TomlValue {
-                key: name.clone(),
-                kind: TomlValueKind::String(config.current_dir.to_string_lossy().to_string()),
-            });
-
-            return fs::write(&config.config_path, toml.to_toml()).map_err(InitError::Error);
-        };
-
-        // overwrite existing workspace
-        let workspace = workspace.unwrap();
-        if !opts.overwrite {
-            if let TomlValueKind::String(value) = &workspace.kind {
-                println!(
+    let Some(TomlValueKind::Table(key_values)) = toml.get("workspaces") else {
+        return Err(InitError::ConfigError(ConfigError::KeyMissing));
+    };
+
+    // check if workspace exists
+    // if does NOT exist, create it
+    // if does exist, only overwrite its location with -o
+    let existing = key_values.iter().find(|w| w.key == name);
+    if let (Some(workspace), false) = (existing, opts.overwrite) {
+        if let TomlValueKind::String(value) = &workspace.kind {
+            if output::is_json() {
+                output::json(
+                    Json::object()
+                        .with("workspace", &name)
+                        .with("location", value)
+                        .with("updated", false),
+                );
+            } else {
+                output::println(format!(
                     "\x1b[1;33m»\x1b[0m Overwrite current location? (\x1b[1m{}: {}\x1b[0m)\n\nPass the '-o' flag to overwrite",
                     name,
                     value,
-                );
+                ));
             }
-
-            return Ok(());
-        } else if let TomlValueKind::String(value) = &mut workspace.kind {
-            *value = config.current_dir.to_string_lossy().to_string();
         }
-    } else {
-        return Err(InitError::ConfigError(ConfigError::KeyMissing));
+
+        return Ok(());
+    }
+
+    let created = existing.is_none();
+    if created {
+        validate_name(NameKind::Workspace, &name).map_err(InitError::InvalidName)?;
+    }
+    let location = TomlValueKind::String(config.current_dir.to_string_lossy().to_string());
+    update_config(&config.config_path, |toml| {
+        toml.set_in("workspaces", name.clone(), location)
+    })
+    .map_err(InitError::ConfigError)?;
+
+    if output::is_json() {
+        let json = Json::object()
+            .with("workspace", &name)
+            .with("location", &config.current_dir);
+        output::json(if created {
+            json.with("created", true)
+        } else {
+            json.with("updated", true)
+        });
+    } else if created {
+        output::println(format!(
+            "\x1b[1;32m»\x1b[0m Created new workspace for storing cloups: {name}\n\nTo change to this workspace, use 'cloup workspace {name}'",
+        ));
     }
 
-    fs::write(&config.config_path, toml.to_toml()).map_err(InitError::Error)
+    Ok(())
 }
@@ -1,82 +1,254 @@
-use std::{fs, ops::Not, path::Path};
+use std::{
+    fs,
+    ops::Not,
+    path::{Path, PathBuf},
+};
 
-use crate::utils::config::{get_config, ConfigError};
+use crate::utils::{
+    archive::is_compressed,
+    config::{get_config, ConfigError, Workspace},
+    error::{ErrorKind, Report},
+    json::Json,
+    output,
+    store::{is_packed, packed_size},
+};
 
 #[derive(Debug)]
 pub enum ListError {
+    NoActiveWorkspace,
+    Error(PathBuf, std::io::Error),
     ConfigError(ConfigError),
 }
 
 impl std::fmt::Display for ListError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
+            ListError::NoActiveWorkspace => write!(f, "No active workspace"),
+            ListError::Error(_, e) => write!(f, "Error: {}", e),
             ListError::ConfigError(e) => write!(f, "Config error: {}", e),
         }
     }
 }
 impl std::error::Error for ListError {}
 
-pub fn run() -> Result<(), ListError> {
+impl Report for ListError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ListError::NoActiveWorkspace => ErrorKind::NotFound,
+            ListError::Error(..) => ErrorKind::Io,
+            ListError::ConfigError(e) => e.kind(),
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            ListError::Error(path, _) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ListOpts {
+    // List the cloups of every workspace
+    pub all: bool,
+}
+
+/// Cloups of a workspace as listed, along with whether each one is shadowed
+/// by a cloup with the same name earlier in the search path
+struct Listing<'a> {
+    workspace: &'a Workspace,
+    searched: bool,
+    cloups: Vec<(String, bool)>,
+}
+
+pub fn run(opts: ListOpts) -> Result<(), ListError> {
     let config = get_config().map_err(ListError::ConfigError)?;
 
-    let workspace = config
+    let active = config
         .data
         .workspaces
         .iter()
         .find(|w| w.active)
-        .expect("Must have an active workspace");
+        .ok_or(ListError::NoActiveWorkspace)?;
+    let search_order = config.data.search_order();
 
-    let cloups = std::fs::read_dir(&workspace.location)
-        .expect("Could not read directory")
+    // workspaces outside of the search path come last
+    let workspaces: Vec<&Workspace> = if opts.all {
+        search_order
+            .iter()
+            .copied()
+            .chain(
+                config
+                    .data
+                    .workspaces
+                    .iter()
+                    .filter(|w| !search_order.iter().any(|s| s.name == w.name)),
+            )
+            .collect()
+    } else {
+        vec![active]
+    };
+
+    let mut seen: Vec<String> = vec![];
+    let mut listings = vec![];
+    for workspace in workspaces {
+        let searched = search_order.iter().any(|w| w.name == workspace.name);
+        let names = match cloup_names(&workspace.location) {
+            Ok(names) => names,
+            // a broken workspace shouldn't hide the others
+            Err(_) if opts.all => vec![],
+            Err(e) => return Err(ListError::Error(workspace.location.clone(), e)),
+        };
+
+        let cloups = names
+            .into_iter()
+            .map(|name| {
+                let shadowed = searched && seen.contains(&name);
+                if searched {
+                    seen.push(name.clone());
+                }
+                (name, shadowed)
+            })
+            .collect();
+
+        listings.push(Listing {
+            workspace,
+            searched,
+            cloups,
+        });
+    }
+
+    if output::is_json() {
+        let json = |listing: &Listing| {
+            let cloups = listing
+                .cloups
+                .iter()
+                .map(|(cloup, shadowed)| {
+                    let cloup_path = listing.workspace.location.join(format!("cl_{}", cloup));
+                    let mut json = Json::object()
+                        .with("name", cloup)
+                        .with("size", calculate_size(&cloup_path))
+                        .with("compressed", is_compressed(&cloup_path))
+                        .with("path", &cloup_path);
+                    if opts.all {
+                        json = json.with("shadowed", *shadowed);
+                    }
+                    json
+                })
+                .collect::<Vec<_>>();
+
+            Json::object()
+                .with("workspace", &listing.workspace.name)
+                .with("location", &listing.workspace.location)
+                .with("cloups", cloups)
+        };
+
+        if opts.all {
+            output::json(
+                Json::object().with(
+                    "workspaces",
+                    listings
+                        .iter()
+                        .map(|l| json(l).with("searched", l.searched))
+                        .collect::<Vec<_>>(),
+                ),
+            );
+        } else {
+            output::json(json(&listings[0]));
+        }
+
+        return Ok(());
+    }
+
+    for listing in &listings {
+        print_listing(listing);
+    }
+
+    Ok(())
+}
+
+fn print_listing(listing: &Listing) {
+    let name = &listing.workspace.name;
+    let note = if listing.searched {
+        ""
+    } else {
+        " \x1b[2m(not searched)\x1b[0m"
+    };
+
+    if listing.cloups.is_empty() {
+        output::println(format!(
+            "\x1b[1;33m»\x1b[0m No cloups in workspace '{}'{}",
+            name, note
+        ));
+        return;
+    }
+
+    output::println(format!(
+        "\x1b[1;32m»\x1b[0m Cloups in workspace '{}'{}:",
+        name, note
+    ));
+
+    for (i, (cloup, shadowed)) in listing.cloups.iter().enumerate() {
+        let cloup_path = listing.workspace.location.join(format!("cl_{}", cloup));
+        let mut size_str = format_size(calculate_size(&cloup_path));
+        if is_compressed(&cloup_path) {
+            size_str.push_str(", compressed");
+        }
+
+        let prefix = if i == listing.cloups.len() - 1 {
+            "    └── "
+        } else {
+            "    ├── "
+        };
+
+        let shadowed = if *shadowed {
+            format!(" \x1b[2mshadowed, use {}/{}\x1b[0m", name, cloup)
+        } else {
+            String::new()
+        };
+
+        output::println(format!(
+            "{}\x1b[1m{}\x1b[0m ({}){}",
+            prefix, cloup, size_str, shadowed
+        ));
+    }
+}
+
+/// Names of the cloups stored in a workspace location
+pub fn cloup_names(location: &Path) -> Result<Vec<String>, std::io::Error> {
+    let mut names: Vec<String> = fs::read_dir(location)?
         .filter_map(|entry| {
-            let entry = entry.expect("Could not read entry");
+            let entry = entry.ok()?;
 
-            if entry.file_name() == ".DS_Store"
-                || entry.file_name().to_string_lossy().starts_with("cl_").not()
-            {
+            if entry.file_name().to_string_lossy().starts_with("cl_").not() {
                 None
             } else {
-                Some(entry.file_name())
+                Some(entry.file_name().to_string_lossy().replacen("cl_", "", 1))
             }
         })
-        .collect::<Vec<_>>();
+        .collect();
 
-    if cloups.is_empty() {
-        println!(
-            "\x1b[1;33m»\x1b[0m No cloups in workspace '{}'",
-            workspace.name
-        )
-    } else {
-        println!(
-            "\x1b[1;32m»\x1b[0m Cloups in workspace '{}':",
-            workspace.name
-        );
-
-        for (i, cloup) in cloups.iter().enumerate() {
-            let cloup_path = workspace.location.join(cloup);
-            let cloup_name = cloup.to_string_lossy().replace("cl_", "");
-            let size_in_mb = calculate_size(&cloup_path) as f64 / 1_000_000.0;
-
-            let size_str = if size_in_mb > 1000.0 {
-                format!("{:.2} GB", size_in_mb / 1000.0)
-            } else {
-                format!("{:.2} MB", size_in_mb)
-            };
+    names.sort();
+    Ok(names)
+}
 
-            let prefix = if i == cloups.len() - 1 {
-                "    └── "
-            } else {
-                "    ├── "
-            };
+/// Human readable size, in MB or GB
+pub fn format_size(bytes: u64) -> String {
+    let size_in_mb = bytes as f64 / 1_000_000.0;
 
-            println!("{}\x1b[1m{}\x1b[0m ({})", prefix, cloup_name, size_str);
-        }
+    if size_in_mb > 1000.0 {
+        format!("{:.2} GB", size_in_mb / 1000.0)
+    } else {
+        format!("{:.2} MB", size_in_mb)
     }
-
-    Ok(())
 }
 
-fn calculate_size(path: &Path) -> u64 {
+pub fn calculate_size(path: &Path) -> u64 {
+    if is_packed(path) {
+        return packed_size(path);
+    }
+
     let mut total_size = 0;
 
     if let Ok(entries) = fs::read_dir(path) {
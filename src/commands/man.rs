@@ -0,0 +1,250 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::{
+    error::{ErrorKind, Report},
+    json::Json,
+    output,
+    parse::{command_usage, flag_usage, CommandSpec, FlagSpec, COMMANDS, GLOBAL_FLAGS},
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Environment variables read by cloup, in order of precedence for the config file
+const ENVIRONMENT: &[(&str, &str)] = &[
+    ("CLOUP_CONFIG", "Path of the config file to use instead of Cloup.toml in the config directory."),
+    ("CLOUP_HOME", "Directory holding Cloup.toml, used when CLOUP_CONFIG isn't set."),
+    ("XDG_CONFIG_HOME", "Config directory on Linux, cloup/Cloup.toml is read from it when neither of the above is set. Defaults to ~/.config."),
+    ("CLOUP_WORKSPACE", "Workspace to use instead of the active workspace, without changing the active workspace. The --workspace flag takes precedence."),
+    ("CLOUP_PASSPHRASE", "Passphrase of encrypted files, used by create and apply when --key-file isn't given instead of asking for it."),
+];
+
+#[derive(Debug)]
+pub enum ManError {
+    Error(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for ManError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ManError::Error(path, e) => write!(
+                f,
+                "Could not write man page '{}': {}",
+                path.to_string_lossy(),
+                e
+            ),
+        }
+    }
+}
+impl std::error::Error for ManError {}
+
+impl Report for ManError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Io
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            ManError::Error(path, _) => Some(path),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ManOpts {
+    // Command to print the man page of, cloup itself if empty
+    pub command: Option<&'static CommandSpec>,
+
+    // Directory to write every man page into
+    pub dir: Option<PathBuf>,
+}
+
+pub fn run(opts: ManOpts) -> Result<(), ManError> {
+    let Some(dir) = opts.dir else {
+        match opts.command {
+            Some(spec) => print!("{}", command_page(spec)),
+            None => print!("{}", main_page()),
+        }
+        return Ok(());
+    };
+
+    fs::create_dir_all(&dir).map_err(|e| ManError::Error(dir.clone(), e))?;
+
+    let mut pages = vec![(dir.join("cloup.1"), main_page())];
+    for spec in COMMANDS
+        .iter()
+        .flat_map(|c| std::iter::once(c).chain(c.subcommands))
+    {
+        pages.push((
+            dir.join(format!("{}.1", page_name(spec))),
+            command_page(spec),
+        ));
+    }
+
+    for (path, page) in &pages {
+        fs::write(path, page).map_err(|e| ManError::Error(path.clone(), e))?;
+    }
+
+    if output::is_json() {
+        output::json(
+            Json::object().with(
+                "pages",
+                pages
+                    .iter()
+                    .map(|(path, _)| Json::from(path))
+                    .collect::<Vec<_>>(),
+            ),
+        );
+    } else {
+        output::println(format!(
+            "\x1b[1;32m»\x1b[0m Wrote {} man pages to \x1b[1m{}\x1b[0m",
+            pages.len(),
+            dir.to_string_lossy()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Name of the man page of a command, e.g. `cloup-workspace-remove`
+fn page_name(spec: &CommandSpec) -> String {
+    format!("cloup-{}", spec.name.replace(' ', "-"))
+}
+
+/// Escape text for roff
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+
+    // A leading dot or quote would be read as a request
+    if text.starts_with('.') || text.starts_with('\'') {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
+
+/// Paragraphs of a long description as roff
+fn paragraphs(text: &str) -> String {
+    text.split("\n\n")
+        .map(|p| escape(&p.split_whitespace().collect::<Vec<_>>().join(" ")))
+        .collect::<Vec<_>>()
+        .join("\n.PP\n")
+}
+
+/// Flags as a roff tagged paragraph list
+fn options(flags: &[&FlagSpec]) -> String {
+    flags
+        .iter()
+        .map(|f| {
+            format!(
+                ".TP\n\\fB{}\\fR\n{}\n",
+                escape(&flag_usage(f)),
+                escape(f.help)
+            )
+        })
+        .collect()
+}
+
+fn header(name: &str, about: &str) -> String {
+    format!(
+        ".TH {} 1 \"\" \"cloup {}\" \"Cloup Manual\"\n.SH NAME\n{} \\- {}\n",
+        name.to_uppercase(),
+        VERSION,
+        escape(name),
+        escape(about)
+    )
+}
+
+/// Man page of cloup itself
+fn main_page() -> String {
+    let mut page = header("cloup", "Local template manager");
+
+    page.push_str(".SH SYNOPSIS\n\\fBcloup\\fR [command] [flags]\n");
+    page.push_str(".SH DESCRIPTION\n");
+    page.push_str(&paragraphs(
+        "Cloup is a template manager that delivers the files you desire when you need them. Files and directories are saved as cloups in a workspace, and applied to any directory with a single command.",
+    ));
+    page.push_str("\n.SH COMMANDS\n");
+    for spec in COMMANDS {
+        page.push_str(&format!(
+            ".TP\n\\fBcloup\\-{}\\fR(1)\n{}\n",
+            escape(spec.name),
+            escape(spec.about)
+        ));
+    }
+    page.push_str(".SH OPTIONS\n");
+    page.push_str(&options(&GLOBAL_FLAGS.iter().collect::<Vec<_>>()));
+    page.push_str(".SH ENVIRONMENT\n");
+    for (name, help) in ENVIRONMENT {
+        page.push_str(&format!(".TP\n\\fB{}\\fR\n{}\n", name, escape(help)));
+    }
+    page.push_str(".SH SEE ALSO\n");
+    page.push_str(
+        &COMMANDS
+            .iter()
+            .map(|s| format!("\\fBcloup\\-{}\\fR(1)", escape(s.name)))
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+    page.push('\n');
+
+    page
+}
+
+/// Man page of a command
+fn command_page(spec: &CommandSpec) -> String {
+    let mut page = header(&page_name(spec), spec.about);
+
+    let usage = command_usage(spec);
+    let (command, rest) = usage.split_at(format!("cloup {}", spec.name).len());
+    page.push_str(&format!(
+        ".SH SYNOPSIS\n\\fB{}\\fR{}\n",
+        escape(command),
+        escape(rest)
+    ));
+    page.push_str(&format!(
+        ".SH DESCRIPTION\n{}\n",
+        paragraphs(spec.long_about)
+    ));
+
+    if !spec.args.is_empty() {
+        page.push_str(".SH ARGUMENTS\n");
+        for arg in spec.args {
+            page.push_str(&format!(
+                ".TP\n\\fB<{}>\\fR\n{}\n",
+                escape(arg.name),
+                escape(arg.help)
+            ));
+        }
+    }
+
+    if !spec.subcommands.is_empty() {
+        page.push_str(".SH COMMANDS\n");
+        for subcommand in spec.subcommands {
+            page.push_str(&format!(
+                ".TP\n\\fB{}\\fR(1)\n{}\n",
+                escape(&page_name(subcommand)),
+                escape(subcommand.about)
+            ));
+        }
+    }
+
+    page.push_str(".SH OPTIONS\n");
+    page.push_str(&options(
+        &spec.flags.iter().chain(GLOBAL_FLAGS).collect::<Vec<_>>(),
+    ));
+
+    if !spec.examples.is_empty() {
+        page.push_str(".SH EXAMPLES\n.nf\n");
+        for example in spec.examples {
+            page.push_str(&format!("{}\n", escape(example)));
+        }
+        page.push_str(".fi\n");
+    }
+
+    page.push_str(".SH SEE ALSO\n\\fBcloup\\fR(1)\n");
+
+    page
+}
@@ -1,5 +1,13 @@
 pub mod apply;
+pub mod completions;
+pub mod compress;
+pub mod config;
 pub mod create;
+pub mod doctor;
+pub mod export;
+pub mod gc;
+pub mod import;
 pub mod init;
 pub mod list;
+pub mod man;
 pub mod workspace;
@@ -1,15 +1,41 @@
-use std::fs;
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
 
 use crate::{
-    toml::data::{TomlValue, TomlValueKind},
-    utils::config::{get_config, ConfigError},
+    commands::list::{calculate_size, cloup_names, format_size},
+    toml::data::TomlValueKind,
+    utils::{
+        archive::is_compressed,
+        config::{get_config, update_config, Config, ConfigError, Workspace, WORKSPACE_FILENAME},
+        error::{ErrorKind, Report},
+        file::{copy_recursive, is_excluded, is_writable, CopyOptions, FileError},
+        json::Json,
+        name::{validate_name, NameError, NameKind},
+        output,
+        store::{
+            is_dedup, is_packed, move_store, pack, unpack_in_place, Storage, StoreError, STORE_DIR,
+        },
+    },
 };
 
 #[derive(Debug)]
 pub enum WorkspaceError {
     NotFound,
+    Missing(String),
+    AlreadyExists(String),
+    Active(String),
+    NotEmpty(String, usize),
+    CloupExists(String, PathBuf),
+    SameLocation(String, PathBuf),
+    InsideWorkspace(String, PathBuf),
+    Local(String),
+    InvalidName(NameError),
     Error(std::io::Error),
     ConfigError(ConfigError),
+    FileError(FileError),
+    StoreError(StoreError),
 }
 
 impl std::fmt::Display for WorkspaceError {
@@ -18,12 +44,109 @@ impl std::fmt::Display for WorkspaceError {
             WorkspaceError::NotFound => {
                 write!(f, "Workspace not found. Use the '-c' flag to create it")
             }
+            WorkspaceError::Missing(name) => write!(f, "Workspace '{}' does not exist", name),
+            WorkspaceError::AlreadyExists(name) => {
+                write!(f, "Workspace '{}' already exists", name)
+            }
+            WorkspaceError::Active(name) => write!(
+                f,
+                "Workspace '{}' is the active workspace, switch to another workspace first",
+                name
+            ),
+            WorkspaceError::NotEmpty(name, count) => write!(
+                f,
+                "Workspace '{}' contains {} cloup(s), pass '--force' to remove it anyway",
+                name, count
+            ),
+            WorkspaceError::CloupExists(name, _) => {
+                write!(f, "Cloup '{}' already exists in the new location", name)
+            }
+            WorkspaceError::SameLocation(name, path) => write!(
+                f,
+                "Workspace '{}' is already in {}",
+                name,
+                path.to_string_lossy()
+            ),
+            WorkspaceError::InsideWorkspace(name, path) => write!(
+                f,
+                "{} is in workspace '{}', workspaces can't share a directory",
+                path.to_string_lossy(),
+                name
+            ),
+            WorkspaceError::Local(name) => write!(
+                f,
+                "Workspace '{}' is a project-local workspace, it isn't registered in Cloup.toml",
+                name
+            ),
+            WorkspaceError::InvalidName(e) => write!(f, "{}", e),
             WorkspaceError::Error(e) => write!(f, "Error: {}", e),
             WorkspaceError::ConfigError(e) => write!(f, "Config error: {}", e),
+            WorkspaceError::FileError(e) => write!(f, "File error: {}", e),
+            WorkspaceError::StoreError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for WorkspaceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WorkspaceError::FileError(e) => e.source(),
+            WorkspaceError::StoreError(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl Report for WorkspaceError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            WorkspaceError::NotFound | WorkspaceError::Missing(_) => ErrorKind::NotFound,
+            WorkspaceError::AlreadyExists(_)
+            | WorkspaceError::Active(_)
+            | WorkspaceError::NotEmpty(..)
+            | WorkspaceError::CloupExists(..)
+            | WorkspaceError::InsideWorkspace(..) => ErrorKind::Conflict,
+            WorkspaceError::SameLocation(..) | WorkspaceError::Local(_) => ErrorKind::Usage,
+            WorkspaceError::InvalidName(e) => e.kind(),
+            WorkspaceError::Error(_) => ErrorKind::Io,
+            WorkspaceError::ConfigError(e) => e.kind(),
+            WorkspaceError::FileError(e) => e.kind(),
+            WorkspaceError::StoreError(e) => e.kind(),
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            WorkspaceError::CloupExists(_, path)
+            | WorkspaceError::SameLocation(_, path)
+            | WorkspaceError::InsideWorkspace(_, path) => Some(path),
+            WorkspaceError::FileError(e) => e.path(),
+            WorkspaceError::StoreError(e) => e.path(),
+            _ => None,
         }
     }
 }
-impl std::error::Error for WorkspaceError {}
+
+#[derive(Debug)]
+pub enum WorkspaceAction {
+    Remove {
+        name: String,
+        force: bool,
+    },
+    Rename {
+        name: String,
+        new_name: String,
+    },
+    Move {
+        name: String,
+        location: PathBuf,
+        move_cloups: bool,
+    },
+    Storage {
+        name: String,
+        storage: Storage,
+    },
+}
 
 #[derive(Debug)]
 pub struct WorkspaceOpts {
@@ -35,42 +158,98 @@ pub struct WorkspaceOpts {
 
     // Name of workspace to set as active
     pub name: Option<String>,
+
+    // Remove, rename, move or convert a workspace instead
+    pub action: Option<WorkspaceAction>,
 }
 
 pub fn run(opts: WorkspaceOpts) -> Result<(), WorkspaceError> {
     let config = get_config().map_err(WorkspaceError::ConfigError)?;
-    let mut toml = config.toml;
+
+    if let Some(action) = opts.action {
+        return match action {
+            WorkspaceAction::Remove { name, force } => remove(config, &name, force),
+            WorkspaceAction::Rename { name, new_name } => rename(config, &name, &new_name),
+            WorkspaceAction::Move {
+                name,
+                location,
+                move_cloups,
+            } => relocate(config, &name, &location, move_cloups),
+            WorkspaceAction::Storage { name, storage } => convert(config, &name, storage),
+        };
+    }
 
     if opts.list {
+        if output::is_json() {
+            let workspaces = config
+                .data
+                .workspaces
+                .iter()
+                .map(|w| {
+                    Json::object()
+                        .with("name", &w.name)
+                        .with("location", &w.location)
+                        .with("active", w.active)
+                        .with("local", w.local)
+                })
+                .collect::<Vec<_>>();
+
+            output::json(Json::object().with("workspaces", workspaces));
+            return Ok(());
+        }
+
         config.data.workspaces.iter().for_each(|w| {
-            println!(
-                "{}{}: {}",
+            output::println(format!(
+                "{}{}: {}{}",
                 if w.active { "\x1b[1;33m»\x1b[0m " } else { "" },
                 w.name,
                 w.location.to_string_lossy(),
-            );
+                if w.local {
+                    " \x1b[2m(project)\x1b[0m"
+                } else {
+                    ""
+                },
+            ));
         });
         return Ok(());
     }
 
     if let Some(name) = opts.name {
-        if !config.data.workspaces.iter().any(|w| w.name == name) {
+        // project-local workspaces can't be activated, they're only found from the project
+        if let (false, Err(e @ WorkspaceError::Local(_))) =
+            (opts.create, find(&config.data.workspaces, &name))
+        {
+            return Err(e);
+        }
+
+        if !config
+            .data
+            .workspaces
+            .iter()
+            .any(|w| w.name == name && !w.local)
+        {
             // if not found, we _can_ create it
             if opts.create {
-                if let Some(TomlValueKind::Table(key_values)) = toml.get_mut("workspaces") {
-                    key_values.push(TomlValue {
-                        key: name.clone(),
-                        kind: TomlValueKind::String(
-                            config.current_dir.to_string_lossy().to_string(),
-                        ),
-                    });
+                validate_name(NameKind::Workspace, &name).map_err(WorkspaceError::InvalidName)?;
+                let location =
+                    TomlValueKind::String(config.current_dir.to_string_lossy().to_string());
+                update_config(&config.config_path, |toml| {
+                    toml.set_in("workspaces", name.clone(), location)
+                })
+                .map_err(WorkspaceError::ConfigError)?;
+                if output::is_json() {
+                    output::json(
+                        Json::object()
+                            .with("workspace", &name)
+                            .with("location", &config.current_dir)
+                            .with("created", true),
+                    );
+                } else {
+                    output::println(format!(
+                        "\x1b[1;33m»\x1b[0m Workspace \x1b[1;33m{}\x1b[0m created",
+                        name
+                    ));
                 }
-
-                fs::write(config.config_path, toml.to_toml()).map_err(WorkspaceError::Error)?;
-                println!(
-                    "\x1b[1;33m»\x1b[0m Workspace \x1b[1;33m{}\x1b[0m created",
-                    name
-                );
                 return Ok(());
             }
 
@@ -78,23 +257,436 @@ pub fn run(opts: WorkspaceOpts) -> Result<(), WorkspaceError> {
         }
 
         if opts.create {
-            println!(
-                "\x1b[1;33m»\x1b[0m Workspace \x1b[1;33m{}\x1b[0m already exists",
-                name
-            );
+            if output::is_json() {
+                output::json(
+                    Json::object()
+                        .with("workspace", &name)
+                        .with("created", false),
+                );
+            } else {
+                output::println(format!(
+                    "\x1b[1;33m»\x1b[0m Workspace \x1b[1;33m{}\x1b[0m already exists",
+                    name
+                ));
+            }
             return Ok(());
         }
 
-        if let Some(TomlValueKind::String(value)) = toml.get_mut("active_workspace") {
-            *value = name.clone();
+        update_config(&config.config_path, |toml| {
+            toml.set(
+                "active_workspace".to_string(),
+                TomlValueKind::String(name.clone()),
+            )
+        })
+        .map_err(WorkspaceError::ConfigError)?;
+        if output::is_json() {
+            output::json(Json::object().with("active_workspace", &name));
+        } else {
+            output::println(format!(
+                "\x1b[1;33m»\x1b[0m The active workspace has been set to \x1b[1;33m{}\x1b[0m",
+                name
+            ));
         }
+        return Ok(());
+    }
+
+    status(&config.data.workspaces, &config.settings.exclude)
+}
+
+/// Show the active workspace and report problems with its location
+fn status(workspaces: &[Workspace], exclude: &[String]) -> Result<(), WorkspaceError> {
+    let workspace = workspaces
+        .iter()
+        .find(|w| w.active)
+        .ok_or(WorkspaceError::NotFound)?;
+
+    let cloups = cloup_names(&workspace.location).unwrap_or_default();
+    let size: u64 = cloups
+        .iter()
+        .map(|c| calculate_size(&workspace.location.join(format!("cl_{}", c))))
+        .sum();
+    let problems = problems(&workspace.location, exclude);
+    let local = workspaces.iter().find(|w| w.local);
+    let storage = if is_dedup(&workspace.location) {
+        Storage::Dedup
+    } else {
+        Storage::Plain
+    };
 
-        fs::write(config.config_path, toml.to_toml()).map_err(WorkspaceError::Error)?;
+    if output::is_json() {
+        output::json(
+            Json::object()
+                .with("workspace", &workspace.name)
+                .with("location", &workspace.location)
+                .with("cloups", cloups.len())
+                .with("size", size)
+                .with("storage", storage.to_string())
+                .with("problems", problems)
+                .with(
+                    "project",
+                    local.map(|w| {
+                        Json::object()
+                            .with("workspace", &w.name)
+                            .with("location", &w.location)
+                    }),
+                ),
+        );
+        return Ok(());
+    }
+
+    output::println(format!(
+        "\x1b[1;33m»\x1b[0m Active workspace \x1b[1;33m{}\x1b[0m",
+        workspace.name
+    ));
+    println!("    location: {}", workspace.location.to_string_lossy());
+    println!("    cloups:   {} ({})", cloups.len(), format_size(size));
+    println!("    storage:  {}", storage);
+    if let Some(local) = local {
         println!(
-            "\x1b[1;33m»\x1b[0m The active workspace has been set to \x1b[1;33m{}\x1b[0m",
+            "    project:  {} ({})",
+            local.name,
+            local.location.to_string_lossy()
+        );
+    }
+
+    for problem in problems {
+        output::println(format!("\x1b[1;31m!\x1b[0m {}", problem));
+    }
+
+    Ok(())
+}
+
+/// Problems with a workspace location, empty if it's healthy
+fn problems(location: &Path, exclude: &[String]) -> Vec<String> {
+    let entries = match fs::read_dir(location) {
+        Ok(entries) => entries,
+        Err(_) if !location.exists() => return vec!["Location does not exist".to_string()],
+        Err(_) if !location.is_dir() => return vec!["Location is not a directory".to_string()],
+        Err(e) => return vec![format!("Location can't be read: {}", e)],
+    };
+
+    let mut problems = vec![];
+
+    if !is_writable(location) {
+        problems.push("Location is not writable".to_string());
+    }
+
+    let mut junk: Vec<String> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            !is_excluded(&entry.path(), exclude)
+                && name != WORKSPACE_FILENAME
+                && !((name == STORE_DIR || name.starts_with("cl_")) && entry.path().is_dir())
+        })
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    junk.sort();
+
+    if !junk.is_empty() {
+        problems.push(format!(
+            "Location contains files that aren't cloups: {}",
+            junk.join(", ")
+        ));
+    }
+
+    problems
+}
+
+/// Find a workspace registered in Cloup.toml
+fn find<'a>(workspaces: &'a [Workspace], name: &str) -> Result<&'a Workspace, WorkspaceError> {
+    match workspaces.iter().find(|w| w.name == name && !w.local) {
+        Some(workspace) => Ok(workspace),
+        None if workspaces.iter().any(|w| w.name == name) => {
+            Err(WorkspaceError::Local(name.to_string()))
+        }
+        None => Err(WorkspaceError::Missing(name.to_string())),
+    }
+}
+
+fn remove(config: Config, name: &str, force: bool) -> Result<(), WorkspaceError> {
+    let workspace = find(&config.data.workspaces, name)?;
+
+    // the active workspace would be left dangling, even if CLOUP_WORKSPACE overrides it
+    let saved_active =
+        matches!(config.toml.get("active_workspace"), Some(TomlValueKind::String(a)) if a == name);
+    if workspace.active || saved_active {
+        return Err(WorkspaceError::Active(name.to_string()));
+    }
+
+    // a missing location has no cloups to lose
+    let cloups = cloup_names(&workspace.location).unwrap_or_default();
+    if !cloups.is_empty() && !force {
+        return Err(WorkspaceError::NotEmpty(name.to_string(), cloups.len()));
+    }
+
+    update_config(&config.config_path, |toml| {
+        toml.delete_in("workspaces", name);
+        if let Some(TomlValueKind::Array(search_path)) = toml.get_mut("search_path") {
+            search_path.retain(|w| !matches!(w, TomlValueKind::String(w) if w == name));
+        }
+        Ok(())
+    })
+    .map_err(WorkspaceError::ConfigError)?;
+
+    if output::is_json() {
+        output::json(Json::object().with("workspace", name).with("removed", true));
+    } else {
+        output::println(format!(
+            "\x1b[1;32m»\x1b[0m Workspace \x1b[1m{}\x1b[0m removed, its files were left in place",
             name
+        ));
+    }
+
+    Ok(())
+}
+
+fn rename(config: Config, name: &str, new_name: &str) -> Result<(), WorkspaceError> {
+    find(&config.data.workspaces, name)?;
+    validate_name(NameKind::Workspace, new_name).map_err(WorkspaceError::InvalidName)?;
+    if config
+        .data
+        .workspaces
+        .iter()
+        .any(|w| w.name == new_name && !w.local)
+    {
+        return Err(WorkspaceError::AlreadyExists(new_name.to_string()));
+    }
+
+    update_config(&config.config_path, |toml| {
+        if let Some(TomlValueKind::Table(key_values)) = toml.get_mut("workspaces") {
+            if let Some(workspace) = key_values.iter_mut().find(|w| w.key == name) {
+                workspace.key = new_name.to_string();
+            }
+        }
+
+        if let Some(TomlValueKind::Array(search_path)) = toml.get_mut("search_path") {
+            for value in search_path {
+                if matches!(value, TomlValueKind::String(w) if w == name) {
+                    *value = TomlValueKind::String(new_name.to_string());
+                }
+            }
+        }
+
+        // keep the workspace active under its new name
+        if let Some(TomlValueKind::String(value)) = toml.get_mut("active_workspace") {
+            if value == name {
+                *value = new_name.to_string();
+            }
+        }
+
+        Ok(())
+    })
+    .map_err(WorkspaceError::ConfigError)?;
+
+    if output::is_json() {
+        output::json(
+            Json::object()
+                .with("workspace", new_name)
+                .with("previous_name", name),
         );
-        return Ok(());
+    } else {
+        output::println(format!(
+            "\x1b[1;32m»\x1b[0m Workspace \x1b[1m{}\x1b[0m renamed to \x1b[1m{}\x1b[0m",
+            name, new_name
+        ));
+    }
+
+    Ok(())
+}
+
+fn relocate(
+    config: Config,
+    name: &str,
+    location: &Path,
+    move_cloups: bool,
+) -> Result<(), WorkspaceError> {
+    let old_location = find(&config.data.workspaces, name)?.location.clone();
+    let location = config.current_dir.join(location);
+
+    let resolved = resolve(&location);
+    if resolved == resolve(&old_location) {
+        return Err(WorkspaceError::SameLocation(name.to_string(), location));
+    }
+    for workspace in config.data.workspaces.iter().filter(|w| w.name != name) {
+        if resolved.starts_with(resolve(&workspace.location)) {
+            return Err(WorkspaceError::InsideWorkspace(
+                workspace.name.clone(),
+                location,
+            ));
+        }
+    }
+
+    let cloups = if move_cloups && old_location.exists() {
+        cloup_names(&old_location).map_err(WorkspaceError::Error)?
+    } else {
+        vec![]
+    };
+
+    // check everything up front so we never stop halfway
+    for cloup in &cloups {
+        let destination = location.join(format!("cl_{}", cloup));
+        if destination.exists() {
+            return Err(WorkspaceError::CloupExists(cloup.clone(), destination));
+        }
+    }
+
+    fs::create_dir_all(&location).map_err(WorkspaceError::Error)?;
+
+    let mut moved = vec![];
+    for cloup in cloups {
+        let source = old_location.join(format!("cl_{}", cloup));
+        let destination = location.join(format!("cl_{}", cloup));
+        move_cloup(&source, &destination)?;
+        moved.push(cloup);
+    }
+
+    // cloups kept in the store need it in their new location
+    if move_cloups {
+        move_store(&old_location, &location).map_err(WorkspaceError::StoreError)?;
+    }
+
+    let value = TomlValueKind::String(location.to_string_lossy().to_string());
+    let updated = update_config(&config.config_path, |toml| {
+        toml.set_in("workspaces", name.to_string(), value)
+    });
+
+    // the workspace still points to its old location, put the cloups back there
+    if let Err(e) = updated {
+        if move_cloups {
+            let _ = move_store(&location, &old_location);
+        }
+        for cloup in moved.iter().rev() {
+            let _ = move_cloup(
+                &location.join(format!("cl_{}", cloup)),
+                &old_location.join(format!("cl_{}", cloup)),
+            );
+        }
+        return Err(WorkspaceError::ConfigError(e));
+    }
+
+    if output::is_json() {
+        output::json(
+            Json::object()
+                .with("workspace", name)
+                .with("location", &location)
+                .with("previous_location", &old_location)
+                .with("moved_cloups", moved),
+        );
+    } else {
+        output::println(format!(
+            "\x1b[1;32m»\x1b[0m Workspace \x1b[1m{}\x1b[0m moved to \x1b[1m{}\x1b[0m ({} cloup(s) moved)",
+            name,
+            location.to_string_lossy(),
+            moved.len()
+        ));
+    }
+
+    Ok(())
+}
+
+fn move_cloup(source: &Path, destination: &Path) -> Result<(), WorkspaceError> {
+    // renaming fails across filesystems, fall back to copying
+    if fs::rename(source, destination).is_err() {
+        fs::create_dir_all(destination).map_err(WorkspaceError::Error)?;
+        let copy_options = CopyOptions {
+            preserve_mtime: true,
+            ..CopyOptions::default()
+        };
+        copy_recursive(source, destination, &copy_options).map_err(WorkspaceError::FileError)?;
+        fs::remove_dir_all(source).map_err(WorkspaceError::Error)?;
+    }
+    Ok(())
+}
+
+/// Path with `.` and `..` removed and symlinks resolved as far as it
+/// exists, so locations that don't exist yet can be compared too
+fn resolve(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    let mut existing = normalized.as_path();
+    let mut rest = vec![];
+    while let (Err(_), Some(parent), Some(name)) = (
+        existing.canonicalize(),
+        existing.parent(),
+        existing.file_name(),
+    ) {
+        rest.push(name);
+        existing = parent;
+    }
+
+    let mut resolved = existing
+        .canonicalize()
+        .unwrap_or_else(|_| existing.to_path_buf());
+    resolved.extend(rest.iter().rev());
+    resolved
+}
+
+/// Switch a workspace between plain directories and the store, converting
+/// every cloup in it
+fn convert(config: Config, name: &str, storage: Storage) -> Result<(), WorkspaceError> {
+    // nothing is written to Cloup.toml, project-local workspaces can be converted too
+    let workspace = config
+        .data
+        .workspaces
+        .iter()
+        .find(|w| w.name == name)
+        .ok_or(WorkspaceError::Missing(name.to_string()))?;
+    let location = &workspace.location;
+    let store = location.join(STORE_DIR);
+
+    let cloups = cloup_names(location).map_err(WorkspaceError::Error)?;
+    let mut converted = vec![];
+
+    match storage {
+        Storage::Dedup => {
+            fs::create_dir_all(&store).map_err(WorkspaceError::Error)?;
+            for cloup in cloups {
+                let cloup_path = location.join(format!("cl_{}", cloup));
+                // compressed cloups stay a single archive
+                if !is_packed(&cloup_path) && !is_compressed(&cloup_path) {
+                    pack(&cloup_path).map_err(WorkspaceError::StoreError)?;
+                    converted.push(cloup);
+                }
+            }
+        }
+        Storage::Plain => {
+            for cloup in cloups {
+                let cloup_path = location.join(format!("cl_{}", cloup));
+                if is_packed(&cloup_path) {
+                    unpack_in_place(&cloup_path).map_err(WorkspaceError::StoreError)?;
+                    converted.push(cloup);
+                }
+            }
+            if store.exists() {
+                fs::remove_dir_all(&store).map_err(WorkspaceError::Error)?;
+            }
+        }
+    }
+
+    if output::is_json() {
+        output::json(
+            Json::object()
+                .with("workspace", name)
+                .with("storage", storage.to_string())
+                .with("converted", converted),
+        );
+    } else {
+        output::println(format!(
+            "\x1b[1;32m»\x1b[0m Workspace \x1b[1m{}\x1b[0m now uses {} storage ({} cloup(s) converted)",
+            name,
+            storage,
+            converted.len()
+        ));
     }
 
     Ok(())
@@ -3,17 +3,27 @@ use std::path::PathBuf;
 
 use crate::utils::config::ConfigError;
 
+/// Platform config directory. On Linux and other unix systems this is
+/// `$XDG_CONFIG_HOME`, falling back to `~/.config`.
 pub fn config_dir() -> Result<PathBuf, ConfigError> {
-    let path = home_dir()?;
-
     #[cfg(target_os = "macos")]
-    return Ok(path.join("Library/Application Support"));
+    return Ok(home_dir()?.join("Library/Application Support"));
 
     #[cfg(target_os = "windows")]
-    return Ok(path.join(r"AppData\Roaming"));
+    return Ok(home_dir()?.join(r"AppData\Roaming"));
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        // Relative paths are invalid per the XDG spec and must be ignored
+        if let Some(path) = var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+        {
+            return Ok(path);
+        }
 
-    #[cfg(target_os = "linux")]
-    return Ok(path.join(".config"));
+        Ok(home_dir()?.join(".config"))
+    }
 }
 
 fn home_dir() -> Result<PathBuf, ConfigError> {
@@ -36,6 +46,7 @@ fn home_dir() -> Result<PathBuf, ConfigError> {
 mod tests {
     use super::*;
 
+    #[cfg(target_os = "macos")]
     #[test]
     fn config_dir_mac() {
         let path = config_dir().expect(
@@ -45,4 +56,14 @@ mod tests {
             .to_string_lossy()
             .contains("/Library/Application Support"));
     }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn config_dir_xdg() {
+        let expected = match var_os("XDG_CONFIG_HOME").map(PathBuf::from) {
+            Some(path) if path.is_absolute() => path,
+            _ => home_dir().unwrap().join(".config"),
+        };
+        assert_eq!(config_dir().unwrap(), expected);
+    }
 }
@@ -3,28 +3,56 @@ mod dirs;
 mod toml;
 mod utils;
 
-use commands::{apply, create, init, list, workspace};
-use std::env;
-use utils::parse::{command_parser, Command};
+use commands::{
+    apply, completions, compress, config, create, doctor, export, gc, import, init, list, man,
+    workspace,
+};
+use std::{env, process::ExitCode};
+use utils::{
+    error::{self, Report},
+    output,
+    parse::{command_parser, output_format, version, Command},
+};
 
-fn run_app() -> Result<(), Box<dyn std::error::Error>> {
+fn run_app() -> Result<(), Box<dyn Report>> {
     let argv: Vec<String> = env::args().skip(1).collect();
 
+    output::set_format(output_format(&argv)?);
     let command = command_parser(argv)?;
 
     match command {
         Command::Init(opts) => init::run(opts)?,
         Command::Create(opts) => create::run(opts)?,
         Command::Apply(opts) => apply::run(opts)?,
-        Command::List() => list::run()?,
+        Command::List(opts) => list::run(opts)?,
         Command::Workspace(opts) => workspace::run(opts)?,
+        Command::Config(opts) => config::run(opts)?,
+        Command::Doctor(opts) => doctor::run(opts)?,
+        Command::Compress(opts) => compress::run(opts)?,
+        Command::Export(opts) => export::run(opts)?,
+        Command::Import(opts) => import::run(opts)?,
+        Command::Gc(opts) => gc::run(opts)?,
+        Command::Completions(opts) => completions::run(opts)?,
+        Command::Complete(words) => completions::complete(words),
+        Command::Man(opts) => man::run(opts)?,
+        Command::Help(help) => output::print(help),
+        Command::Version => output::println(version()),
     }
 
     Ok(())
 }
 
-fn main() {
-    if let Err(err) = run_app() {
-        eprintln!("{err}");
+fn main() -> ExitCode {
+    match run_app() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            if output::is_json() {
+                output::json_err(error::to_json(err.as_ref()));
+            } else {
+                output::eprintln(error::to_text(err.as_ref()));
+            }
+
+            ExitCode::from(err.kind().exit_code())
+        }
     }
 }
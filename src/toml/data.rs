@@ -5,6 +5,7 @@ pub enum TomlValueKind {
     String(String),
     Integer(i64),
     Boolean(bool),
+    Array(Vec<TomlValueKind>),
     Table(Vec<TomlValue>),
 }
 
@@ -14,6 +15,34 @@ pub struct TomlValue {
     pub kind: TomlValueKind,
 }
 
+#[derive(Debug)]
+pub enum TomlError {
+    InvalidKey(String),
+    NotATable(String),
+}
+
+impl std::fmt::Display for TomlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TomlError::InvalidKey(key) => write!(
+                f,
+                "Invalid key '{}', keys may only contain letters, digits, '_' and '-'",
+                key
+            ),
+            TomlError::NotATable(key) => write!(f, "'{}' is not a table", key),
+        }
+    }
+}
+impl std::error::Error for TomlError {}
+
+/// Check if a key can be written as a bare key
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
 #[derive(Debug)]
 pub struct Toml {
     pub data: Vec<TomlValue>,
@@ -37,11 +66,6 @@ impl From<Option<Vec<TomlValue>>> for Toml {
 }
 
 impl Toml {
-    /// Create a new Toml structure
-    pub fn new() -> Self {
-        Toml { data: vec![] }
-    }
-
     /// Get a value from the TOML file
     pub fn get(&self, key: &str) -> Option<&TomlValueKind> {
         self.data.iter().find(|v| v.key == key).map(|v| &v.kind)
@@ -55,11 +79,59 @@ impl Toml {
             .map(|v| &mut v.kind)
     }
 
+    /// Get a value from a table in the TOML file
+    pub fn get_in(&self, table: &str, key: &str) -> Option<&TomlValueKind> {
+        match self.get(table) {
+            Some(TomlValueKind::Table(key_values)) => {
+                key_values.iter().find(|v| v.key == key).map(|v| &v.kind)
+            }
+            _ => None,
+        }
+    }
+
+    /// Set a value in a table of the TOML file, creating the table if needed
+    pub fn set_in(
+        &mut self,
+        table: &str,
+        key: String,
+        kind: TomlValueKind,
+    ) -> Result<(), TomlError> {
+        if !is_valid_key(&key) {
+            return Err(TomlError::InvalidKey(key));
+        }
+
+        if self.get(table).is_none() {
+            self.set(table.to_string(), TomlValueKind::Table(vec![]))?;
+        }
+
+        let Some(TomlValueKind::Table(key_values)) = self.get_mut(table) else {
+            return Err(TomlError::NotATable(table.to_string()));
+        };
+
+        match key_values.iter_mut().find(|v| v.key == key) {
+            Some(value) => value.kind = kind,
+            None => key_values.push(TomlValue { key, kind }),
+        }
+
+        Ok(())
+    }
+
+    /// Delete a value from a table in the TOML file, returns whether it existed
+    pub fn delete_in(&mut self, table: &str, key: &str) -> bool {
+        match self.get_mut(table) {
+            Some(TomlValueKind::Table(key_values)) => {
+                let len = key_values.len();
+                key_values.retain(|v| v.key != key);
+                key_values.len() != len
+            }
+            _ => false,
+        }
+    }
+
     /// Set a value in the TOML file
-    pub fn set(&mut self, key: String, kind: TomlValueKind) {
-        // Check if key is alphanumeric
-        if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
-            panic!("Key must be alphanumeric: {}", key);
+    pub fn set(&mut self, key: String, kind: TomlValueKind) -> Result<(), TomlError> {
+        if !is_valid_key(&key) {
+            return Err(TomlError::InvalidKey(key));
         }
 
         let data = TomlValue { key, kind };
@@ -67,14 +139,26 @@ impl Toml {
         // If key exists, overwrite
         if let Some(index) = self.data.iter().position(|v| v.key == data.key) {
             self.data[index] = data;
-        } else {
+        } else if matches!(data.kind, TomlValueKind::Table(_)) {
             self.data.push(data)
+        } else {
+            // Keys written after a table would belong to it, keep them above
+            let index = self
+                .data
+                .iter()
+                .position(|v| matches!(v.kind, TomlValueKind::Table(_)))
+                .unwrap_or(self.data.len());
+            self.data.insert(index, data)
         }
+
+        Ok(())
     }
 
-    /// Delete a value from the TOML file
-    pub fn delete(&mut self, key: &str) {
+    /// Delete a value from the TOML file, returns whether it existed
+    pub fn delete(&mut self, key: &str) -> bool {
+        let len = self.data.len();
         self.data.retain(|v| v.key != key);
+        self.data.len() != len
     }
 
     /// Convert the TOML structure to a TOML string
@@ -83,13 +167,8 @@ impl Toml {
 
         for value in &self.data {
             match &value.kind {
-                TomlValueKind::String(val) => {
-                    toml.push_str(&format!("{} = \"{}\"\n", value.key, val))
-                }
-                TomlValueKind::Integer(val) => toml.push_str(&format!("{} = {}\n", value.key, val)),
-                TomlValueKind::Boolean(val) => toml.push_str(&format!("{} = {}\n", value.key, val)),
                 TomlValueKind::Table(val) => {
-                    if self.data.first().unwrap().key == value.key {
+                    if toml.is_empty() || toml.ends_with("\n\n") {
                         toml.push_str(&format!("[{}]\n", value.key));
                     } else {
                         toml.push_str(&format!("\n[{}]\n", value.key));
@@ -98,25 +177,92 @@ impl Toml {
                     let key_values = val.iter();
 
                     for kv in key_values {
-                        match &kv.kind {
-                            TomlValueKind::String(val) => {
-                                toml.push_str(&format!("{} = \"{}\"\n", kv.key, val))
-                            }
-                            TomlValueKind::Integer(val) => {
-                                toml.push_str(&format!("{} = {}\n", kv.key, val))
-                            }
-                            TomlValueKind::Boolean(val) => {
-                                toml.push_str(&format!("{} = {}\n", kv.key, val))
-                            }
-                            _ => (),
+                        if let Some(val) = Self::format_value(&kv.kind) {
+                            toml.push_str(&format!("{} = {}\n", kv.key, val))
                         }
                     }
 
                     toml.push('\n')
                 }
+                kind => {
+                    if let Some(val) = Self::format_value(kind) {
+                        toml.push_str(&format!("{} = {}\n", value.key, val))
+                    }
+                }
             }
         }
 
         format!("{}\n", toml.trim_end())
     }
+
+    /// Format a value as TOML, tables can't be nested so they're skipped
+    pub fn format_value(kind: &TomlValueKind) -> Option<String> {
+        match kind {
+            TomlValueKind::String(val) => Some(format!("\"{}\"", escape(val))),
+            TomlValueKind::Integer(val) => Some(val.to_string()),
+            TomlValueKind::Boolean(val) => Some(val.to_string()),
+            TomlValueKind::Array(values) => Some(format!(
+                "[{}]",
+                values
+                    .iter()
+                    .filter_map(Self::format_value)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            TomlValueKind::Table(_) => None,
+        }
+    }
+}
+
+/// Escape a value for a basic string, which can't hold control characters
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04X}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped_strings() {
+        let values = [
+            "C:\\cloups \"quoted\"",
+            "first line\nsecond line",
+            "tab\tcarriage\rreturn",
+            "bell\u{7} escape\u{1b} delete\u{7f}",
+            "unicode ✓ \\u0041",
+        ];
+        let toml = Toml::from(Some(
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| TomlValue {
+                    key: format!("value{}", i),
+                    kind: TomlValueKind::String(value.to_string()),
+                })
+                .collect(),
+        ));
+
+        let written = toml.to_toml();
+        assert_eq!(written.lines().count(), values.len());
+        let read = Toml::from(written);
+        for (i, value) in values.iter().enumerate() {
+            assert!(matches!(
+                read.get(&format!("value{}", i)),
+                Some(TomlValueKind::String(v)) if v == value
+            ));
+        }
+    }
 }
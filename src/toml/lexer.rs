@@ -8,6 +8,7 @@ use std::{iter::Peekable, str::Chars};
 pub enum TokenType {
     Key,
     Value,
+    String,
     Equals,
     Newline,
     LeftBracket,
@@ -22,9 +23,6 @@ pub struct Token {
 
 /// Tokenizer for the TOML format
 pub struct Lexer<'a> {
-    /// Source string to tokenize
-    input: &'a str,
-
     /// Current position in the input string (used while tokenizing)
     position: Peekable<Chars<'a>>,
 }
@@ -33,7 +31,6 @@ impl<'a> Lexer<'a> {
     /// Create a lexer for the given input string
     pub fn new(input: &'a str) -> Self {
         Lexer {
-            input,
             position: input.chars().peekable(),
         }
     }
@@ -57,7 +54,7 @@ impl<'a> Lexer<'a> {
 
     /// Check if character is a valid key character
     fn is_key_char(&self, c: char) -> bool {
-        c.is_ascii_alphanumeric() || c == '_'
+        c.is_ascii_alphanumeric() || c == '_' || c == '-'
     }
 
     /// Check if character is a valid value character
@@ -72,6 +69,57 @@ impl<'a> Lexer<'a> {
             || c == ':'
     }
 
+    /// Read a quoted string up to the closing quote, unescaping basic strings.
+    /// Unknown escapes are kept as they are.
+    fn read_string(&mut self, quote: char) -> String {
+        let mut value = String::new();
+
+        while let Some(c) = self.consume_char() {
+            if c == quote || self.is_newline(c) {
+                break;
+            }
+
+            if c == '\\' && quote == '"' {
+                if let Some(unescaped) = self.read_escape() {
+                    value.push(unescaped);
+                    continue;
+                }
+            }
+
+            value.push(c);
+        }
+
+        value
+    }
+
+    /// Read the character after a backslash, if it's an escape
+    fn read_escape(&mut self) -> Option<char> {
+        let unescaped = match self.peek_char()? {
+            'b' => '\u{8}',
+            't' => '\t',
+            'n' => '\n',
+            'f' => '\u{c}',
+            'r' => '\r',
+            c @ ('\\' | '"') => c,
+            c @ ('u' | 'U') => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let digits: String = self.position.clone().skip(1).take(len).collect();
+                let unescaped = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|_| digits.len() == len)
+                    .and_then(char::from_u32)?;
+                for _ in 0..len {
+                    self.consume_char();
+                }
+                unescaped
+            }
+            _ => return None,
+        };
+
+        self.consume_char();
+        Some(unescaped)
+    }
+
     /// Tokenize the input and return the next token
     pub fn next_token(&mut self) -> Option<Token> {
         while let Some(c) = self.consume_char() {
@@ -104,6 +152,11 @@ impl<'a> Lexer<'a> {
                     token_type,
                     value: c.to_string(),
                 });
+            } else if c == '"' || c == '\'' {
+                return Some(Token {
+                    token_type: TokenType::String,
+                    value: self.read_string(c),
+                });
             } else if self.is_key_char(c) {
                 let mut value = c.to_string();
 
@@ -42,18 +42,49 @@ impl Parser {
     fn parse_key(&mut self) -> Option<TomlValue> {
         let key = self.consume_token()?.value.clone();
         self.consume_token()?; // consume the equals sign
-        let value = self.consume_token()?.value.clone();
 
-        let kind = match value.parse::<i64>() {
+        let kind = if self.peek_token()?.token_type == TokenType::LeftBracket {
+            self.parse_array()
+        } else {
+            Self::parse_value(self.consume_token()?)
+        };
+
+        Some(TomlValue { key, kind })
+    }
+
+    /// Parse a single value, quoted values are always strings
+    fn parse_value(token: &Token) -> TomlValueKind {
+        if token.token_type == TokenType::String {
+            return TomlValueKind::String(token.value.clone());
+        }
+
+        match token.value.parse::<i64>() {
             Ok(int) => TomlValueKind::Integer(int),
-            Err(_) => match value.as_str() {
+            Err(_) => match token.value.as_str() {
                 "true" => TomlValueKind::Boolean(true),
                 "false" => TomlValueKind::Boolean(false),
-                _ => TomlValueKind::String(value),
+                _ => TomlValueKind::String(token.value.clone()),
             },
-        };
+        }
+    }
 
-        Some(TomlValue { key, kind })
+    /// Parse an array of values (per the TokenType::LeftBracket after an equals sign).
+    /// Arrays may span multiple lines, nested arrays aren't supported.
+    fn parse_array(&mut self) -> TomlValueKind {
+        self.consume_token(); // consume the left bracket
+
+        let mut values = vec![];
+        while let Some(token) = self.consume_token() {
+            match token.token_type {
+                TokenType::RightBracket => break,
+                TokenType::String | TokenType::Value | TokenType::Key => {
+                    values.push(Self::parse_value(token))
+                }
+                _ => (),
+            }
+        }
+
+        TomlValueKind::Array(values)
     }
 
     /// Parse a table (per the TokenType::LeftBracket)
@@ -143,5 +174,9 @@ mod tests {
         let toml = parser.parse();
 
         println!("{:#?}", toml.data);
+        assert!(matches!(
+            toml.get("search_path"),
+            Some(TomlValueKind::Array(values)) if values.len() == 2
+        ));
     }
 }
@@ -0,0 +1,228 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    crypto::{hex, random_bytes},
+    error::{ErrorKind, Report},
+    gzip::{gunzip, GzipWriter, MAX_INFLATED_SIZE},
+    store::Checkout,
+    tar::{extract, read_tar, write_tar_of, TarEntry},
+};
+
+/// File a compressed cloup is kept in, the only one in its `cl_<name>`
+/// directory
+pub const ARCHIVE_FILE: &str = ".cloup-archive.tar.gz";
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Malformed(PathBuf, String),
+    Error(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ArchiveError::Malformed(path, reason) => {
+                write!(f, "'{}' can't be read: {}", path.to_string_lossy(), reason)
+            }
+            ArchiveError::Error(path, _) => {
+                write!(f, "Could not read or write '{}'", path.to_string_lossy())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ArchiveError::Error(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Report for ArchiveError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Io
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            ArchiveError::Malformed(path, _) | ArchiveError::Error(path, _) => Some(path),
+        }
+    }
+}
+
+/// The cloup is kept as a compressed archive
+pub fn is_compressed(cloup_path: &Path) -> bool {
+    cloup_path.join(ARCHIVE_FILE).is_file()
+}
+
+/// Replace the files of a plain cloup with an archive of them
+pub fn compress(cloup_path: &Path) -> Result<(), ArchiveError> {
+    let error = |e| ArchiveError::Error(cloup_path.to_path_buf(), e);
+
+    // written next to its place first, an interrupted write loses nothing, and
+    // the files are only removed once the archive is sure to be on disk
+    let path = cloup_path.join(ARCHIVE_FILE);
+    let partial = cloup_path.join(format!("{}.{}.tmp", ARCHIVE_FILE, std::process::id()));
+    write_archive(cloup_path, &partial)
+        .and_then(|_| fs::rename(&partial, &path))
+        .and_then(|_| sync_dir(cloup_path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&partial);
+            ArchiveError::Error(path.clone(), e)
+        })?;
+
+    for entry in fs::read_dir(cloup_path).map_err(error)? {
+        let entry = entry.map_err(error)?.path();
+        if entry == path {
+            continue;
+        }
+
+        let is_dir = fs::symlink_metadata(&entry).is_ok_and(|m| m.is_dir());
+        let removed = if is_dir {
+            fs::remove_dir_all(&entry)
+        } else {
+            fs::remove_file(&entry)
+        };
+        removed.map_err(|e| ArchiveError::Error(entry, e))?;
+    }
+
+    Ok(())
+}
+
+/// Stream the archive of a cloup through gzip into `partial`, then flush it
+/// to disk
+fn write_archive(cloup_path: &Path, partial: &Path) -> std::io::Result<()> {
+    let mut gzip = GzipWriter::new(fs::File::create(partial)?);
+    write_tar_of(cloup_path, &mut gzip, is_partial_archive)?;
+
+    if gzip.size() > MAX_INFLATED_SIZE as u64 {
+        return Err(std::io::Error::other(format!(
+            "the cloup is larger than {} MiB, the most an archive can hold",
+            MAX_INFLATED_SIZE >> 20
+        )));
+    }
+    gzip.finish()?.sync_all()
+}
+
+/// Make a rename in a directory durable. Directories can't be opened as
+/// files on Windows, which doesn't need it.
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    if cfg!(unix) {
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Archive left behind by a `compress` that was interrupted, or one still
+/// being written
+pub fn is_partial_archive(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy();
+        name.strip_prefix(ARCHIVE_FILE)
+            .is_some_and(|rest| rest.starts_with('.') && rest.ends_with(".tmp"))
+    })
+}
+
+/// Entries of a compressed cloup, read straight from its archive
+pub fn read_entries(cloup_path: &Path) -> Result<Vec<TarEntry>, ArchiveError> {
+    let path = cloup_path.join(ARCHIVE_FILE);
+    let data = fs::read(&path).map_err(|e| ArchiveError::Error(path.clone(), e))?;
+
+    let tar = gunzip(&data).map_err(|e| ArchiveError::Malformed(path.clone(), e.to_string()))?;
+    read_tar(&tar).map_err(|e| ArchiveError::Malformed(path, e.to_string()))
+}
+
+/// Turn a compressed cloup back into a plain directory
+pub fn decompress(cloup_path: &Path) -> Result<(), ArchiveError> {
+    let entries = read_entries(cloup_path)?;
+    extract(&entries, cloup_path).map_err(|e| ArchiveError::Error(cloup_path.to_path_buf(), e))?;
+
+    let path = cloup_path.join(ARCHIVE_FILE);
+    fs::remove_file(&path).map_err(|e| ArchiveError::Error(path, e))
+}
+
+/// Extract a compressed cloup to a new temporary directory only the user can
+/// read, removed once the checkout is dropped
+pub fn checkout(cloup_path: &Path) -> Result<Checkout, ArchiveError> {
+    let entries = read_entries(cloup_path)?;
+
+    let path = private_dir(cloup_path).map_err(|e| ArchiveError::Error(std::env::temp_dir(), e))?;
+    let checkout = Checkout { path };
+    extract(&entries, &checkout.path).map_err(|e| ArchiveError::Error(checkout.path.clone(), e))?;
+    Ok(checkout)
+}
+
+/// Create a directory of the temporary directory under a name no one can
+/// guess, only the user can read, failing rather than reusing anything
+/// already there, such as a symlink planted by another user
+pub fn private_dir(cloup_path: &Path) -> std::io::Result<PathBuf> {
+    let name = cloup_path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = std::env::temp_dir();
+
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+    let mut attempts = 0;
+    loop {
+        let random = random_bytes::<8>()
+            .map_err(|_| std::io::Error::other("the random number generator couldn't be read"))?;
+        let path = temp.join(format!("cloup-{}-{}", name, hex(&random)));
+
+        match builder.create(&path) {
+            Ok(()) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempts < 10 => {
+                attempts += 1
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::store::collect_garbage;
+    use crate::utils::testing::{backdate, write_tree, TempDir};
+
+    #[test]
+    fn compressed_cloups() {
+        let dir = TempDir::new("archive");
+        let cloup = dir.join("cl_assets");
+        let font = "glyphs ".repeat(10_000);
+        write_tree(&cloup, &[("fonts/inter.woff", &font), ("empty/", "")]);
+
+        compress(&cloup).unwrap();
+        assert!(is_compressed(&cloup));
+        assert_eq!(fs::read_dir(&cloup).unwrap().count(), 1);
+        assert!(fs::metadata(cloup.join(ARCHIVE_FILE)).unwrap().len() < 1000);
+
+        {
+            let checkout = checkout(&cloup).unwrap();
+            assert_eq!(
+                fs::read_to_string(checkout.path.join("fonts").join("inter.woff")).unwrap(),
+                font
+            );
+            assert!(checkout.path.join("empty").is_dir());
+        }
+
+        decompress(&cloup).unwrap();
+        assert!(!is_compressed(&cloup));
+        assert!(cloup.join("fonts").join("inter.woff").is_file());
+
+        // left behind by an interrupted compress, removed once it's stale
+        let partial = cloup.join(format!("{}.1234.tmp", ARCHIVE_FILE));
+        assert!(is_partial_archive(&partial));
+        assert!(!is_partial_archive(&cloup.join(ARCHIVE_FILE)));
+        fs::write(&partial, "").unwrap();
+        assert_eq!(collect_garbage(&dir, false).unwrap().archives, 0);
+        backdate(&partial, std::time::Duration::from_secs(2 * 60 * 60));
+        assert_eq!(collect_garbage(&dir, false).unwrap().archives, 1);
+        assert!(!partial.exists());
+    }
+}
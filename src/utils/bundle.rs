@@ -0,0 +1,275 @@
+use std::path::{Path, PathBuf};
+
+use super::{
+    crypto::{hex, sha256},
+    gzip::{gunzip, gzip, GZIP_MAGIC},
+    store::{format_entry, parse_entry, Entry, EntryKind},
+    tar::{entries_of, read_tar, write_tar, TarEntry, TarKind},
+};
+use crate::toml::data::{Toml, TomlValue, TomlValueKind};
+
+/// Manifest at the root of an exported cloup
+pub const BUNDLE_MANIFEST: &str = "cloup.toml";
+
+/// Directory of an exported cloup holding its files, as stored in the workspace
+const FILES_DIR: &str = "files";
+
+/// Version of the export format, bumped when older versions can't read it
+const FORMAT: i64 = 1;
+
+#[derive(Debug)]
+pub enum BundleError {
+    /// Not an exported cloup, or a damaged one
+    Malformed(String),
+    /// A path that would be written outside of the cloup
+    UnsafePath(String),
+    /// A file that doesn't match its checksum, or isn't in the manifest
+    Checksum(String),
+    /// Exported by a newer version of cloup
+    Version(i64),
+}
+
+impl std::fmt::Display for BundleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BundleError::Malformed(reason) => write!(f, "not an exported cloup: {}", reason),
+            BundleError::UnsafePath(path) => {
+                write!(f, "'{}' would be written outside of the cloup", path)
+            }
+            BundleError::Checksum(path) => {
+                write!(f, "'{}' doesn't match the manifest of the archive", path)
+            }
+            BundleError::Version(version) => write!(
+                f,
+                "exported with format {}, this version of cloup reads format {}",
+                version, FORMAT
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+/// A cloup read from an export, its files checked against the manifest
+#[derive(Debug)]
+pub struct Bundle {
+    pub name: String,
+    pub description: Option<String>,
+
+    /// Files of the cloup, with paths relative to it
+    pub entries: Vec<TarEntry>,
+}
+
+/// Archive the files of a cloup, as stored in its workspace, along with a
+/// manifest of their checksums and modes
+pub fn write_bundle(
+    name: &str,
+    description: Option<&str>,
+    source: &Path,
+) -> std::io::Result<Vec<u8>> {
+    let mut entries = entries_of(source)?;
+
+    let manifest: Vec<TomlValueKind> = entries
+        .iter()
+        .map(|entry| TomlValueKind::String(format_entry(&manifest_entry(entry))))
+        .collect();
+
+    let mut toml = vec![
+        TomlValue {
+            key: "format".to_string(),
+            kind: TomlValueKind::Integer(FORMAT),
+        },
+        TomlValue {
+            key: "name".to_string(),
+            kind: TomlValueKind::String(name.to_string()),
+        },
+    ];
+    if let Some(description) = description {
+        // a single line, the manifest has no multi-line strings
+        toml.push(TomlValue {
+            key: "description".to_string(),
+            kind: TomlValueKind::String(description.lines().collect::<Vec<_>>().join(" ")),
+        });
+    }
+    toml.push(TomlValue {
+        key: "files".to_string(),
+        kind: TomlValueKind::Table(vec![TomlValue {
+            key: "entries".to_string(),
+            kind: TomlValueKind::Array(manifest),
+        }]),
+    });
+
+    for entry in &mut entries {
+        entry.path = format!("{}/{}", FILES_DIR, entry.path);
+    }
+    entries.insert(
+        0,
+        TarEntry {
+            path: BUNDLE_MANIFEST.to_string(),
+            kind: TarKind::File,
+            mode: 0o644,
+            mtime: 0,
+            data: Toml::from(Some(toml)).to_toml().into_bytes(),
+            link: String::new(),
+        },
+    );
+
+    Ok(gzip(&write_tar(&entries)))
+}
+
+/// Read an exported cloup. Nothing in it is trusted: paths leading outside
+/// of the cloup, files missing from the manifest and files that don't match
+/// their checksum are all refused.
+pub fn read_bundle(data: &[u8]) -> Result<Bundle, BundleError> {
+    // archives recompressed by hand, or not at all, are read too
+    let tar = if data.starts_with(GZIP_MAGIC) {
+        gunzip(data).map_err(|e| BundleError::Malformed(e.to_string()))?
+    } else {
+        data.to_vec()
+    };
+    let entries = read_tar(&tar).map_err(|e| match e {
+        crate::utils::tar::TarError::UnsafePath(path) => BundleError::UnsafePath(path),
+        e => BundleError::Malformed(e.to_string()),
+    })?;
+
+    let manifest = entries
+        .iter()
+        .find(|e| e.path == BUNDLE_MANIFEST && e.kind == TarKind::File)
+        .ok_or_else(|| BundleError::Malformed(format!("{} is missing", BUNDLE_MANIFEST)))?;
+    let toml = Toml::from(String::from_utf8_lossy(&manifest.data).to_string());
+
+    let format = match toml.get("format") {
+        Some(TomlValueKind::Integer(format)) => *format,
+        _ => return Err(BundleError::Malformed("the format is missing".to_string())),
+    };
+    if format > FORMAT {
+        return Err(BundleError::Version(format));
+    }
+
+    let name = match toml.get("name") {
+        Some(TomlValueKind::String(name)) => name.clone(),
+        _ => return Err(BundleError::Malformed("the name is missing".to_string())),
+    };
+    let description = match toml.get("description") {
+        Some(TomlValueKind::String(description)) => Some(description.clone()),
+        _ => None,
+    };
+
+    let mut expected = vec![];
+    if let Some(TomlValueKind::Array(values)) = toml.get_in("files", "entries") {
+        for value in values {
+            let TomlValueKind::String(line) = value else {
+                return Err(BundleError::Malformed("unreadable manifest".to_string()));
+            };
+            let entry = parse_entry(line).ok_or_else(|| match line.splitn(4, ' ').nth(3) {
+                Some(path) => BundleError::UnsafePath(path.to_string()),
+                None => BundleError::Malformed("unreadable manifest".to_string()),
+            })?;
+            expected.push(entry);
+        }
+    }
+
+    let prefix = format!("{}/", FILES_DIR);
+    let mut files = vec![];
+    for mut entry in entries {
+        if entry.path == BUNDLE_MANIFEST || entry.path == FILES_DIR {
+            continue;
+        }
+        let Some(path) = entry.path.strip_prefix(&prefix) else {
+            return Err(BundleError::Malformed(format!(
+                "'{}' is outside of {}/",
+                entry.path, FILES_DIR
+            )));
+        };
+        entry.path = path.to_string();
+
+        // the manifest has the final say on modes
+        let actual = manifest_entry(&entry);
+        match expected.iter().find(|e| e.path == actual.path) {
+            Some(e) if e.kind == actual.kind && e.hash == actual.hash => entry.mode = e.mode,
+            _ => return Err(BundleError::Checksum(entry.path)),
+        }
+        files.push(entry);
+    }
+
+    // files listed in the manifest but left out of the archive
+    for entry in &expected {
+        if !files.iter().any(|f| manifest_entry(f).path == entry.path) {
+            return Err(BundleError::Checksum(
+                entry.path.to_string_lossy().to_string(),
+            ));
+        }
+    }
+
+    Ok(Bundle {
+        name,
+        description,
+        entries: files,
+    })
+}
+
+/// Entry of the manifest for an entry of the archive, a symlink being
+/// checked by its target
+fn manifest_entry(entry: &TarEntry) -> Entry {
+    let (kind, hash) = match entry.kind {
+        TarKind::File => (EntryKind::File, hex(&sha256(&entry.data))),
+        TarKind::Symlink => (EntryKind::Link, hex(&sha256(entry.link.as_bytes()))),
+        TarKind::Dir => (EntryKind::Dir, "-".to_string()),
+    };
+
+    Entry {
+        kind,
+        mode: entry.mode,
+        hash,
+        path: entry.path.split('/').collect::<PathBuf>(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{write_tree, TempDir};
+
+    #[test]
+    fn bundles() {
+        let cloup = TempDir::new("bundle");
+        write_tree(&cloup, &[("src/main.rs", "fn main() {}\n")]);
+
+        let data = write_bundle("service", Some("A service\nskeleton"), &cloup).unwrap();
+        let bundle = read_bundle(&data).unwrap();
+        assert_eq!(bundle.name, "service");
+        assert_eq!(bundle.description.as_deref(), Some("A service skeleton"));
+        let paths: Vec<_> = bundle.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["src", "src/main.rs"]);
+
+        // a file changed after the export
+        let mut entries = read_tar(&gunzip(&data).unwrap()).unwrap();
+        entries
+            .iter_mut()
+            .find(|e| e.path == "files/src/main.rs")
+            .unwrap()
+            .data = b"fn main() { evil() }\n".to_vec();
+        assert!(matches!(
+            read_bundle(&write_tar(&entries)),
+            Err(BundleError::Checksum(_))
+        ));
+
+        // a manifest entry leading outside of the cloup
+        let manifest = format!(
+            "format = 1\nname = \"x\"\n[files]\nentries = [\"file 0644 {} ../evil\"]\n",
+            "0".repeat(64)
+        );
+        let archive = write_tar(&[TarEntry {
+            path: BUNDLE_MANIFEST.to_string(),
+            kind: TarKind::File,
+            mode: 0o644,
+            mtime: 0,
+            data: manifest.into_bytes(),
+            link: String::new(),
+        }]);
+        assert!(matches!(
+            read_bundle(&archive),
+            Err(BundleError::UnsafePath(_))
+        ));
+    }
+}
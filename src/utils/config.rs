@@ -1,20 +1,63 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 use crate::{
     dirs::{self},
-    toml::data::{Toml, TomlValue, TomlValueKind},
+    toml::data::{Toml, TomlError, TomlValue, TomlValueKind},
+    utils::{
+        error::{ErrorKind, Report},
+        name::{validate_name, NameKind, LOCAL_WORKSPACE_NAME},
+        output,
+        settings::{read_settings, Settings, SettingsError},
+    },
 };
 
 const CONFIG_FILENAME: &str = "Cloup.toml";
 
+/// Version of the config file format written by this release
+pub const CONFIG_VERSION: &str = "1.0";
+
+/// Version of config files written before the version key existed
+const UNVERSIONED: &str = "0";
+
+/// A step from one version of the config file format to the next
+struct Migration {
+    from: &'static str,
+    to: &'static str,
+    migrate: fn(&mut Toml),
+}
+
+/// Every migration, oldest first. A file is taken through each migration
+/// starting at its version, so new ones are only ever added at the end.
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: UNVERSIONED,
+    to: "1.0",
+    migrate: |_| (),
+}];
+
+/// Path of the config file, takes precedence over everything else
+const CONFIG_ENV: &str = "CLOUP_CONFIG";
+
+/// Directory holding `Cloup.toml`, takes precedence over the platform config directory
+const HOME_ENV: &str = "CLOUP_HOME";
+
+/// Workspace to use instead of the active one, for a single invocation
+const WORKSPACE_ENV: &str = "CLOUP_WORKSPACE";
+
+/// Directory holding the cloups of a project-local workspace
+const LOCAL_DIRNAME: &str = ".cloup";
+
+/// Marks a directory as a workspace, and optionally names it
+pub const WORKSPACE_FILENAME: &str = "cloup-workspace.toml";
+
 #[derive(Debug)]
 pub struct Config {
     /// Initial run?
     pub initial_run: bool,
 
-    /// Version of config
-    pub version: Option<String>,
-
     /// Current directory
     pub current_dir: PathBuf,
 
@@ -24,6 +67,9 @@ pub struct Config {
     /// Data from Toml
     pub data: ConfigData,
 
+    /// Values of the `[settings]` table
+    pub settings: Settings,
+
     /// Toml in case it's needed
     pub toml: Toml,
 }
@@ -32,6 +78,28 @@ pub struct Config {
 pub struct ConfigData {
     /// List of available workspaces
     pub workspaces: Vec<Workspace>,
+
+    /// Names of the workspaces searched for cloups after the active one
+    pub search_path: Vec<String>,
+}
+
+impl ConfigData {
+    /// Workspaces in the order cloups are looked up in: the project-local
+    /// workspace, the active workspace, then the workspaces of the search path
+    pub fn search_order(&self) -> Vec<&Workspace> {
+        let mut order: Vec<&Workspace> = self.workspaces.iter().filter(|w| w.local).collect();
+        order.extend(self.workspaces.iter().filter(|w| w.active));
+
+        for name in &self.search_path {
+            if let Some(workspace) = self.workspaces.iter().find(|w| &w.name == name) {
+                if !order.iter().any(|w| w.name == workspace.name) {
+                    order.push(workspace);
+                }
+            }
+        }
+
+        order
+    }
 }
 
 impl Iterator for ConfigData {
@@ -47,6 +115,9 @@ pub struct Workspace {
     pub name: String,
     pub location: PathBuf,
     pub active: bool,
+
+    /// Discovered from the current directory rather than registered in Cloup.toml
+    pub local: bool,
 }
 
 #[derive(Debug)]
@@ -59,6 +130,21 @@ pub enum ConfigError {
     /// Config contents errors
     KeyMissing,
     UnexpectedValue,
+
+    InvalidSettings(SettingsError),
+
+    /// Version errors
+    InvalidVersion(String),
+    NewerVersion(String),
+    Backup(PathBuf, std::io::Error),
+
+    /// Write errors
+    Write(PathBuf, std::io::Error),
+    Update(TomlError),
+
+    /// Environment errors
+    UnknownWorkspace(String),
+    LocalNameTaken(String, PathBuf),
 }
 
 impl std::fmt::Display for ConfigError {
@@ -72,6 +158,59 @@ impl std::fmt::Display for ConfigError {
             ConfigError::FileParseError => write!(f, "Config file parse error"),
             ConfigError::KeyMissing => write!(f, "Key missing in config file"),
             ConfigError::UnexpectedValue => write!(f, "Key present, but value was unexpected"),
+            ConfigError::InvalidSettings(e) => write!(f, "Invalid settings: {}", e),
+            ConfigError::InvalidVersion(version) => {
+                write!(f, "Config file has an invalid version '{}'", version)
+            }
+            ConfigError::NewerVersion(version) => write!(
+                f,
+                "Config file version {} is newer than the supported version {}, upgrade cloup to use it",
+                version, CONFIG_VERSION
+            ),
+            ConfigError::Backup(path, e) => write!(
+                f,
+                "Could not back up the config to '{}': {}",
+                path.to_string_lossy(),
+                e
+            ),
+            ConfigError::Write(path, e) => {
+                write!(f, "Could not write '{}': {}", path.to_string_lossy(), e)
+            }
+            ConfigError::Update(e) => write!(f, "Could not update config: {}", e),
+            ConfigError::UnknownWorkspace(name) => write!(
+                f,
+                "Workspace '{}' set in {} does not exist",
+                name, WORKSPACE_ENV
+            ),
+            ConfigError::LocalNameTaken(name, path) => write!(
+                f,
+                "The local workspace at '{}' is named '{}' like a registered workspace, give it another name in {}",
+                path.to_string_lossy(),
+                name,
+                WORKSPACE_FILENAME
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Report for ConfigError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ConfigError::UnknownWorkspace(_) => ErrorKind::NotFound,
+            ConfigError::LocalNameTaken(..) => ErrorKind::Conflict,
+            ConfigError::Backup(..) | ConfigError::Write(..) => ErrorKind::Io,
+            _ => ErrorKind::Config,
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            ConfigError::Backup(path, _)
+            | ConfigError::Write(path, _)
+            | ConfigError::LocalNameTaken(_, path) => Some(path),
+            _ => None,
         }
     }
 }
@@ -86,49 +225,115 @@ impl From<std::io::Error> for ConfigError {
     }
 }
 
+/// Location of the config file, in order of precedence:
+/// 1. `CLOUP_CONFIG`, the path of the config file itself
+/// 2. `CLOUP_HOME`, a directory holding `Cloup.toml`
+/// 3. `cloup/Cloup.toml` in the platform config directory (see `dirs::config_dir`)
+pub fn config_path() -> Result<PathBuf, ConfigError> {
+    if let Some(path) = env_path(CONFIG_ENV) {
+        return Ok(path);
+    }
+
+    if let Some(home) = env_path(HOME_ENV) {
+        return Ok(home.join(CONFIG_FILENAME));
+    }
+
+    Ok(dirs::config_dir()?.join("cloup").join(CONFIG_FILENAME))
+}
+
+/// Path from an environment variable, resolved against the current directory.
+/// Empty variables count as unset.
+fn env_path(key: &str) -> Option<PathBuf> {
+    let value = std::env::var_os(key).filter(|v| !v.is_empty())?;
+
+    match std::env::current_dir() {
+        Ok(current_dir) => Some(current_dir.join(value)),
+        Err(_) => Some(PathBuf::from(value)),
+    }
+}
+
+/// Make the workspace named in `CLOUP_WORKSPACE` the active one, without
+/// touching the active workspace saved in the config file
+fn override_active_workspace(workspaces: &mut [Workspace]) -> Result<(), ConfigError> {
+    let Some(name) = std::env::var(WORKSPACE_ENV).ok().filter(|v| !v.is_empty()) else {
+        return Ok(());
+    };
+
+    if !workspaces.iter().any(|w| w.name == name && !w.local) {
+        return Err(ConfigError::UnknownWorkspace(name));
+    }
+
+    for workspace in workspaces.iter_mut() {
+        workspace.active = workspace.name == name && !workspace.local;
+    }
+
+    Ok(())
+}
+
 pub fn get_config() -> Result<Config, ConfigError> {
-    let config_dir = dirs::config_dir()?.join("cloup");
-    let config_path = config_dir.join(CONFIG_FILENAME);
+    read_config(&config_path()?)
+}
+
+/// Read the config file at `config_path`, creating it if needed and migrating
+/// it if it was written by an older release
+fn read_config(config_path: &Path) -> Result<Config, ConfigError> {
+    let config_path = config_path.to_path_buf();
+    let config_dir = config_path.parent().ok_or(ConfigError::DirNotFound)?;
     let current_dir = std::env::current_dir()?;
 
     if !config_dir.exists() {
-        fs::create_dir_all(&config_dir)?;
-        return get_config();
+        fs::create_dir_all(config_dir)?;
+        return read_config(&config_path);
     }
 
     if !config_path.exists() {
         // create default config
-        let mut toml = Toml::new();
-        toml.set(
-            "version".to_string(),
-            TomlValueKind::String("1.0".to_string()),
-        );
-        toml.set(
-            "active_workspace".to_string(),
-            TomlValueKind::String("default".to_string()),
-        );
-        toml.set(
-            "workspaces".to_string(),
-            TomlValueKind::Table(vec![TomlValue {
-                key: "default".to_string(),
-                kind: TomlValueKind::String(current_dir.to_string_lossy().to_string()),
-            }]),
-        );
+        let toml = Toml::from(Some(vec![
+            TomlValue {
+                key: "version".to_string(),
+                kind: TomlValueKind::String(CONFIG_VERSION.to_string()),
+            },
+            TomlValue {
+                key: "active_workspace".to_string(),
+                kind: TomlValueKind::String("default".to_string()),
+            },
+            TomlValue {
+                key: "workspaces".to_string(),
+                kind: TomlValueKind::Table(vec![TomlValue {
+                    key: "default".to_string(),
+                    kind: TomlValueKind::String(current_dir.to_string_lossy().to_string()),
+                }]),
+            },
+        ]));
+
+        {
+            let _lock = lock(&config_path)?;
 
-        fs::write(&config_path, toml.to_toml())?;
+            // another cloup may have created it while we waited for the lock
+            if config_path.exists() {
+                return read_config(&config_path);
+            }
+            write_atomic(&config_path, &toml.to_toml())?;
+        }
+
+        let mut workspaces = vec![Workspace {
+            name: "default".to_string(),
+            location: PathBuf::from(current_dir.to_string_lossy().to_string()),
+            active: true,
+            local: false,
+        }];
+        add_local_workspace(&mut workspaces, &current_dir)?;
+        override_active_workspace(&mut workspaces)?;
 
         return Ok(Config {
             initial_run: true,
-            version: Some("1".to_string()),
             current_dir: current_dir.clone(),
             config_path,
             data: ConfigData {
-                workspaces: vec![Workspace {
-                    name: "default".to_string(),
-                    location: PathBuf::from(current_dir.to_string_lossy().to_string()),
-                    active: true,
-                }],
+                workspaces,
+                search_path: vec![],
             },
+            settings: Settings::default(),
             toml,
         });
     }
@@ -136,24 +341,32 @@ pub fn get_config() -> Result<Config, ConfigError> {
     let config_content = fs::read_to_string(&config_path)?;
 
     // Parse config file
-    let toml = Toml::from(config_content);
+    let mut toml = Toml::from(config_content);
     if toml.data.is_empty() {
-        fs::remove_file(config_path)?;
-        return get_config();
+        fs::remove_file(&config_path)?;
+        return read_config(&config_path);
+    }
+
+    // Bring files written by older releases up to date, keeping the original
+    if let Some(from) = migrate(&mut toml)? {
+        let backup = config_path.with_extension(format!("toml.v{}.bak", from));
+        fs::copy(&config_path, &backup).map_err(|e| ConfigError::Backup(backup.clone(), e))?;
+        toml = update_config(&config_path, |_| Ok(()))?;
+
+        output::eprintln(format!(
+            "\x1b[1;33m»\x1b[0m Migrated config from version {} to {}, the original was saved to {}",
+            from,
+            CONFIG_VERSION,
+            backup.to_string_lossy()
+        ));
     }
 
     // Get contents of config file
-    let version = toml.get("version");
     let active_workspace = toml
         .get("active_workspace")
         .ok_or(ConfigError::KeyMissing)?;
     let workspaces = toml.get("workspaces").ok_or(ConfigError::KeyMissing)?;
 
-    let version = match version {
-        Some(TomlValueKind::String(value)) => Some(value.clone()),
-        _ => None,
-    };
-
     let active_workspace = match active_workspace {
         TomlValueKind::String(value) => Ok(value.clone()),
         _ => Err(ConfigError::KeyMissing),
@@ -162,38 +375,398 @@ pub fn get_config() -> Result<Config, ConfigError> {
     let workspaces = match workspaces {
         TomlValueKind::Table(values) => values
             .iter()
-            .map(|v| {
-                let result = match &v.kind {
-                    TomlValueKind::String(location) => Ok(Workspace {
-                        name: v.key.to_string(),
-                        location: PathBuf::from(location),
-                        active: active_workspace == v.key,
-                    }),
-                    _ => Err(ConfigError::UnexpectedValue),
-                };
-                result.map_err(ConfigError::from)
+            .map(|v| match &v.kind {
+                TomlValueKind::String(location) => Ok(Workspace {
+                    name: v.key.to_string(),
+                    location: PathBuf::from(location),
+                    active: active_workspace == v.key,
+                    local: false,
+                }),
+                _ => Err(ConfigError::UnexpectedValue),
             })
-            .collect(),
+            .collect::<Result<Vec<_>, _>>(),
         _ => Err(ConfigError::UnexpectedValue),
     }?;
 
+    let mut workspaces = workspaces;
+    add_local_workspace(&mut workspaces, &current_dir)?;
+    override_active_workspace(&mut workspaces)?;
+
+    let search_path = match toml.get("search_path") {
+        None => Ok(vec![]),
+        Some(TomlValueKind::Array(values)) => values
+            .iter()
+            .map(|v| match v {
+                TomlValueKind::String(name) => Ok(name.clone()),
+                _ => Err(ConfigError::UnexpectedValue),
+            })
+            .collect(),
+        Some(_) => Err(ConfigError::UnexpectedValue),
+    }?;
+
+    let settings = read_settings(&toml).map_err(ConfigError::InvalidSettings)?;
+
+    // the colour mode is only known once the config is read
+    output::set_color(settings.color);
+
     Ok(Config {
         initial_run: false,
-        version,
         current_dir,
         config_path,
-        data: ConfigData { workspaces },
+        data: ConfigData {
+            workspaces,
+            search_path,
+        },
+        settings,
         toml,
     })
 }
 
+/// Change the config file and write it back, the only way `Cloup.toml` is
+/// written. While holding a lock on `Cloup.toml.lock`, the file is read again
+/// and `change` is applied to what's on disk, so changes made by another cloup
+/// since the config was loaded are kept. The result goes to a temporary file
+/// that's synced and renamed over the config, so a crash never leaves half a
+/// file behind. Returns the config as written.
+pub fn update_config(
+    config_path: &Path,
+    change: impl FnOnce(&mut Toml) -> Result<(), TomlError>,
+) -> Result<Toml, ConfigError> {
+    let _lock = lock(config_path)?;
+
+    let content = match fs::read_to_string(config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(ConfigError::Write(config_path.to_path_buf(), e)),
+    };
+
+    let mut toml = Toml::from(content);
+    if !toml.data.is_empty() {
+        migrate(&mut toml)?;
+    }
+    change(&mut toml).map_err(ConfigError::Update)?;
+
+    write_atomic(config_path, &toml.to_toml())?;
+    Ok(toml)
+}
+
+/// Take the advisory lock guarding writes to the config, waiting for other
+/// cloup processes to release it. The lock is released when the file is dropped.
+fn lock(config_path: &Path) -> Result<File, ConfigError> {
+    let path = config_path.with_extension("toml.lock");
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| ConfigError::Write(path.clone(), e))?;
+
+    file.lock().map_err(|e| ConfigError::Write(path, e))?;
+    Ok(file)
+}
+
+/// Replace a file with new contents through a synced temporary file
+fn write_atomic(path: &Path, contents: &str) -> Result<(), ConfigError> {
+    let error = |e| ConfigError::Write(path.to_path_buf(), e);
+    let temp = path.with_extension(format!("toml.{}.tmp", std::process::id()));
+
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp, path));
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(error(e));
+    }
+
+    // make the rename itself durable, not supported everywhere
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let _ = File::open(dir).and_then(|dir| dir.sync_all());
+    }
+
+    Ok(())
+}
+
+/// Parse a `major.minor` version, a missing minor counting as 0
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let (major, minor) = version.split_once('.').unwrap_or((version, "0"));
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Version of a config, refusing invalid versions and versions from a newer
+/// release rather than reading keys this release doesn't know
+pub fn config_version(toml: &Toml) -> Result<String, ConfigError> {
+    let version = match toml.get("version") {
+        None => UNVERSIONED.to_string(),
+        Some(TomlValueKind::String(version)) => version.clone(),
+        Some(value) => {
+            return Err(ConfigError::InvalidVersion(
+                Toml::format_value(value).unwrap_or_default(),
+            ))
+        }
+    };
+
+    let parsed = parse_version(&version).ok_or(ConfigError::InvalidVersion(version.clone()))?;
+    if parsed > parse_version(CONFIG_VERSION).unwrap_or_default() {
+        return Err(ConfigError::NewerVersion(version));
+    }
+
+    Ok(version)
+}
+
+/// Run the migrations needed to bring a config to `CONFIG_VERSION`, returning
+/// the version it started from if anything was migrated
+fn migrate(toml: &mut Toml) -> Result<Option<String>, ConfigError> {
+    migrate_with(toml, MIGRATIONS)
+}
+
+fn migrate_with(toml: &mut Toml, migrations: &[Migration]) -> Result<Option<String>, ConfigError> {
+    let from = config_version(toml)?;
+    let version = parse_version(&from).unwrap_or_default();
+    if version == parse_version(CONFIG_VERSION).unwrap_or_default() {
+        return Ok(None);
+    }
+
+    for migration in migrations {
+        let applies = parse_version(migration.from).is_some_and(|v| v >= version);
+        if applies {
+            (migration.migrate)(toml);
+            set_version(toml, migration.to);
+        }
+    }
+    set_version(toml, CONFIG_VERSION);
+
+    Ok(Some(from))
+}
+
+/// Set the version key, keeping it at the top of the file
+fn set_version(toml: &mut Toml, version: &str) {
+    match toml.get_mut("version") {
+        Some(kind) => *kind = TomlValueKind::String(version.to_string()),
+        None => toml.data.insert(
+            0,
+            TomlValue {
+                key: "version".to_string(),
+                kind: TomlValueKind::String(version.to_string()),
+            },
+        ),
+    }
+}
+
+/// Put the project-local workspace found from the current directory before the
+/// registered ones, unless it's one of them. It can't take the name of
+/// another one, which it would hide.
+fn add_local_workspace(
+    workspaces: &mut Vec<Workspace>,
+    current_dir: &Path,
+) -> Result<(), ConfigError> {
+    let Some(local) = find_local_workspace(current_dir) else {
+        return Ok(());
+    };
+
+    let canonical = fs::canonicalize(&local.location).ok();
+    let registered = workspaces.iter().any(|w| {
+        w.location == local.location
+            || canonical.is_some() && fs::canonicalize(&w.location).ok() == canonical
+    });
+    if registered {
+        return Ok(());
+    }
+    if workspaces.iter().any(|w| w.name == local.name) {
+        return Err(ConfigError::LocalNameTaken(local.name, local.location));
+    }
+
+    workspaces.insert(0, local);
+    Ok(())
+}
+
+/// Find a project-local workspace by walking up from the given directory. The
+/// first directory containing a `.cloup` directory or a `cloup-workspace.toml`
+/// file wins, `.cloup` being the workspace location in the former case.
+pub fn find_local_workspace(current_dir: &Path) -> Option<Workspace> {
+    let location = current_dir.ancestors().find_map(|dir| {
+        let local_dir = dir.join(LOCAL_DIRNAME);
+        if local_dir.is_dir() {
+            Some(local_dir)
+        } else if dir.join(WORKSPACE_FILENAME).is_file() {
+            Some(dir.to_path_buf())
+        } else {
+            None
+        }
+    })?;
+
+    let name = fs::read_to_string(location.join(WORKSPACE_FILENAME))
+        .ok()
+        .and_then(|content| match Toml::from(content).get("name") {
+            Some(TomlValueKind::String(name)) => Some(name.clone()),
+            _ => None,
+        })
+        // a name that couldn't be registered isn't used either
+        .filter(|name| validate_name(NameKind::Workspace, name).is_ok())
+        .unwrap_or(LOCAL_WORKSPACE_NAME.to_string());
+
+    Some(Workspace {
+        name,
+        location,
+        active: false,
+        local: true,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::testing::{write_tree, TempDir};
+
+    #[test]
+    fn test_read_config() {
+        let dir = TempDir::new("config-read");
+        let path = dir.join(CONFIG_FILENAME);
+        let original = "active_workspace = \"default\"\n\n[workspaces]\ndefault = \"/tmp\"\n";
+        fs::write(&path, original).unwrap();
+
+        // an unversioned file is migrated, the original kept as a backup
+        let config = read_config(&path).unwrap();
+        let toml = Toml::from(fs::read_to_string(&path).unwrap());
+        assert_eq!(config_version(&toml).unwrap(), CONFIG_VERSION);
+        assert!(config.data.workspaces.iter().any(|w| w.name == "default"));
+        let backup = path.with_extension(format!("toml.v{}.bak", UNVERSIONED));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), original);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut toml = Toml::from("active_workspace = \"default\"\n".to_string());
+        assert_eq!(migrate(&mut toml).unwrap(), Some(UNVERSIONED.to_string()));
+        assert!(
+            matches!(toml.get("version"), Some(TomlValueKind::String(v)) if v == CONFIG_VERSION)
+        );
+        assert_eq!(migrate(&mut toml).unwrap(), None);
+
+        let mut toml = Toml::from("version = \"99.0\"\n".to_string());
+        assert!(matches!(
+            migrate(&mut toml),
+            Err(ConfigError::NewerVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_migrate_steps() {
+        fn step(toml: &mut Toml, name: &str) {
+            if let Some(TomlValueKind::String(steps)) = toml.get_mut("steps") {
+                steps.push_str(name);
+            }
+        }
+        let steps = |toml: &Toml| match toml.get("steps") {
+            Some(TomlValueKind::String(steps)) => steps.clone(),
+            _ => String::new(),
+        };
+        let migrations = [
+            Migration {
+                from: UNVERSIONED,
+                to: "0.1",
+                migrate: |toml| step(toml, "a"),
+            },
+            Migration {
+                from: "0.1",
+                to: "0.5",
+                migrate: |toml| step(toml, "b"),
+            },
+            Migration {
+                from: "0.5",
+                to: CONFIG_VERSION,
+                migrate: |toml| step(toml, "c"),
+            },
+        ];
+
+        // every step from the version of the file, in order
+        let mut toml = Toml::from("steps = \"\"\n".to_string());
+        let from = migrate_with(&mut toml, &migrations).unwrap();
+        assert_eq!(from.as_deref(), Some(UNVERSIONED));
+        assert_eq!(steps(&toml), "abc");
+        assert_eq!(config_version(&toml).unwrap(), CONFIG_VERSION);
+
+        let mut toml = Toml::from("version = \"0.1\"\nsteps = \"\"\n".to_string());
+        let from = migrate_with(&mut toml, &migrations).unwrap();
+        assert_eq!(from.as_deref(), Some("0.1"));
+        assert_eq!(steps(&toml), "bc");
+
+        // an up to date file is left alone
+        let current = format!("version = \"{}\"\nsteps = \"\"\n", CONFIG_VERSION);
+        let mut toml = Toml::from(current.clone());
+        assert_eq!(migrate_with(&mut toml, &migrations).unwrap(), None);
+        assert_eq!(toml.to_toml(), Toml::from(current).to_toml());
+    }
 
     #[test]
-    fn test_get_config() {
-        let config = get_config().unwrap();
-        println!("{:#?}", config);
+    fn test_update_config() {
+        let dir = TempDir::new("config-update");
+        let path = dir.join(CONFIG_FILENAME);
+        fs::write(&path, "version = \"1.0\"\n\n[workspaces]\n").unwrap();
+
+        // every change survives, none overwrites another
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    update_config(&path, |toml| {
+                        toml.set_in("workspaces", format!("w{}", i), TomlValueKind::Integer(i))
+                    })
+                    .unwrap();
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+
+        let toml = Toml::from(fs::read_to_string(&path).unwrap());
+        assert!((0..8).all(|i| toml.get_in("workspaces", &format!("w{}", i)).is_some()));
+    }
+
+    #[test]
+    fn test_find_local_workspace() {
+        let project = TempDir::new("config-local");
+        write_tree(
+            &project,
+            &[
+                ("services/api/", ""),
+                (WORKSPACE_FILENAME, "name = \"mono\"\n"),
+            ],
+        );
+        let nested = project.join("services").join("api");
+
+        let workspace = find_local_workspace(&nested).unwrap();
+        assert_eq!(workspace.name, "mono");
+        assert_eq!(workspace.location, project.to_path_buf());
+
+        // added once, and never under the name of a registered workspace
+        let registered = |name: &str, location: &Path| Workspace {
+            name: name.to_string(),
+            location: location.to_path_buf(),
+            active: true,
+            local: false,
+        };
+        let mut workspaces = vec![registered("default", &nested)];
+        add_local_workspace(&mut workspaces, &nested).unwrap();
+        assert_eq!(workspaces[0].name, "mono");
+        assert!(workspaces[0].local);
+
+        let mut workspaces = vec![registered("monorepo", &project)];
+        add_local_workspace(&mut workspaces, &nested).unwrap();
+        assert_eq!(workspaces.len(), 1);
+
+        let mut workspaces = vec![registered("mono", &nested)];
+        assert!(matches!(
+            add_local_workspace(&mut workspaces, &nested),
+            Err(ConfigError::LocalNameTaken(..))
+        ));
+
+        fs::create_dir(project.join(LOCAL_DIRNAME)).unwrap();
+        let workspace = find_local_workspace(&nested).unwrap();
+        assert_eq!(workspace.name, LOCAL_WORKSPACE_NAME);
+        assert_eq!(workspace.location, project.join(LOCAL_DIRNAME));
     }
 }
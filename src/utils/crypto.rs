@@ -0,0 +1,230 @@
+// Encryption of files at rest with XChaCha20-Poly1305 from the
+// `chacha20poly1305` crate, the key being derived from the passphrase with
+// Argon2id from the `argon2` crate. Salts and nonces come from the random
+// number generator of the OS through `getrandom`.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use sha2::{Digest, Sha256};
+
+/// First bytes of every encrypted file
+pub const MAGIC: &[u8; 8] = b"CLOUPENC";
+
+const FORMAT_VERSION: u8 = 1;
+
+/// Bounds on the Argon2 costs read from a file, so a crafted one can't hang
+/// apply or exhaust memory
+const MAX_MEMORY_COST: u32 = 1024 * 1024;
+const MAX_TIME_COST: u32 = 64;
+const MAX_PARALLELISM: u32 = 16;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 3 * 4 + SALT_LEN + NONCE_LEN;
+
+#[derive(Debug, PartialEq)]
+pub enum CryptoError {
+    /// Not the format written by `seal`, or a version this release can't read
+    Malformed,
+    /// Wrong passphrase, or the file was changed
+    Mismatch,
+    /// More data than can be encrypted with a single nonce
+    TooLarge,
+    /// The random number generator of the OS couldn't be read
+    Random,
+}
+
+/// Check if data was written by `Keys::seal`
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Key derived from a passphrase and a salt. Deriving it is slow on purpose,
+/// so files encrypted together share it, each with its own nonce.
+pub struct Keys {
+    salt: Vec<u8>,
+    params: Params,
+    cipher: XChaCha20Poly1305,
+}
+
+impl Keys {
+    /// Key for newly encrypted files, with a new salt and the default costs
+    pub fn new(passphrase: &str) -> Result<Self, CryptoError> {
+        let salt = random_bytes::<SALT_LEN>()?;
+        Keys::derive(passphrase, &salt, Params::default())
+    }
+
+    fn derive(passphrase: &str, salt: &[u8], params: Params) -> Result<Self, CryptoError> {
+        let mut key = Key::default();
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|_| CryptoError::Malformed)?;
+
+        Ok(Keys {
+            salt: salt.to_vec(),
+            params,
+            cipher: XChaCha20Poly1305::new(&key),
+        })
+    }
+
+    /// Encrypt data, the result holding everything needed to decrypt it but
+    /// the passphrase
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.seal_with(plaintext, random_bytes::<NONCE_LEN>()?)
+    }
+
+    fn seal_with(&self, plaintext: &[u8], nonce: [u8; NONCE_LEN]) -> Result<Vec<u8>, CryptoError> {
+        let mut data = Vec::with_capacity(HEADER_LEN + plaintext.len() + TAG_LEN);
+        data.extend_from_slice(MAGIC);
+        data.push(FORMAT_VERSION);
+        data.extend_from_slice(&self.params.m_cost().to_be_bytes());
+        data.extend_from_slice(&self.params.t_cost().to_be_bytes());
+        data.extend_from_slice(&self.params.p_cost().to_be_bytes());
+        data.extend_from_slice(&self.salt);
+        data.extend_from_slice(&nonce);
+
+        // the header isn't secret, but it's authenticated with the data
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                chacha20poly1305::aead::Payload {
+                    msg: plaintext,
+                    aad: &data,
+                },
+            )
+            .map_err(|_| CryptoError::TooLarge)?;
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+}
+
+/// Decrypts files sealed with a passphrase, deriving keys once per salt
+pub struct Opener {
+    passphrase: String,
+    keys: Vec<Keys>,
+}
+
+impl Opener {
+    pub fn new(passphrase: &str) -> Self {
+        Opener {
+            passphrase: passphrase.to_string(),
+            keys: vec![],
+        }
+    }
+
+    /// Decrypt data, checking that it wasn't changed
+    pub fn open(&mut self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        if !is_sealed(data)
+            || data.len() < HEADER_LEN + TAG_LEN
+            || data[MAGIC.len()] != FORMAT_VERSION
+        {
+            return Err(CryptoError::Malformed);
+        }
+
+        let (header, ciphertext) = data.split_at(HEADER_LEN);
+        let mut offset = MAGIC.len() + 1;
+        let mut take = |len: usize| {
+            let bytes = &header[offset..offset + len];
+            offset += len;
+            bytes
+        };
+        let mut cost = || u32::from_be_bytes(take(4).try_into().unwrap_or_default());
+        let (memory, time, parallelism) = (cost(), cost(), cost());
+        let salt = take(SALT_LEN);
+        let nonce = take(NONCE_LEN);
+
+        if memory > MAX_MEMORY_COST || time > MAX_TIME_COST || parallelism > MAX_PARALLELISM {
+            return Err(CryptoError::Malformed);
+        }
+        let params =
+            Params::new(memory, time, parallelism, None).map_err(|_| CryptoError::Malformed)?;
+
+        let index = match self.keys.iter().position(|k| {
+            k.salt == salt
+                && (k.params.m_cost(), k.params.t_cost(), k.params.p_cost())
+                    == (memory, time, parallelism)
+        }) {
+            Some(index) => index,
+            None => {
+                self.keys
+                    .push(Keys::derive(&self.passphrase, salt, params)?);
+                self.keys.len() - 1
+            }
+        };
+
+        self.keys[index]
+            .cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                chacha20poly1305::aead::Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| CryptoError::Mismatch)
+    }
+}
+
+/// Bytes for salts, nonces and unguessable names, from the random number
+/// generator of the OS
+pub fn random_bytes<const N: usize>() -> Result<[u8; N], CryptoError> {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes).map_err(|_| CryptoError::Random)?;
+    Ok(bytes)
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// Lowercase hexadecimal form of a digest
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_data() {
+        let params = Params::new(64, 1, 1, None).unwrap();
+        let keys = Keys::derive("passphrase", &[1; SALT_LEN], params).unwrap();
+        let sealed = keys
+            .seal_with(b"DB_PASSWORD=hunter2", [2; NONCE_LEN])
+            .unwrap();
+        assert!(is_sealed(&sealed));
+        assert_eq!(
+            Opener::new("passphrase").open(&sealed).unwrap(),
+            b"DB_PASSWORD=hunter2"
+        );
+        assert_eq!(
+            Opener::new("wrong").open(&sealed),
+            Err(CryptoError::Mismatch)
+        );
+
+        // the ciphertext, and the header it's bound to
+        let mut opener = Opener::new("passphrase");
+        for at in [HEADER_LEN, HEADER_LEN - 1] {
+            let mut changed = sealed.clone();
+            changed[at] ^= 1;
+            assert_eq!(opener.open(&changed), Err(CryptoError::Mismatch));
+        }
+
+        let mut costly = sealed.clone();
+        costly[MAGIC.len() + 1..MAGIC.len() + 5].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(opener.open(&costly), Err(CryptoError::Malformed));
+        assert_eq!(opener.open(b"CLOUPENC"), Err(CryptoError::Malformed));
+        assert_ne!(keys.seal(b"").unwrap(), keys.seal(b"").unwrap());
+
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
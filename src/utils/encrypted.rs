@@ -0,0 +1,202 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    crypto::{CryptoError, Keys, Opener},
+    error::{ErrorKind, Report},
+    file::{matches_path, Copied},
+    meta::applied_path,
+    output,
+};
+
+/// Environment variable holding the passphrase of encrypted files
+pub const PASSPHRASE_ENV: &str = "CLOUP_PASSPHRASE";
+
+#[derive(Debug)]
+pub enum EncryptionError {
+    NoPassphrase,
+    EmptyPassphrase,
+    PassphraseMismatch,
+    Decrypt(PathBuf),
+    Malformed(PathBuf),
+    TooLarge(PathBuf),
+    Random,
+    Error(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EncryptionError::NoPassphrase => write!(
+                f,
+                "A passphrase is needed for encrypted files, pass --key-file or set {}",
+                PASSPHRASE_ENV
+            ),
+            EncryptionError::EmptyPassphrase => write!(f, "The passphrase can't be empty"),
+            EncryptionError::PassphraseMismatch => write!(f, "The passphrases don't match"),
+            EncryptionError::Decrypt(path) => write!(
+                f,
+                "Could not decrypt '{}', the passphrase is wrong or the file was changed",
+                path.to_string_lossy()
+            ),
+            EncryptionError::Malformed(path) => write!(
+                f,
+                "'{}' is not an encrypted file this version of cloup can read",
+                path.to_string_lossy()
+            ),
+            EncryptionError::TooLarge(path) => {
+                write!(
+                    f,
+                    "'{}' is too large to be encrypted",
+                    path.to_string_lossy()
+                )
+            }
+            EncryptionError::Random => {
+                write!(
+                    f,
+                    "Could not read the random number generator of the system"
+                )
+            }
+            EncryptionError::Error(path, _) => {
+                write!(f, "Could not read or write '{}'", path.to_string_lossy())
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EncryptionError::Error(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Report for EncryptionError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            EncryptionError::NoPassphrase
+            | EncryptionError::EmptyPassphrase
+            | EncryptionError::PassphraseMismatch
+            | EncryptionError::Decrypt(_) => ErrorKind::Usage,
+            EncryptionError::Malformed(_)
+            | EncryptionError::TooLarge(_)
+            | EncryptionError::Random
+            | EncryptionError::Error(..) => ErrorKind::Io,
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            EncryptionError::Decrypt(path)
+            | EncryptionError::Malformed(path)
+            | EncryptionError::TooLarge(path)
+            | EncryptionError::Error(path, _) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// Get the passphrase from the key file, the environment or, as a last
+/// resort, by asking. A new passphrase is asked twice.
+pub fn passphrase(key_file: Option<&Path>, confirm: bool) -> Result<String, EncryptionError> {
+    let passphrase = if let Some(path) = key_file {
+        fs::read_to_string(path)
+            .map_err(|e| EncryptionError::Error(path.to_path_buf(), e))?
+            .trim_end_matches(['\n', '\r'])
+            .to_string()
+    } else if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        passphrase
+    } else if output::is_json() || !std::io::stdin().is_terminal() {
+        return Err(EncryptionError::NoPassphrase);
+    } else {
+        let passphrase = prompt("Passphrase: ")?;
+        if confirm && !passphrase.is_empty() && prompt("Repeat passphrase: ")? != passphrase {
+            return Err(EncryptionError::PassphraseMismatch);
+        }
+        passphrase
+    };
+
+    if passphrase.is_empty() {
+        return Err(EncryptionError::EmptyPassphrase);
+    }
+    Ok(passphrase)
+}
+
+/// Ask for a passphrase on the terminal without echoing it, echo being
+/// turned back on even when reading fails
+fn prompt(text: &str) -> Result<String, EncryptionError> {
+    rpassword::prompt_password(format!("\x1b[1;33m»\x1b[0m {}", text))
+        .map_err(|e| EncryptionError::Error(PathBuf::from("stdin"), e))
+}
+
+/// Files of a copy that are to be encrypted, by the path they're applied to
+/// or their name, as `(source, destination)` pairs. Symlinks are only
+/// encrypted when followed.
+pub fn files_to_encrypt(
+    cloup_path: &Path,
+    copied: &Copied,
+    patterns: &[String],
+    follow: bool,
+) -> Vec<(PathBuf, PathBuf)> {
+    copied
+        .sources
+        .iter()
+        .zip(&copied.files)
+        .filter(|(source, _)| match fs::symlink_metadata(source) {
+            Ok(m) if m.is_symlink() => follow && source.is_file(),
+            Ok(m) => m.is_file(),
+            Err(_) => false,
+        })
+        .filter(|(_, file)| {
+            applied_path(cloup_path, file).is_some_and(|p| matches_path(&p, patterns))
+        })
+        .map(|(source, file)| (source.clone(), file.clone()))
+        .collect()
+}
+
+/// Encrypt files in memory, so their plaintext is never written to the cloup
+pub fn seal_files(
+    files: &[PathBuf],
+    passphrase: &str,
+) -> Result<HashMap<PathBuf, Vec<u8>>, EncryptionError> {
+    let keys = Keys::new(passphrase).map_err(|_| EncryptionError::Random)?;
+    let mut sealed = HashMap::new();
+
+    for file in files {
+        let plaintext = fs::read(file).map_err(|e| EncryptionError::Error(file.clone(), e))?;
+        let data = keys.seal(&plaintext).map_err(|e| match e {
+            CryptoError::TooLarge => EncryptionError::TooLarge(file.clone()),
+            _ => EncryptionError::Random,
+        })?;
+        sealed.insert(file.clone(), data);
+    }
+
+    Ok(sealed)
+}
+
+/// Decrypt files in memory, all of them being checked before anything is
+/// written
+pub fn decrypt_files(
+    files: &[PathBuf],
+    passphrase: &str,
+) -> Result<HashMap<PathBuf, Vec<u8>>, EncryptionError> {
+    let mut decrypted = HashMap::new();
+    let mut opener = Opener::new(passphrase);
+
+    for file in files {
+        let data = fs::read(file).map_err(|e| EncryptionError::Error(file.clone(), e))?;
+        let plaintext = opener.open(&data).map_err(|e| match e {
+            CryptoError::Mismatch => EncryptionError::Decrypt(file.clone()),
+            _ => EncryptionError::Malformed(file.clone()),
+        })?;
+        decrypted.insert(file.clone(), plaintext);
+    }
+
+    Ok(decrypted)
+}
@@ -0,0 +1,102 @@
+use std::{error::Error, path::Path};
+
+use super::json::Json;
+
+/// Class of an error, used to pick the process exit code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    /// Invalid arguments or flags
+    Usage,
+    /// Missing or malformed configuration
+    Config,
+    /// A workspace or cloup that does not exist
+    NotFound,
+    /// Reading or writing files failed
+    Io,
+    /// Something already exists and would be overwritten
+    Conflict,
+}
+
+impl ErrorKind {
+    /// Exit code returned by the process for this class of error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ErrorKind::Usage => 2,
+            ErrorKind::Config => 3,
+            ErrorKind::NotFound => 4,
+            ErrorKind::Io => 5,
+            ErrorKind::Conflict => 6,
+        }
+    }
+
+    /// Stable name used in JSON output
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Usage => "usage",
+            ErrorKind::Config => "config",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::Io => "io",
+            ErrorKind::Conflict => "conflict",
+        }
+    }
+}
+
+/// An error that can be reported to the user by `main`
+pub trait Report: Error {
+    /// Class of the error
+    fn kind(&self) -> ErrorKind;
+
+    /// Path the error relates to, if any
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+impl<E: Report + 'static> From<E> for Box<dyn Report> {
+    fn from(error: E) -> Self {
+        Box::new(error)
+    }
+}
+
+/// Messages of every error in the chain below `error`
+fn causes(error: &dyn Report) -> Vec<String> {
+    let mut causes = vec![];
+    let mut source = error.source();
+
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+
+    causes
+}
+
+/// Render an error as human readable text
+pub fn to_text(error: &dyn Report) -> String {
+    let mut text = error.to_string();
+
+    if let Some(path) = error.path() {
+        text.push_str(&format!("\n    path: {}", path.to_string_lossy()));
+    }
+
+    for cause in causes(error) {
+        text.push_str(&format!("\n    caused by: {}", cause));
+    }
+
+    text
+}
+
+/// Render an error as a JSON object
+pub fn to_json(error: &dyn Report) -> Json {
+    let kind = error.kind();
+
+    Json::object().with(
+        "error",
+        Json::object()
+            .with("kind", kind.name())
+            .with("code", kind.exit_code() as i64)
+            .with("message", error.to_string())
+            .with("path", error.path())
+            .with("causes", causes(error)),
+    )
+}
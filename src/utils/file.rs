@@ -1,72 +1,701 @@
 use std::{
+    collections::HashMap,
+    ffi::{OsStr, OsString},
     fs,
+    io::IsTerminal,
     path::{Path, PathBuf},
 };
 
+use super::{
+    archive::is_partial_archive,
+    error::{ErrorKind, Report},
+    meta::{KEEP_FILE, META_FILE},
+    output,
+};
+
 #[derive(Debug)]
 pub enum FileError {
-    Error(std::io::Error),
+    Error(PathBuf, std::io::Error),
+    Exists(PathBuf),
+    Symlink(PathBuf),
+    SymlinkLoop(PathBuf),
+    Escapes(PathBuf),
 }
 
 impl std::fmt::Display for FileError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            FileError::Error(e) => write!(f, "Error: {}", e),
+            FileError::Error(path, _) => write!(f, "Could not copy '{}'", path.to_string_lossy()),
+            FileError::Exists(path) => {
+                write!(f, "'{}' already exists", path.to_string_lossy())
+            }
+            FileError::Symlink(path) => write!(
+                f,
+                "'{}' is a symlink, pass --symlinks to choose what to do with it",
+                path.to_string_lossy()
+            ),
+            FileError::SymlinkLoop(path) => write!(
+                f,
+                "'{}' links back to a directory being copied",
+                path.to_string_lossy()
+            ),
+            FileError::Escapes(path) => write!(
+                f,
+                "Refusing to write '{}', a symlink leads it outside of the destination",
+                path.to_string_lossy()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FileError::Error(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Report for FileError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            FileError::Error(..) | FileError::SymlinkLoop(_) => ErrorKind::Io,
+            FileError::Exists(_) | FileError::Symlink(_) | FileError::Escapes(_) => {
+                ErrorKind::Conflict
+            }
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            FileError::Error(path, _)
+            | FileError::Exists(path)
+            | FileError::Symlink(path)
+            | FileError::SymlinkLoop(path)
+            | FileError::Escapes(path) => Some(path),
+        }
+    }
+}
+
+/// What to do with files that already exist in the destination
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conflict {
+    Ask,
+    Skip,
+    Overwrite,
+    Abort,
+}
+
+impl std::str::FromStr for Conflict {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "ask" => Ok(Conflict::Ask),
+            "skip" => Ok(Conflict::Skip),
+            "overwrite" => Ok(Conflict::Overwrite),
+            "abort" => Ok(Conflict::Abort),
+            _ => Err(value.to_string()),
+        }
+    }
+}
+
+/// What copying does with symlinks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symlinks {
+    /// Recreate the link, pointing to the same target
+    Preserve,
+    /// Copy what the link points to
+    Follow,
+    Skip,
+    Error,
+}
+
+impl std::str::FromStr for Symlinks {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "preserve" => Ok(Symlinks::Preserve),
+            "follow" => Ok(Symlinks::Follow),
+            "skip" => Ok(Symlinks::Skip),
+            "error" => Ok(Symlinks::Error),
+            _ => Err(value.to_string()),
+        }
+    }
+}
+
+/// How `copy_recursive` copies files
+#[derive(Debug)]
+pub struct CopyOptions {
+    /// File names or glob patterns such as `*.log`, left out at any depth
+    pub exclude: Vec<String>,
+
+    /// What to do with files that already exist in the destination
+    pub conflict: Conflict,
+
+    /// What to do with symlinks in the source
+    pub symlinks: Symlinks,
+
+    /// Refuse to write outside of the destination through symlinks found there
+    pub confine: bool,
+
+    /// Keep the permissions of files and of the directories created
+    pub preserve_mode: bool,
+
+    /// Keep the modification times of files
+    pub preserve_mtime: bool,
+
+    /// Leave out the metadata files of cloups
+    pub skip_meta: bool,
+
+    /// Dotfiles given an escaped name in the destination, see `escape_name`
+    pub escape: Vec<String>,
+
+    /// Give escaped names their original name back in the destination
+    pub unescape: bool,
+
+    /// Contents written instead of those of some source files, such as
+    /// decrypted files
+    pub contents: HashMap<PathBuf, Vec<u8>>,
+
+    /// Only list what would be copied, without writing anything
+    pub dry_run: bool,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        CopyOptions {
+            exclude: vec![],
+            conflict: Conflict::Overwrite,
+            symlinks: Symlinks::Preserve,
+            confine: false,
+            preserve_mode: true,
+            preserve_mtime: false,
+            skip_meta: false,
+            escape: vec![],
+            unescape: false,
+            contents: HashMap::new(),
+            dry_run: false,
+        }
+    }
+}
+
+/// Files written and skipped by `copy_recursive`
+#[derive(Debug, Default)]
+pub struct Copied {
+    pub files: Vec<PathBuf>,
+
+    /// Source of each of `files`, in the same order
+    pub sources: Vec<PathBuf>,
+
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Match a file name against a pattern where `*` matches any run of
+/// characters and `?` a single character
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // let the last star swallow one more character
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
         }
     }
+
+    pattern[p..].iter().all(|c| *c == '*')
 }
 
-impl std::error::Error for FileError {}
+/// Check if a path is left out by one of the exclude patterns. Patterns with
+/// a directory, e.g. `src/target`, are matched by their last component.
+pub fn is_excluded(path: &Path, exclude: &[String]) -> bool {
+    let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else {
+        return false;
+    };
+
+    exclude.iter().any(|pattern| {
+        let pattern = Path::new(pattern)
+            .file_name()
+            .map(|p| p.to_string_lossy())
+            .unwrap_or_default();
+        glob_match(&pattern, &name)
+    })
+}
 
+/// Check if a path relative to a cloup matches one of the patterns, by its
+/// file name or as a whole with `/` as separator
+pub fn matches_path(path: &Path, patterns: &[String]) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let path: Vec<_> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    let path = path.join("/");
+
+    patterns
+        .iter()
+        .any(|p| glob_match(p, &name) || glob_match(p, &path))
+}
+
+/// Prefix of dotfiles stored under an escaped name, `.gitignore` being
+/// stored as `_dot_gitignore`
+pub const ESCAPE_PREFIX: &str = "_dot_";
+
+/// Escaped name of a dotfile matching one of the patterns, so tools acting on
+/// it leave it alone. Other names are kept.
+pub fn escape_name(name: &OsStr, escape: &[String]) -> OsString {
+    match name.to_str() {
+        Some(text) if escape.iter().any(|p| glob_match(p, text)) => match text.strip_prefix('.') {
+            Some(rest) if !rest.is_empty() => format!("{}{}", ESCAPE_PREFIX, rest).into(),
+            _ => name.to_os_string(),
+        },
+        _ => name.to_os_string(),
+    }
+}
+
+/// Original name of an escaped dotfile, whatever the escape setting was when
+/// it was stored
+pub fn unescape_name(name: &OsStr) -> OsString {
+    match name.to_str().and_then(|n| n.strip_prefix(ESCAPE_PREFIX)) {
+        // `_dot_.` would become `..`
+        Some(rest) if !rest.is_empty() && rest != "." => format!(".{}", rest).into(),
+        _ => name.to_os_string(),
+    }
+}
+
+/// Check if files can be created in a directory, the only reliable way to
+/// know being to try
+pub fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(".cloup-write-test");
+    match fs::write(&probe, "") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Copy the contents of `source` into `destination`
 pub fn copy_recursive(
     source: &Path,
     destination: &Path,
-    exclude: &[PathBuf],
-) -> Result<(), FileError> {
-    for entry in fs::read_dir(source).map_err(FileError::Error)? {
-        let entry = entry.map_err(FileError::Error)?;
-        let path = entry.path();
-        let filename = path
+    opts: &CopyOptions,
+) -> Result<Copied, FileError> {
+    Copier::run(opts, destination, |copier| {
+        copier.copy_dir(source, destination)
+    })
+}
+
+/// Copy a single file, directory or symlink to `destination`
+pub fn copy_path(
+    source: &Path,
+    destination: &Path,
+    opts: &CopyOptions,
+) -> Result<Copied, FileError> {
+    let root = destination.parent().unwrap_or(destination);
+    Copier::run(opts, root, |copier| copier.copy_entry(source, destination))
+}
+
+struct Copier<'a> {
+    opts: &'a CopyOptions,
+    conflict: Conflict,
+
+    /// Only look for conflicts, without writing anything
+    dry_run: bool,
+
+    /// Canonical destination, nothing is written outside of it when confined
+    root: PathBuf,
+
+    /// Canonical directories being copied, to catch symlink loops
+    visited: Vec<PathBuf>,
+
+    copied: Copied,
+}
+
+impl<'a> Copier<'a> {
+    fn run(
+        opts: &'a CopyOptions,
+        root: &Path,
+        copy: impl Fn(&mut Copier) -> Result<(), FileError>,
+    ) -> Result<Copied, FileError> {
+        // Without anyone to ask, refuse like abort would
+        let conflict = match opts.conflict {
+            Conflict::Ask if output::is_json() || !std::io::stdin().is_terminal() => {
+                Conflict::Abort
+            }
+            conflict => conflict,
+        };
+
+        let mut copier = Copier {
+            opts,
+            conflict,
+            dry_run: opts.dry_run,
+            root: fs::canonicalize(root).map_err(|e| FileError::Error(root.to_path_buf(), e))?,
+            visited: vec![],
+            copied: Copied::default(),
+        };
+
+        // Check everything up front so nothing is written when aborting
+        if conflict == Conflict::Abort {
+            copier.dry_run = true;
+            copy(&mut copier)?;
+            copier.dry_run = opts.dry_run;
+            copier.copied = Copied::default();
+        }
+
+        copy(&mut copier)?;
+        Ok(copier.copied)
+    }
+
+    fn copy_dir(&mut self, source: &Path, destination: &Path) -> Result<(), FileError> {
+        let canonical =
+            fs::canonicalize(source).map_err(|e| FileError::Error(source.to_path_buf(), e))?;
+        if self.visited.contains(&canonical) {
+            return Err(FileError::SymlinkLoop(source.to_path_buf()));
+        }
+        self.visited.push(canonical);
+
+        for entry in fs::read_dir(source).map_err(|e| FileError::Error(source.to_path_buf(), e))? {
+            let entry = entry.map_err(|e| FileError::Error(source.to_path_buf(), e))?;
+            let name = if self.opts.unescape {
+                unescape_name(&entry.file_name())
+            } else {
+                escape_name(&entry.file_name(), &self.opts.escape)
+            };
+            self.copy_entry(&entry.path(), &destination.join(name))?;
+        }
+
+        self.visited.pop();
+        Ok(())
+    }
+
+    fn copy_entry(&mut self, path: &Path, destination: &Path) -> Result<(), FileError> {
+        let is_meta = path
             .file_name()
-            .ok_or(FileError::Error(std::io::ErrorKind::InvalidInput.into()))?;
-        let destination = destination.join(filename);
-
-        // if glob matches filename, e.g. *.ts* should remove any file extension that starts with .ts
-        if exclude.iter().any(|p| p.is_dir()) {
-            // if filename matches directory
-            if exclude
-                .iter()
-                .any(|p| p.is_dir() && p.file_name() == Some(filename))
-            {
-                println!(
-                    "\x1b[1;31m 2 Skipping {:?} because it's in exclude\x1b[0m",
-                    &path
-                );
-                continue;
+            .is_some_and(|name| name == META_FILE || name == KEEP_FILE);
+        if self.opts.skip_meta && (is_meta || is_partial_archive(path)) {
+            return Ok(());
+        }
+
+        if is_excluded(path, &self.opts.exclude) {
+            self.note(format!(
+                "\x1b[1;31mSkipping {:?} because it's in exclude\x1b[0m",
+                path
+            ));
+            return Ok(());
+        }
+
+        let error = |e| FileError::Error(path.to_path_buf(), e);
+        let mut metadata = fs::symlink_metadata(path).map_err(error)?;
+
+        if metadata.file_type().is_symlink() {
+            match self.opts.symlinks {
+                Symlinks::Skip => {
+                    self.note(format!("\x1b[1;31mSkipping symlink {:?}\x1b[0m", path));
+                    return Ok(());
+                }
+                Symlinks::Error => return Err(FileError::Symlink(path.to_path_buf())),
+                Symlinks::Preserve => {
+                    if !self.make_room(destination)? {
+                        return Ok(());
+                    }
+                    if !self.dry_run {
+                        copy_link(path, destination)?;
+                    }
+                    self.copied.files.push(destination.to_path_buf());
+                    self.copied.sources.push(path.to_path_buf());
+                    return Ok(());
+                }
+                Symlinks::Follow => metadata = fs::metadata(path).map_err(error)?,
+            }
+        }
+
+        if metadata.is_dir() {
+            // a destination inside the source, e.g. a workspace in the current directory
+            if fs::canonicalize(path).is_ok_and(|p| p == self.root) {
+                return Ok(());
+            }
+
+            let created = !destination.exists();
+            if !self.dry_run {
+                self.confine(destination)?;
+                fs::create_dir_all(destination)
+                    .map_err(|e| FileError::Error(destination.to_path_buf(), e))?;
             }
+            self.copy_dir(path, destination)?;
+
+            // set last, a read-only directory couldn't be filled. Directories
+            // that were already there keep their permissions.
+            if created && !self.dry_run && self.opts.preserve_mode {
+                fs::set_permissions(destination, metadata.permissions())
+                    .map_err(|e| FileError::Error(destination.to_path_buf(), e))?;
+            }
+            return Ok(());
+        }
+
+        if !self.make_room(destination)? {
+            return Ok(());
+        }
+        if !self.dry_run {
+            copy_file(path, destination, self.opts).map_err(error)?;
+        }
+        self.copied.files.push(destination.to_path_buf());
+        self.copied.sources.push(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Check that a file can be written to `destination`, removing what's there
+    /// if it's to be overwritten. Returns false if the file is skipped.
+    fn make_room(&mut self, destination: &Path) -> Result<bool, FileError> {
+        let Ok(existing) = fs::symlink_metadata(destination) else {
+            if !self.dry_run {
+                self.confine(destination)?;
+            }
+            return Ok(true);
+        };
+
+        if !overwrite(destination, &mut self.conflict)? {
+            self.copied.skipped.push(destination.to_path_buf());
+            return Ok(false);
+        }
+
+        if !self.dry_run {
+            self.confine(destination)?;
+
+            // symlinks are replaced, never written through
+            if existing.file_type().is_symlink() {
+                fs::remove_file(destination)
+                    .map_err(|e| FileError::Error(destination.to_path_buf(), e))?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Refuse destinations whose directory resolves outside of the root
+    fn confine(&self, destination: &Path) -> Result<(), FileError> {
+        if !self.opts.confine {
+            return Ok(());
         }
 
-        // if filename is *.ts (or any other glob pattern)
-        if exclude.iter().any(|p| p.is_file()) {
-            // if filename matches file
-            if exclude
-                .iter()
-                .any(|p| p.is_file() && p.file_name() == Some(filename))
-            {
-                println!(
-                    "\x1b[1;31m 3 Skipping {:?} because it's in exclude\x1b[0m",
-                    &path
-                );
-                continue;
+        let parent = destination.parent().unwrap_or(destination);
+        match fs::canonicalize(parent) {
+            Ok(parent) if parent.starts_with(&self.root) => Ok(()),
+            _ => Err(FileError::Escapes(destination.to_path_buf())),
+        }
+    }
+
+    /// Print a note, unless output is JSON or nothing is being written yet
+    fn note(&self, text: String) {
+        if !self.dry_run && !output::is_json() {
+            output::println(text);
+        }
+    }
+}
+
+/// Decide whether an existing file is overwritten, asking if needed
+fn overwrite(path: &Path, conflict: &mut Conflict) -> Result<bool, FileError> {
+    match conflict {
+        Conflict::Overwrite => Ok(true),
+        Conflict::Skip => Ok(false),
+        Conflict::Abort => Err(FileError::Exists(path.to_path_buf())),
+        Conflict::Ask => {
+            output::print(format!(
+                "\x1b[1;33m»\x1b[0m '{}' already exists, overwrite? [y]es, [n]o, [a]ll, [q]uit: ",
+                path.to_string_lossy()
+            ));
+
+            let mut answer = String::new();
+            std::io::stdin()
+                .read_line(&mut answer)
+                .map_err(|e| FileError::Error(path.to_path_buf(), e))?;
+
+            match answer.trim() {
+                "y" | "yes" => Ok(true),
+                "a" | "all" => {
+                    *conflict = Conflict::Overwrite;
+                    Ok(true)
+                }
+                "q" | "quit" => Err(FileError::Exists(path.to_path_buf())),
+                _ => Ok(false),
             }
         }
+    }
+}
 
-        if path.is_dir() {
-            fs::create_dir_all(&destination).map_err(FileError::Error)?;
-            copy_recursive(&path, &destination, exclude)?;
-        } else {
-            fs::copy(&path, &destination).map_err(FileError::Error)?;
+/// Copy the contents of a file, and its permissions and modification time if
+/// asked to
+fn copy_file(path: &Path, destination: &Path, opts: &CopyOptions) -> std::io::Result<()> {
+    if let Some(content) = opts.contents.get(path) {
+        fs::write(destination, content)?;
+        if opts.preserve_mode {
+            fs::set_permissions(destination, fs::metadata(path)?.permissions())?;
         }
+    } else if opts.preserve_mode {
+        // fs::copy carries the permissions over
+        fs::copy(path, destination)?;
+    } else {
+        // new files get the default permissions, existing ones keep theirs
+        std::io::copy(
+            &mut fs::File::open(path)?,
+            &mut fs::File::create(destination)?,
+        )?;
     }
+
+    if opts.preserve_mtime {
+        let modified = fs::metadata(path)?.modified()?;
+
+        // owning the file is enough on unix, even when it's read-only
+        #[cfg(unix)]
+        let file = fs::File::open(destination)?;
+        #[cfg(not(unix))]
+        let file = fs::File::options().write(true).open(destination)?;
+
+        file.set_modified(modified)?;
+    }
+
     Ok(())
 }
+
+/// Create a symlink at `link` pointing to `target`, which on Windows is a
+/// directory link if the target is a directory
+pub fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(target, link);
+
+    #[cfg(windows)]
+    return if link.parent().unwrap_or(link).join(target).is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    };
+}
+
+/// Recreate a symlink at `destination`, pointing to the same target
+fn copy_link(path: &Path, destination: &Path) -> Result<(), FileError> {
+    let target = fs::read_link(path).map_err(|e| FileError::Error(path.to_path_buf(), e))?;
+
+    // symlinks are never written through
+    if fs::symlink_metadata(destination).is_ok() {
+        fs::remove_file(destination).map_err(|e| FileError::Error(destination.to_path_buf(), e))?;
+    }
+
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(&target, destination);
+
+    #[cfg(windows)]
+    let result = if path.is_dir() {
+        std::os::windows::fs::symlink_dir(&target, destination)
+    } else {
+        std::os::windows::fs::symlink_file(&target, destination)
+    };
+
+    result.map_err(|e| FileError::Error(path.to_path_buf(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{write_tree, TempDir};
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match(".DS_Store", ".DS_Store"));
+        assert!(glob_match("*.log", "debug.log"));
+        assert!(glob_match("*.ts*", "index.tsx"));
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("*.log", "debug.log.gz"));
+        assert!(!glob_match("target", "targets"));
+        assert!(is_excluded(
+            Path::new("/project/src/node_modules"),
+            &["node_modules".to_string()]
+        ));
+    }
+
+    #[test]
+    fn escaped_names() {
+        let escape = vec![".gitignore".to_string(), ".npm*".to_string()];
+        let escaped = |name: &str| escape_name(OsStr::new(name), &escape);
+
+        assert_eq!(escaped(".gitignore"), "_dot_gitignore");
+        assert_eq!(escaped(".npmrc"), "_dot_npmrc");
+        assert_eq!(escaped(".prettierrc"), ".prettierrc");
+        assert_eq!(unescape_name(OsStr::new("_dot_gitignore")), ".gitignore");
+        assert_eq!(unescape_name(OsStr::new("_dot_")), "_dot_");
+        assert_eq!(unescape_name(OsStr::new("_dot_.")), "_dot_.");
+        assert_eq!(unescape_name(OsStr::new("index.ts")), "index.ts");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_policies() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("links");
+        write_tree(
+            &dir,
+            &[("source/file", ""), ("target/", ""), ("outside/", "")],
+        );
+        let (source, target, outside) =
+            (dir.join("source"), dir.join("target"), dir.join("outside"));
+        symlink("file", source.join("link")).unwrap();
+        symlink(".", source.join("loop")).unwrap();
+
+        let opts = |symlinks| CopyOptions {
+            symlinks,
+            confine: true,
+            ..CopyOptions::default()
+        };
+
+        let copied = copy_recursive(&source, &target, &opts(Symlinks::Preserve)).unwrap();
+        assert_eq!(copied.files.len(), 3);
+        assert_eq!(
+            fs::read_link(target.join("link")).unwrap(),
+            Path::new("file")
+        );
+
+        assert!(matches!(
+            copy_recursive(&source, &target, &opts(Symlinks::Follow)),
+            Err(FileError::SymlinkLoop(_))
+        ));
+        assert!(matches!(
+            copy_recursive(&source, &target, &opts(Symlinks::Error)),
+            Err(FileError::Symlink(_))
+        ));
+
+        // a directory of the target leading elsewhere is never written through
+        fs::remove_file(source.join("loop")).unwrap();
+        write_tree(&source, &[("sub/file", "")]);
+        symlink(&outside, target.join("sub")).unwrap();
+        assert!(matches!(
+            copy_recursive(&source, &target, &opts(Symlinks::Skip)),
+            Err(FileError::Escapes(_))
+        ));
+        assert!(!outside.join("file").exists());
+    }
+}
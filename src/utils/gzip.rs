@@ -0,0 +1,769 @@
+// gzip compression without dependencies: deflate (RFC 1951) with fixed
+// Huffman codes or stored blocks for writing, and every kind of block for
+// reading streams written by other tools, wrapped as gzip (RFC 1952).
+
+use std::io::Write;
+
+/// Bytes every gzip stream starts with
+pub const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// Positions looked at for a match, more compresses better but slower
+const MAX_CHAIN: usize = 64;
+
+/// Input compressed per block, each one written as stored if that's shorter
+const BLOCK: usize = 64 * 1024;
+
+/// Bound on the size of decompressed data, so a crafted stream can't exhaust
+/// memory
+pub const MAX_INFLATED_SIZE: usize = 1 << 30;
+
+const LEN_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LEN_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order in which the lengths of the code length code are written
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+#[derive(Debug, PartialEq)]
+pub enum GzipError {
+    /// Not a gzip stream, or one using features that don't exist
+    Malformed,
+    /// The stream ends early
+    Truncated,
+    /// The content doesn't match the checksum of the stream
+    Checksum,
+    /// The content is larger than `MAX_INFLATED_SIZE`
+    TooLarge,
+}
+
+impl std::fmt::Display for GzipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GzipError::Malformed => write!(f, "not a valid gzip stream"),
+            GzipError::Truncated => write!(f, "the gzip stream ends early"),
+            GzipError::Checksum => write!(f, "the gzip stream is corrupt"),
+            GzipError::TooLarge => write!(
+                f,
+                "the gzip stream holds more than {} MiB",
+                MAX_INFLATED_SIZE >> 20
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GzipError {}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    update_crc32(0, data)
+}
+
+/// Continue a CRC-32 with more data
+fn update_crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+
+    !data.iter().fold(!crc, |crc, b| {
+        table[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Compress data into a gzip stream (RFC 1952), with fixed Huffman codes or
+/// stored blocks
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut writer = GzipWriter::new(vec![]);
+    // writing to a vector doesn't fail
+    let _ = writer.write_all(data);
+    writer.finish().unwrap_or_default()
+}
+
+/// Compresses what's written to it into a gzip stream written to `inner` as
+/// it goes, keeping no more than a block and the window before it
+pub struct GzipWriter<W: Write> {
+    inner: W,
+    writer: BitWriter,
+    matcher: Matcher,
+
+    /// The window of data already compressed, then data still to compress
+    data: Vec<u8>,
+    start: usize,
+
+    crc: u32,
+    size: u64,
+}
+
+impl<W: Write> GzipWriter<W> {
+    pub fn new(inner: W) -> Self {
+        // no name and no modification time, the same data always gives the same stream
+        let header = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+        GzipWriter {
+            inner,
+            writer: BitWriter {
+                out: header,
+                bits: 0,
+                count: 0,
+            },
+            matcher: Matcher::new(),
+            data: vec![],
+            start: 0,
+            crc: 0,
+            size: 0,
+        }
+    }
+
+    /// Bytes written so far, before compression
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Compress what's left and write the end of the stream, giving back
+    /// what it was written to
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.block(true);
+        self.writer.align();
+        self.writer.out.extend(self.crc.to_le_bytes());
+        self.writer.out.extend((self.size as u32).to_le_bytes());
+        self.inner.write_all(&self.writer.out)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Compress the data still to compress as one block, then keep only the
+    /// window matches of the next one can refer to
+    fn block(&mut self, last: bool) {
+        let (data, start, end) = (&self.data, self.start, self.data.len());
+        self.crc = update_crc32(self.crc, &data[start..end]);
+        let tokens = self.matcher.tokens(data, start, end);
+
+        // literals above 143 take 9 bits, data that doesn't compress is stored
+        if fixed_cost(&tokens) < (end - start) as u64 * 8 || start == end {
+            self.writer.bits(last as u32, 1);
+            self.writer.bits(1, 2);
+            write_fixed(&mut self.writer, &tokens);
+        } else {
+            for (i, chunk) in data[start..end].chunks(u16::MAX as usize).enumerate() {
+                let final_chunk = last && start + (i + 1) * u16::MAX as usize >= end;
+                self.writer.bits(final_chunk as u32, 1);
+                self.writer.bits(0, 2);
+                self.writer.align();
+                self.writer.out.extend((chunk.len() as u16).to_le_bytes());
+                self.writer
+                    .out
+                    .extend((!(chunk.len() as u16)).to_le_bytes());
+                self.writer.out.extend(chunk);
+            }
+        }
+
+        // positions move back with the data, so the matcher starts over
+        // from the window
+        let drop = end.saturating_sub(WINDOW);
+        self.data.drain(..drop);
+        self.start = self.data.len();
+        self.matcher = Matcher::new();
+        for pos in 0..self.start {
+            self.matcher.insert(&self.data, pos);
+        }
+    }
+}
+
+impl<W: Write> Write for GzipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.start + BLOCK - self.data.len());
+        self.data.extend(&buf[..len]);
+        self.size += len as u64;
+
+        if self.data.len() - self.start == BLOCK {
+            self.block(false);
+            // whole bytes are written out, the bits of the last one kept
+            let out = std::mem::take(&mut self.writer.out);
+            self.inner.write_all(&out)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decompress a gzip stream, checking it against its checksum. Streams
+/// written by other tools can be read too.
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, GzipError> {
+    if data.len() < 18 {
+        return Err(GzipError::Truncated);
+    }
+    if !data.starts_with(GZIP_MAGIC) || data[2] != 8 || data[3] & 0xe0 != 0 {
+        return Err(GzipError::Malformed);
+    }
+
+    let flags = data[3];
+    let mut pos = 10;
+    let skip = |pos: usize, len: usize| {
+        pos.checked_add(len)
+            .filter(|end| *end <= data.len())
+            .ok_or(GzipError::Truncated)
+    };
+    let skip_string = |pos: usize| {
+        data.get(pos..)
+            .and_then(|rest| rest.iter().position(|b| *b == 0))
+            .map(|end| pos + end + 1)
+            .ok_or(GzipError::Truncated)
+    };
+
+    // FEXTRA, FNAME, FCOMMENT, FHCRC, none of them running past the data
+    if flags & 4 != 0 {
+        let len = data.get(pos..pos + 2).ok_or(GzipError::Truncated)?;
+        pos = skip(pos + 2, u16::from_le_bytes([len[0], len[1]]) as usize)?;
+    }
+    if flags & 8 != 0 {
+        pos = skip_string(pos)?;
+    }
+    if flags & 16 != 0 {
+        pos = skip_string(pos)?;
+    }
+    if flags & 2 != 0 {
+        pos = skip(pos, 2)?;
+    }
+    if pos + 8 > data.len() {
+        return Err(GzipError::Truncated);
+    }
+
+    let (out, used) = inflate(&data[pos..data.len() - 8], MAX_INFLATED_SIZE)?;
+    let trailer = &data[pos + used..];
+    if trailer.len() < 8 {
+        return Err(GzipError::Truncated);
+    }
+
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out) || size != out.len() as u32 {
+        return Err(GzipError::Checksum);
+    }
+
+    Ok(out)
+}
+
+/// Writes bits least significant first, as deflate packs them
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting from their most significant bit
+    fn code(&mut self, code: u32, len: u32) {
+        self.bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.bits(0, 8 - self.count);
+        }
+    }
+}
+
+enum Token {
+    Literal(u8),
+    Match(u16, u16),
+}
+
+/// Finds earlier occurrences of the data at a position through chains of
+/// positions sharing the same three bytes
+struct Matcher {
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl Matcher {
+    const NONE: usize = usize::MAX;
+    const HASH_BITS: u32 = 15;
+
+    fn new() -> Self {
+        Matcher {
+            head: vec![Self::NONE; 1 << Self::HASH_BITS],
+            prev: vec![Self::NONE; WINDOW],
+        }
+    }
+
+    fn hash(data: &[u8], pos: usize) -> usize {
+        let value = (data[pos] as u32) << 16 | (data[pos + 1] as u32) << 8 | data[pos + 2] as u32;
+        (value.wrapping_mul(2_654_435_761) >> (32 - Self::HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, data: &[u8], pos: usize) {
+        if pos + MIN_MATCH <= data.len() {
+            let hash = Self::hash(data, pos);
+            self.prev[pos % WINDOW] = self.head[hash];
+            self.head[hash] = pos;
+        }
+    }
+
+    fn longest(&self, data: &[u8], pos: usize, end: usize) -> Option<(usize, usize)> {
+        if pos + MIN_MATCH > end {
+            return None;
+        }
+
+        let max = (end - pos).min(MAX_MATCH);
+        let mut best: Option<(usize, usize)> = None;
+        let mut candidate = self.head[Self::hash(data, pos)];
+
+        for _ in 0..MAX_CHAIN {
+            if candidate == Self::NONE || candidate >= pos || pos - candidate > WINDOW {
+                break;
+            }
+
+            let len = data[candidate..]
+                .iter()
+                .zip(&data[pos..pos + max])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len >= MIN_MATCH && best.is_none_or(|(l, _)| len > l) {
+                best = Some((len, pos - candidate));
+                if len == max {
+                    break;
+                }
+            }
+
+            let next = self.prev[candidate % WINDOW];
+            // the slot was reused by a newer position
+            if next != Self::NONE && next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+
+        best
+    }
+
+    fn tokens(&mut self, data: &[u8], start: usize, end: usize) -> Vec<Token> {
+        let mut tokens = vec![];
+        let mut pos = start;
+
+        while pos < end {
+            match self.longest(data, pos, end) {
+                Some((len, dist)) => {
+                    tokens.push(Token::Match(len as u16, dist as u16));
+                    for p in pos..pos + len {
+                        self.insert(data, p);
+                    }
+                    pos += len;
+                }
+                None => {
+                    tokens.push(Token::Literal(data[pos]));
+                    self.insert(data, pos);
+                    pos += 1;
+                }
+            }
+        }
+
+        tokens
+    }
+}
+
+fn length_code(len: u16) -> usize {
+    LEN_BASE.iter().rposition(|base| *base <= len).unwrap_or(0)
+}
+
+fn dist_code(dist: u16) -> usize {
+    DIST_BASE
+        .iter()
+        .rposition(|base| *base <= dist)
+        .unwrap_or(0)
+}
+
+/// Fixed Huffman code of a literal or length symbol, with its length
+fn fixed_code(symbol: usize) -> (u32, u32) {
+    match symbol {
+        0..=143 => (0x30 + symbol as u32, 8),
+        144..=255 => (0x190 + (symbol - 144) as u32, 9),
+        256..=279 => ((symbol - 256) as u32, 7),
+        _ => (0xc0 + (symbol - 280) as u32, 8),
+    }
+}
+
+fn fixed_cost(tokens: &[Token]) -> u64 {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Literal(b) => fixed_code(*b as usize).1 as u64,
+            Token::Match(len, dist) => {
+                let (l, d) = (length_code(*len), dist_code(*dist));
+                (fixed_code(257 + l).1 + LEN_EXTRA[l] as u32 + 5 + DIST_EXTRA[d] as u32) as u64
+            }
+        })
+        .sum::<u64>()
+        + 7
+}
+
+fn write_fixed(writer: &mut BitWriter, tokens: &[Token]) {
+    for token in tokens {
+        match token {
+            Token::Literal(b) => {
+                let (code, len) = fixed_code(*b as usize);
+                writer.code(code, len);
+            }
+            Token::Match(len, dist) => {
+                let l = length_code(*len);
+                let (code, bits) = fixed_code(257 + l);
+                writer.code(code, bits);
+                writer.bits((len - LEN_BASE[l]) as u32, LEN_EXTRA[l] as u32);
+
+                let d = dist_code(*dist);
+                writer.code(d as u32, 5);
+                writer.bits((dist - DIST_BASE[d]) as u32, DIST_EXTRA[d] as u32);
+            }
+        }
+    }
+
+    let (code, len) = fixed_code(256);
+    writer.code(code, len);
+}
+
+/// Reads bits least significant first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> Result<u32, GzipError> {
+        let byte = *self.data.get(self.pos).ok_or(GzipError::Truncated)?;
+        let bit = (byte >> self.bit) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, GzipError> {
+        let mut value = 0;
+        for i in 0..count {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit > 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// Canonical Huffman code, as the number of codes of each length and the
+/// symbols ordered by code
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, GzipError> {
+        let mut counts = [0u16; 16];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // more codes of a length than there's room for
+        let mut left: i32 = 1;
+        for count in &counts[1..] {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return Err(GzipError::Malformed);
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                symbols[offsets[*len as usize] as usize] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, GzipError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for len in 1..16 {
+            code |= reader.bit()? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(GzipError::Malformed)
+    }
+}
+
+/// Decompress a deflate stream of at most `limit` bytes, along with the
+/// number of bytes it took
+fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), GzipError> {
+    let mut reader = BitReader {
+        data,
+        pos: 0,
+        bit: 0,
+    };
+    let mut out = vec![];
+
+    loop {
+        let last = reader.bit()? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = data
+                    .get(reader.pos..reader.pos + 4)
+                    .ok_or(GzipError::Truncated)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err(GzipError::Malformed);
+                }
+
+                let start = reader.pos + 4;
+                let block = data
+                    .get(start..start + len as usize)
+                    .ok_or(GzipError::Truncated)?;
+                if out.len() + block.len() > limit {
+                    return Err(GzipError::TooLarge);
+                }
+                out.extend(block);
+                reader.pos = start + len as usize;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths)?;
+                let distances = Huffman::new(&[5; 30])?;
+                inflate_block(&mut reader, &mut out, limit, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut out, limit, &literals, &distances)?;
+            }
+            _ => return Err(GzipError::Malformed),
+        }
+
+        if last {
+            reader.align();
+            return Ok((out, reader.pos));
+        }
+    }
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), GzipError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(GzipError::Malformed);
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for i in CODE_LENGTH_ORDER.iter().take(code_count) {
+        code_lengths[*i] = reader.bits(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (
+                *lengths.last().ok_or(GzipError::Malformed)?,
+                3 + reader.bits(2)?,
+            ),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count || lengths[256] == 0 {
+        return Err(GzipError::Malformed);
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    limit: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), GzipError> {
+    loop {
+        // a match being at most 258 bytes, checked once per symbol
+        if out.len() + MAX_MATCH > limit {
+            return Err(GzipError::TooLarge);
+        }
+
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let l = symbol - 257;
+                if l >= LEN_BASE.len() {
+                    return Err(GzipError::Malformed);
+                }
+                let len = LEN_BASE[l] as usize + reader.bits(LEN_EXTRA[l] as u32)? as usize;
+
+                let d = distances.decode(reader)? as usize;
+                if d >= DIST_BASE.len() {
+                    return Err(GzipError::Malformed);
+                }
+                let dist = DIST_BASE[d] as usize + reader.bits(DIST_EXTRA[d] as u32)? as usize;
+                if dist > out.len() {
+                    return Err(GzipError::Malformed);
+                }
+
+                // a match may overlap the bytes it writes
+                let start = out.len() - dist;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        let text = "A cloup is a template of files applied anywhere. ".repeat(500);
+        let noise: Vec<u8> = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+
+        for data in [&b""[..], b"a", text.as_bytes(), &noise] {
+            let compressed = gzip(data);
+            assert_eq!(gunzip(&compressed).unwrap(), data);
+        }
+        assert!(gzip(text.as_bytes()).len() < text.len() / 10);
+        assert!(gzip(&noise).len() < noise.len() + 100);
+
+        // written by gzip with a dynamic Huffman code
+        let stream = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x35, 0x88, 0x41, 0x0d,
+            0x00, 0x00, 0x08, 0x84, 0xb2, 0x42, 0xff, 0x10, 0xea, 0xa9, 0x3c, 0xd8, 0x00, 0x1b,
+            0xa4, 0x31, 0x26, 0x7d, 0x0b, 0x93, 0x5c, 0xed, 0xf7, 0x99, 0x51, 0x21, 0x47, 0x57,
+            0x06, 0x40, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            gunzip(&stream).unwrap(),
+            b"abbbbabaaaabbaaaaabbbabbabaaaaababbbaaabaaaaaabbababbbbbbbbbaaab"
+        );
+
+        let mut corrupt = gzip(text.as_bytes());
+        let at = corrupt.len() - 6;
+        corrupt[at] ^= 1;
+        assert_eq!(gunzip(&corrupt), Err(GzipError::Checksum));
+    }
+
+    // header fields running past the end of the stream, and a stream
+    // inflating to more than allowed
+    #[test]
+    fn malformed() {
+        let stream = gzip(b"A cloup is a template of files applied anywhere.");
+        let with_flags = |flags: u8, field: &[u8]| {
+            let mut data = stream[..10].to_vec();
+            data[3] = flags;
+            data.extend(field);
+            data.extend(&stream[10..]);
+            data
+        };
+
+        for (flags, field) in [
+            (4, &[0xff, 0xff][..]),
+            (4 | 8, &[0xff, 0xff]),
+            (4, &[0x40, 0x00, 0x01]),
+            (8, b"no terminating zero"),
+            (16, b"no terminating zero"),
+            (4 | 8 | 16, &[0x00, 0x00, b'a']),
+        ] {
+            let data = with_flags(flags, field);
+            for len in [data.len(), 18, 20] {
+                assert!(gunzip(&data[..len]).is_err());
+            }
+        }
+        let mut short = with_flags(2, &[]);
+        short.truncate(19);
+        assert!(gunzip(&short).is_err());
+
+        let named = with_flags(8 | 16, b"name\0comment\0");
+        assert_eq!(
+            gunzip(&named).unwrap(),
+            b"A cloup is a template of files applied anywhere."
+        );
+
+        let zeros = gzip(&[0; 100_000]);
+        assert_eq!(inflate(&zeros[10..], 10_000), Err(GzipError::TooLarge));
+    }
+}
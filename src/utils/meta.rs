@@ -0,0 +1,306 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::file::{unescape_name, FileError};
+use crate::toml::data::{Toml, TomlValue, TomlValueKind};
+
+/// File kept in every cloup with what the storage of its workspace may lose,
+/// such as permissions in a git repository. It's never applied.
+pub const META_FILE: &str = ".cloup-meta.toml";
+
+/// Empty file keeping an otherwise empty directory of a cloup, which storage
+/// such as git would drop. It's never applied, the directory is.
+pub const KEEP_FILE: &str = ".cloup-keep";
+
+/// Metadata of a cloup, read from and written to its `META_FILE`
+#[derive(Debug, Default)]
+pub struct CloupMeta {
+    /// Permission bits of files, by the path they're applied to relative to
+    /// the destination, with escaped names given back their original name
+    pub modes: Vec<(PathBuf, u32)>,
+
+    /// Encrypted files, by their path in the cloup
+    pub encrypted: Vec<PathBuf>,
+}
+
+impl CloupMeta {
+    /// Read the metadata of a cloup, empty if it has none. Entries that can't
+    /// be understood are left out.
+    pub fn read(cloup_path: &Path) -> Result<Self, FileError> {
+        let path = cloup_path.join(META_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(FileError::Error(path, e)),
+        };
+
+        let toml = Toml::from(content);
+        let modes = match toml.get_in("files", "modes") {
+            Some(TomlValueKind::Array(values)) => values
+                .iter()
+                .filter_map(|v| match v {
+                    TomlValueKind::String(entry) => parse_mode(entry),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+        let encrypted = match toml.get_in("files", "encrypted") {
+            Some(TomlValueKind::Array(values)) => values
+                .iter()
+                .filter_map(|v| match v {
+                    TomlValueKind::String(path) => parse_path(path),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        Ok(CloupMeta { modes, encrypted })
+    }
+
+    /// Write the metadata of a cloup, nothing is written when it's empty
+    pub fn write(&self, cloup_path: &Path) -> Result<(), FileError> {
+        if self.modes.is_empty() && self.encrypted.is_empty() {
+            return Ok(());
+        }
+
+        let mut files = vec![];
+        if !self.modes.is_empty() {
+            files.push(TomlValue {
+                key: "modes".to_string(),
+                kind: TomlValueKind::Array(
+                    self.modes
+                        .iter()
+                        .map(|(path, mode)| TomlValueKind::String(format_mode(path, *mode)))
+                        .collect(),
+                ),
+            });
+        }
+        if !self.encrypted.is_empty() {
+            files.push(TomlValue {
+                key: "encrypted".to_string(),
+                kind: TomlValueKind::Array(
+                    self.encrypted
+                        .iter()
+                        .map(|path| TomlValueKind::String(format_path(path)))
+                        .collect(),
+                ),
+            });
+        }
+        let toml = Toml::from(Some(vec![TomlValue {
+            key: "files".to_string(),
+            kind: TomlValueKind::Table(files),
+        }]));
+
+        let path = cloup_path.join(META_FILE);
+        fs::write(&path, toml.to_toml()).map_err(|e| FileError::Error(path, e))
+    }
+
+    /// Record the permissions of files copied into a cloup
+    pub fn from_files(cloup_path: &Path, files: &[PathBuf]) -> Result<Self, FileError> {
+        let mut meta = CloupMeta::default();
+
+        for file in files {
+            let metadata =
+                fs::symlink_metadata(file).map_err(|e| FileError::Error(file.clone(), e))?;
+            if !metadata.is_file() {
+                continue;
+            }
+
+            if let (Some(applied), Some(mode)) =
+                (applied_path(cloup_path, file), file_mode(&metadata))
+            {
+                meta.modes.push((applied, mode));
+            }
+        }
+
+        Ok(meta)
+    }
+
+    /// Give files copied out of the cloup into `root` the permissions
+    /// recorded for them
+    pub fn restore_modes(&self, root: &Path, files: &[PathBuf]) -> Result<(), FileError> {
+        for file in files {
+            let Ok(relative) = file.strip_prefix(root) else {
+                continue;
+            };
+            let Some((_, mode)) = self.modes.iter().find(|(path, _)| path == relative) else {
+                continue;
+            };
+
+            // permissions of a symlink would be set on what it points to
+            let is_file = fs::symlink_metadata(file).is_ok_and(|m| m.is_file());
+            if is_file {
+                set_mode(file, *mode).map_err(|e| FileError::Error(file.clone(), e))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Path a file of a cloup is applied to, relative to the destination, with
+/// escaped names given back their original name
+pub fn applied_path(cloup_path: &Path, file: &Path) -> Option<PathBuf> {
+    let relative = file.strip_prefix(cloup_path).ok()?;
+    Some(
+        relative
+            .components()
+            .map(|c| unescape_name(c.as_os_str()))
+            .collect(),
+    )
+}
+
+/// Put a `KEEP_FILE` in every empty directory below a cloup
+pub fn keep_empty_dirs(dir: &Path) -> Result<(), FileError> {
+    let error = |e| FileError::Error(dir.to_path_buf(), e);
+
+    for entry in fs::read_dir(dir).map_err(error)? {
+        let path = entry.map_err(error)?.path();
+        let is_dir = fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir());
+        if !is_dir {
+            continue;
+        }
+
+        let empty = fs::read_dir(&path)
+            .map_err(|e| FileError::Error(path.clone(), e))?
+            .next()
+            .is_none();
+        if empty {
+            let keep = path.join(KEEP_FILE);
+            fs::write(&keep, "").map_err(|e| FileError::Error(keep, e))?;
+        } else {
+            keep_empty_dirs(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Format a mode as `0755 path/to/file`, with `/` as separator on every platform
+fn format_mode(path: &Path, mode: u32) -> String {
+    format!("{:04o} {}", mode, format_path(path))
+}
+
+fn parse_mode(entry: &str) -> Option<(PathBuf, u32)> {
+    let (mode, path) = entry.split_once(' ')?;
+    let mode = u32::from_str_radix(mode, 8).ok().filter(|m| *m <= 0o7777)?;
+    Some((path.split('/').collect(), mode))
+}
+
+fn format_path(path: &Path) -> String {
+    let path: Vec<_> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    path.join("/")
+}
+
+/// Path relative to the cloup, none if it would lead outside of it
+fn parse_path(path: &str) -> Option<PathBuf> {
+    let safe = path
+        .split('/')
+        .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+    safe.then(|| path.split('/').collect())
+}
+
+#[cfg(unix)]
+pub fn file_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+pub fn file_mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+pub fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+pub fn set_mode(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{write_tree, TempDir};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn modes() {
+        let dir = TempDir::new("meta");
+        write_tree(
+            &dir,
+            &[
+                ("cl_scripts/scripts/setup.sh", ""),
+                ("target/scripts/setup.sh", ""),
+            ],
+        );
+        let (cloup, target) = (dir.join("cl_scripts"), dir.join("target"));
+
+        let script = cloup.join("scripts").join("setup.sh");
+        set_mode(&script, 0o755).unwrap();
+
+        CloupMeta::from_files(&cloup, &[script])
+            .unwrap()
+            .write(&cloup)
+            .unwrap();
+        let meta = CloupMeta::read(&cloup).unwrap();
+        assert_eq!(meta.modes, vec![(PathBuf::from("scripts/setup.sh"), 0o755)]);
+
+        // a checkout that lost the executable bit gets it back
+        let applied = target.join("scripts").join("setup.sh");
+        set_mode(&applied, 0o644).unwrap();
+        meta.restore_modes(&target, std::slice::from_ref(&applied))
+            .unwrap();
+        assert_eq!(
+            fs::metadata(&applied).unwrap().permissions().mode() & 0o7777,
+            0o755
+        );
+
+        assert_eq!(parse_mode("9999 file"), None);
+    }
+
+    #[test]
+    fn empty_dirs() {
+        use crate::utils::file::{copy_recursive, CopyOptions};
+
+        let dir = TempDir::new("keep");
+        write_tree(
+            &dir,
+            &[
+                ("cl_skeleton/tests/fixtures/", ""),
+                ("cl_skeleton/logs/", ""),
+                ("target/", ""),
+            ],
+        );
+        let (cloup, target) = (dir.join("cl_skeleton"), dir.join("target"));
+
+        keep_empty_dirs(&cloup).unwrap();
+        assert!(cloup.join("logs").join(KEEP_FILE).exists());
+        assert!(cloup
+            .join("tests")
+            .join("fixtures")
+            .join(KEEP_FILE)
+            .exists());
+        assert!(!cloup.join("tests").join(KEEP_FILE).exists());
+
+        let opts = CopyOptions {
+            skip_meta: true,
+            ..CopyOptions::default()
+        };
+        copy_recursive(&cloup, &target, &opts).unwrap();
+        assert!(target.join("logs").is_dir());
+        assert!(!target.join("logs").join(KEEP_FILE).exists());
+        assert!(target.join("tests").join("fixtures").is_dir());
+    }
+}
@@ -1,4 +1,19 @@
-pub mod color;
+pub mod archive;
+pub mod bundle;
 pub mod config;
+pub mod crypto;
+pub mod encrypted;
+pub mod error;
 pub mod file;
+pub mod gzip;
+pub mod json;
+pub mod meta;
+pub mod name;
+pub mod output;
 pub mod parse;
+pub mod secrets;
+pub mod settings;
+pub mod store;
+pub mod tar;
+#[cfg(test)]
+pub mod testing;
@@ -0,0 +1,173 @@
+use super::error::{ErrorKind, Report};
+
+/// Longest name accepted for cloups and workspaces
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// Device names Windows refuses as file names, kept out so cloups and
+/// workspaces can be moved between platforms and exported as files
+const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Subcommands of `cloup workspace` and their aliases, which are read as the
+/// subcommand rather than as the name of a workspace to switch to
+pub const WORKSPACE_SUBCOMMANDS: &[&str] = &["remove", "rm", "rename", "move", "mv", "storage"];
+
+/// Name given to project-local workspaces without a name of their own
+pub const LOCAL_WORKSPACE_NAME: &str = "project";
+
+/// What a name is given to, the rules being the same apart from reserved names
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NameKind {
+    Cloup,
+    Workspace,
+}
+
+impl std::fmt::Display for NameKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NameKind::Cloup => write!(f, "cloup"),
+            NameKind::Workspace => write!(f, "workspace"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NameError {
+    Empty(NameKind),
+    TooLong(NameKind, String),
+    InvalidChar(NameKind, String, char),
+    InvalidStart(NameKind, String),
+    Reserved(NameKind, String),
+}
+
+impl std::fmt::Display for NameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NameError::Empty(kind) => write!(f, "The {} name can't be empty", kind),
+            NameError::TooLong(kind, name) => write!(
+                f,
+                "The {} name '{}' is longer than {} characters",
+                kind, name, MAX_NAME_LENGTH
+            ),
+            NameError::InvalidChar(kind, name, c) => write!(
+                f,
+                "The {} name '{}' contains {:?}, names may only contain letters, digits, '-' and '_'",
+                kind, name, c
+            ),
+            NameError::InvalidStart(kind, name) => write!(
+                f,
+                "The {} name '{}' must start with a letter or a digit",
+                kind, name
+            ),
+            NameError::Reserved(kind, name) => {
+                write!(f, "'{}' is a reserved {} name", name, kind)
+            }
+        }
+    }
+}
+impl std::error::Error for NameError {}
+
+impl Report for NameError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Usage
+    }
+}
+
+/// Check a cloup or workspace name. Names end up in paths and as keys of
+/// Cloup.toml, so only letters, digits, '-' and '_' are allowed, which also
+/// rules out `..` and separators.
+pub fn validate_name(kind: NameKind, name: &str) -> Result<(), NameError> {
+    let Some(first) = name.chars().next() else {
+        return Err(NameError::Empty(kind));
+    };
+
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(NameError::TooLong(kind, name.to_string()));
+    }
+
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        return Err(NameError::InvalidChar(kind, name.to_string(), c));
+    }
+
+    // a leading '-' would be read as a flag
+    if !first.is_ascii_alphanumeric() {
+        return Err(NameError::InvalidStart(kind, name.to_string()));
+    }
+
+    let lowercase = name.to_ascii_lowercase();
+    let reserved = RESERVED_NAMES.contains(&lowercase.as_str())
+        || (kind == NameKind::Workspace
+            && (lowercase == LOCAL_WORKSPACE_NAME
+                || WORKSPACE_SUBCOMMANDS.contains(&lowercase.as_str())));
+    if reserved {
+        return Err(NameError::Reserved(kind, name.to_string()));
+    }
+
+    Ok(())
+}
+
+/// A validated cloup name, stored in its workspace as `cl_<name>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloupName(String);
+
+impl CloupName {
+    pub fn new(name: &str) -> Result<Self, NameError> {
+        validate_name(NameKind::Cloup, name)?;
+        Ok(CloupName(name.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Name of the directory holding the cloup
+    pub fn dir_name(&self) -> String {
+        format!("cl_{}", self.0)
+    }
+}
+
+impl std::fmt::Display for CloupName {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cloup_names() {
+        assert!(CloupName::new("react-dotfiles_2").is_ok());
+        assert!(matches!(CloupName::new(""), Err(NameError::Empty(_))));
+        assert!(matches!(
+            CloupName::new("../../etc"),
+            Err(NameError::InvalidChar(_, _, '.'))
+        ));
+        assert!(matches!(
+            CloupName::new("a/b"),
+            Err(NameError::InvalidChar(_, _, '/'))
+        ));
+        assert!(matches!(
+            CloupName::new("-rf"),
+            Err(NameError::InvalidStart(..))
+        ));
+        assert!(matches!(
+            CloupName::new(&"a".repeat(MAX_NAME_LENGTH + 1)),
+            Err(NameError::TooLong(..))
+        ));
+        assert!(matches!(
+            CloupName::new("NUL"),
+            Err(NameError::Reserved(..))
+        ));
+        assert!(CloupName::new("project").is_ok());
+        assert!(validate_name(NameKind::Workspace, "project").is_err());
+        assert!(validate_name(NameKind::Workspace, "move").is_err());
+        assert!(CloupName::new("move").is_ok());
+    }
+}
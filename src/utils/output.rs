@@ -0,0 +1,151 @@
+use std::{
+    fmt::Display,
+    io::{IsTerminal, Write},
+    sync::OnceLock,
+};
+
+use super::json::Json;
+
+/// Format used for everything printed to stdout and stderr
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(value.to_string()),
+        }
+    }
+}
+
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+/// Set the output format for the rest of the process (only the first call has an effect)
+pub fn set_format(format: Format) {
+    let _ = FORMAT.set(format);
+}
+
+/// Get the output format, defaults to text
+pub fn format() -> Format {
+    *FORMAT.get().unwrap_or(&Format::Text)
+}
+
+/// Check if output should be machine-readable
+pub fn is_json() -> bool {
+    format() == Format::Json
+}
+
+/// When to print coloured text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl std::str::FromStr for Color {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "auto" => Ok(Color::Auto),
+            "always" => Ok(Color::Always),
+            "never" => Ok(Color::Never),
+            _ => Err(value.to_string()),
+        }
+    }
+}
+
+static COLOR: OnceLock<Color> = OnceLock::new();
+
+/// Set the colour mode for the rest of the process (only the first call has an effect)
+pub fn set_color(color: Color) {
+    let _ = COLOR.set(color);
+}
+
+/// Check if text printed to a stream should keep its colours. In auto mode,
+/// colours are used on terminals unless `NO_COLOR` is set.
+fn colored(terminal: bool) -> bool {
+    match COLOR.get().unwrap_or(&Color::Auto) {
+        Color::Always => true,
+        Color::Never => false,
+        Color::Auto => terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+    }
+}
+
+/// Remove the escape sequences that colour text
+fn strip(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip up to and including the final letter of the sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+
+    stripped
+}
+
+/// Text as it should be printed to a stream
+fn paint(text: impl Display, terminal: bool) -> String {
+    let text = text.to_string();
+    if colored(terminal) {
+        text
+    } else {
+        strip(&text)
+    }
+}
+
+/// Print text on stdout, followed by a newline
+pub fn println(text: impl Display) {
+    println!("{}", paint(text, std::io::stdout().is_terminal()));
+}
+
+/// Print text on stdout, without a newline
+pub fn print(text: impl Display) {
+    print!("{}", paint(text, std::io::stdout().is_terminal()));
+    let _ = std::io::stdout().flush();
+}
+
+/// Print text on stderr, followed by a newline
+pub fn eprintln(text: impl Display) {
+    eprintln!("{}", paint(text, std::io::stderr().is_terminal()));
+}
+
+/// Print a JSON document on stdout
+pub fn json(value: Json) {
+    println!("{}", value);
+}
+
+/// Print a JSON document on stderr
+pub fn json_err(value: Json) {
+    eprintln!("{}", value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_colors() {
+        assert_eq!(
+            strip("\x1b[1;32m»\x1b[0m Created \x1b[1mdefault\x1b[0m"),
+            "» Created default"
+        );
+    }
+}
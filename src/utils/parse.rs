@@ -1,241 +1,1337 @@
 use std::path::PathBuf;
 
-use crate::commands::{
-    apply::ApplyOpts, create::CreateOpts, init::InitOpts, workspace::WorkspaceOpts,
+use crate::{
+    commands::{
+        apply::ApplyOpts,
+        completions::{CompletionsOpts, SHELLS},
+        compress::CompressOpts,
+        config::{ConfigAction, ConfigOpts},
+        create::CreateOpts,
+        doctor::DoctorOpts,
+        export::ExportOpts,
+        gc::GcOpts,
+        import::ImportOpts,
+        init::InitOpts,
+        list::ListOpts,
+        man::ManOpts,
+        workspace::{WorkspaceAction, WorkspaceOpts},
+    },
+    utils::{
+        error::{ErrorKind, Report},
+        file::{Conflict, Symlinks},
+        output::Format,
+        secrets::SecretPolicy,
+        settings::{CONFLICT_POLICIES, SECRET_POLICIES, SYMLINK_POLICIES},
+        store::{Storage, STORAGE_MODES},
+    },
 };
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-fn get_flag_params(flags: [&str; 2], args: &[String]) -> Option<Vec<PathBuf>> {
-    let index = flags
-        .iter()
-        .find_map(|&flag| args.iter().position(|e| e == flag));
+/// What to offer when completing the value of a flag or argument
+#[derive(Debug, PartialEq)]
+pub enum Complete {
+    Nothing,
+    Files,
+    Cloups,
+    Workspaces,
+    Commands,
+    Settings,
+    Values(&'static [&'static str]),
+}
 
-    if let Some(pos) = index {
-        let mut params = args[pos + 1..].to_vec();
-        let other_flag = params.iter().position(|e| e.starts_with('-'));
+/// Definition of a flag accepted by a command
+#[derive(Debug)]
+pub struct FlagSpec {
+    /// Single character form, e.g. `-w`
+    pub short: Option<char>,
 
-        if let Some(idx) = other_flag {
-            params.truncate(idx);
-        }
+    /// Long form without the dashes, e.g. `workspace` for `--workspace`
+    pub long: &'static str,
+
+    /// Name of the value the flag takes, `None` for boolean flags
+    pub value: Option<&'static str>,
+
+    /// Whether the flag takes every following value up to the next flag
+    pub multiple: bool,
+
+    /// One line description used in help output
+    pub help: &'static str,
+
+    /// Completion offered for the value of the flag
+    pub complete: Complete,
+}
+
+/// Definition of a positional argument accepted by a command
+#[derive(Debug)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub required: bool,
+
+    /// Whether the argument takes every remaining positional value
+    pub multiple: bool,
+    pub help: &'static str,
+    pub complete: Complete,
+}
+
+/// Definition of a command, used for parsing and generating help
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub about: &'static str,
+
+    /// Long description shown by `cloup help <command>` and in man pages,
+    /// paragraphs are separated by blank lines
+    pub long_about: &'static str,
+    pub examples: &'static [&'static str],
+    pub args: &'static [ArgSpec],
+    pub flags: &'static [FlagSpec],
 
-        Some(params.iter().map(PathBuf::from).collect())
-    } else {
-        None
+    /// Nested commands, e.g. `workspace remove`. Their name includes the parent's name.
+    pub subcommands: &'static [CommandSpec],
+}
+
+impl CommandSpec {
+    /// Word used to invoke the command, the last part of its name
+    pub fn word(&self) -> &'static str {
+        self.name.rsplit(' ').next().unwrap_or(self.name)
     }
 }
 
-pub fn command_parser(argv: Vec<String>) -> Result<Command, CommandError> {
-    if argv.is_empty() {
-        return Err(CommandError::NoArgs);
+/// Flags accepted by every command, anywhere on the command line
+pub const GLOBAL_FLAGS: &[FlagSpec] = &[
+    FlagSpec {
+        short: Some('h'),
+        long: "help",
+        value: None,
+        multiple: false,
+        help: "Print help information",
+        complete: Complete::Nothing,
+    },
+    FlagSpec {
+        short: Some('V'),
+        long: "version",
+        value: None,
+        multiple: false,
+        help: "Print version information",
+        complete: Complete::Nothing,
+    },
+    FlagSpec {
+        short: None,
+        long: "json",
+        value: None,
+        multiple: false,
+        help: "Print machine-readable JSON output",
+        complete: Complete::Nothing,
+    },
+    FlagSpec {
+        short: None,
+        long: "format",
+        value: Some("text|json"),
+        multiple: false,
+        help: "Output format, defaults to text",
+        complete: Complete::Values(&["text", "json"]),
+    },
+];
+
+const WORKSPACE_FLAG: FlagSpec = FlagSpec {
+    short: Some('w'),
+    long: "workspace",
+    value: Some("name"),
+    multiple: false,
+    help: "Use a specific workspace instead of the active one",
+    complete: Complete::Workspaces,
+};
+
+const SYMLINKS_FLAG: FlagSpec = FlagSpec {
+    short: None,
+    long: "symlinks",
+    value: Some("policy"),
+    multiple: false,
+    help: "What to do with symlinks: preserve, follow, skip or error",
+    complete: Complete::Values(SYMLINK_POLICIES),
+};
+
+const KEY_FILE_FLAG: FlagSpec = FlagSpec {
+    short: None,
+    long: "key-file",
+    value: Some("file"),
+    multiple: false,
+    help: "File holding the passphrase of encrypted files",
+    complete: Complete::Files,
+};
+
+const NO_PRESERVE_FLAG: FlagSpec = FlagSpec {
+    short: None,
+    long: "no-preserve",
+    value: None,
+    multiple: false,
+    help: "Don't keep permissions and modification times",
+    complete: Complete::Nothing,
+};
+
+const WORKSPACE_SUBCOMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "workspace remove",
+        aliases: &["rm"],
+        about: "Remove a workspace",
+        long_about: "Removes a workspace from Cloup.toml. The directory of the workspace and the cloups in it are left on disk.
+
+The active workspace can't be removed, switch to another workspace first. A workspace that still contains cloups is only removed with --force.",
+        examples: &["cloup workspace remove old", "cloup workspace remove old --force"],
+        args: &[ArgSpec {
+            name: "name",
+            required: true,
+            multiple: false,
+            help: "Name of the workspace to remove",
+            complete: Complete::Workspaces,
+        }],
+        flags: &[FlagSpec {
+            short: Some('f'),
+            long: "force",
+            value: None,
+            multiple: false,
+            help: "Remove the workspace even if it contains cloups",
+            complete: Complete::Nothing,
+        }],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "workspace rename",
+        aliases: &[],
+        about: "Rename a workspace",
+        long_about: "Gives a workspace a new name. If the workspace is the active workspace, it stays active under its new name.",
+        examples: &["cloup workspace rename default home"],
+        args: &[
+            ArgSpec {
+                name: "name",
+                required: true,
+                multiple: false,
+                help: "Current name of the workspace",
+                complete: Complete::Workspaces,
+            },
+            ArgSpec {
+                name: "new-name",
+                required: true,
+                multiple: false,
+                help: "New name of the workspace",
+                complete: Complete::Nothing,
+            },
+        ],
+        flags: &[],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "workspace move",
+        aliases: &["mv"],
+        about: "Change the location of a workspace",
+        long_about: "Points a workspace to a new directory, which is created if it doesn't exist.
+
+By default only Cloup.toml is updated, for when the directory was already moved by hand. With --move-cloups, the cloups in the old location are moved to the new one as well. Moving fails if the new location already contains a cloup with the same name, or if it is the current location or the location of another workspace (or a directory in it).",
+        examples: &[
+            "cloup workspace move work ~/code/templates",
+            "cloup workspace move work ~/code/templates --move-cloups",
+        ],
+        args: &[
+            ArgSpec {
+                name: "name",
+                required: true,
+                multiple: false,
+                help: "Name of the workspace to move",
+                complete: Complete::Workspaces,
+            },
+            ArgSpec {
+                name: "location",
+                required: true,
+                multiple: false,
+                help: "New location of the workspace",
+                complete: Complete::Files,
+            },
+        ],
+        flags: &[FlagSpec {
+            short: Some('m'),
+            long: "move-cloups",
+            value: None,
+            multiple: false,
+            help: "Move the cloups to the new location",
+            complete: Complete::Nothing,
+        }],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "workspace storage",
+        aliases: &[],
+        about: "Convert a workspace between plain and deduplicated storage",
+        long_about: "Changes how a workspace stores its cloups and converts the cloups already in it.
+
+With plain storage, the default, every cloup is a directory holding its files. With dedup storage, the contents of files are kept once by hash in the .cloup-store directory of the workspace and every cloup is a manifest of its paths, so files shared by many cloups take space once. Cloups are applied the same way with either storage. Run 'cloup gc' to remove contents no cloup refers to anymore.",
+        examples: &[
+            "cloup workspace storage default dedup",
+            "cloup workspace storage default plain",
+        ],
+        args: &[
+            ArgSpec {
+                name: "name",
+                required: true,
+                multiple: false,
+                help: "Name of the workspace to convert",
+                complete: Complete::Workspaces,
+            },
+            ArgSpec {
+                name: "storage",
+                required: true,
+                multiple: false,
+                help: "plain or dedup",
+                complete: Complete::Values(STORAGE_MODES),
+            },
+        ],
+        flags: &[],
+        subcommands: &[],
+    },
+];
+
+const CONFIG_KEY: ArgSpec = ArgSpec {
+    name: "key",
+    required: true,
+    multiple: false,
+    help: "Dotted config key, e.g. settings.color",
+    complete: Complete::Settings,
+};
+
+const CONFIG_SUBCOMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "config get",
+        aliases: &[],
+        about: "Print the value of a config key",
+        long_about: "Prints the value of a config key. Lists are printed one value per line. Workspace locations can be read as workspaces.<name>.",
+        examples: &["cloup config get settings.conflict", "cloup config get workspaces.default"],
+        args: &[CONFIG_KEY],
+        flags: &[],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "config set",
+        aliases: &[],
+        about: "Change the value of a config key",
+        long_about: "Sets a config key after checking the value against the known settings. List settings take every value given, replacing the previous list.",
+        examples: &[
+            "cloup config set settings.color never",
+            "cloup config set settings.exclude node_modules target .git",
+        ],
+        args: &[
+            CONFIG_KEY,
+            ArgSpec {
+                name: "value",
+                required: false,
+                multiple: true,
+                help: "New value, or values for lists",
+                complete: Complete::Nothing,
+            },
+        ],
+        flags: &[],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "config unset",
+        aliases: &[],
+        about: "Remove a config key",
+        long_about: "Removes a config key from Cloup.toml, so its default applies again.",
+        examples: &["cloup config unset settings.editor"],
+        args: &[CONFIG_KEY],
+        flags: &[],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "config list",
+        aliases: &["ls"],
+        about: "Print every config key that is set",
+        long_about: "Prints every key set in Cloup.toml with its value, one per line.",
+        examples: &["cloup config list"],
+        args: &[],
+        flags: &[],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "config edit",
+        aliases: &[],
+        about: "Open the config file in an editor",
+        long_about: "Opens Cloup.toml in the editor set in settings.editor, $VISUAL or $EDITOR, and checks the file once the editor exits.",
+        examples: &["cloup config edit"],
+        args: &[],
+        flags: &[],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "config path",
+        aliases: &[],
+        about: "Print the location of the config file",
+        long_about: "Prints the location of Cloup.toml, taking CLOUP_CONFIG, CLOUP_HOME and XDG_CONFIG_HOME into account. The file is not created if it doesn't exist.",
+        examples: &["cloup config path"],
+        args: &[],
+        flags: &[],
+        subcommands: &[],
+    },
+];
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "init",
+        aliases: &[],
+        about: "Sets the current directory as a location for cloups",
+        long_about: "Sets the current directory as the location of a workspace, the directory where cloups are stored.
+
+The first time cloup runs, the current directory becomes the location of the 'default' workspace. Running init again asks before moving the active workspace to the current directory, pass --overwrite to confirm. With --workspace, the current directory is registered as a new workspace with that name instead.",
+        examples: &["cloup init", "cloup init -w my-workspace"],
+        args: &[],
+        flags: &[
+            FlagSpec {
+                short: Some('w'),
+                long: "workspace",
+                value: Some("name"),
+                multiple: false,
+                help: "New workspace location for cloups",
+                complete: Complete::Workspaces,
+            },
+            FlagSpec {
+                short: Some('o'),
+                long: "overwrite",
+                value: None,
+                multiple: false,
+                help: "Overwrite cloup storage location",
+                complete: Complete::Nothing,
+            },
+        ],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "create",
+        aliases: &[],
+        about: "Create a new cloup",
+        long_about: "Copies files from the current directory into a new cloup in the active workspace.
+
+Without any files, the whole current directory is copied recursively. Files and directories can be given as arguments or with --files, and --exclude skips files or directories by name. Creating a cloup with a name that already exists in the workspace fails.
+
+Symlinks are kept as links by default, following the symlinks setting of Cloup.toml. Pass --symlinks to follow them, skip them or stop with an error instead. Links that lead back to a directory being copied are reported rather than copied forever.
+
+Permissions are kept and recorded in the cloup, so executable scripts stay executable even in a workspace stored somewhere that loses them, such as git. Modification times are kept too with the preserve_mtime setting. Pass --no-preserve to store files with default permissions and a fresh modification time.
+
+Empty directories get a .cloup-keep file, so they aren't lost when the workspace is stored in git. Apply creates the directories and leaves the file out.
+
+Dotfiles that git or npm would act on in the workspace, listed in the escape setting, are stored with a _dot_ prefix instead of the dot, .gitignore becoming _dot_gitignore. Apply gives them their name back.
+
+Files that look like secrets are reported before anything is stored: .env files, private keys, tokens of known services and random-looking values of keys such as password or api_key. By default the cloup isn't created, pass --secrets warn to store it anyway or list the files in the allow_secrets setting.
+
+Files matching --encrypt, by name or by path, are encrypted with a passphrase before they're stored, so the workspace can be committed without exposing them. The passphrase is read from --key-file or CLOUP_PASSPHRASE, or asked for twice. Encrypted files aren't reported as secrets.
+
+With --compress, the cloup is kept as a single compressed archive, which apply and list read directly. Use 'cloup compress' to compress or decompress a cloup later.",
+        examples: &[
+            "cloup create my-cloup",
+            "cloup create my-cloup -w my-workspace",
+            "cloup create my-cloup -f file1 file2 -e file3 file4",
+            "cloup create my-cloup --encrypt .env 'certs/*.pem'",
+            "cloup create my-cloup --compress",
+            "cloup create my-cloup -- -file-with-dash",
+        ],
+        args: &[
+            ArgSpec {
+                name: "name",
+                required: true,
+                multiple: false,
+                help: "Name of the cloup",
+                complete: Complete::Nothing,
+            },
+            ArgSpec {
+                name: "files",
+                required: false,
+                multiple: true,
+                help: "Files to include in cloup, same as --files",
+                complete: Complete::Files,
+            },
+        ],
+        flags: &[
+            WORKSPACE_FLAG,
+            FlagSpec {
+                short: Some('f'),
+                long: "files",
+                value: Some("file"),
+                multiple: true,
+                help: "Files to include in cloup",
+                complete: Complete::Files,
+            },
+            FlagSpec {
+                short: Some('e'),
+                long: "exclude",
+                value: Some("file"),
+                multiple: true,
+                help: "Files to exclude from cloup",
+                complete: Complete::Files,
+            },
+            SYMLINKS_FLAG,
+            NO_PRESERVE_FLAG,
+            FlagSpec {
+                short: None,
+                long: "secrets",
+                value: Some("policy"),
+                multiple: false,
+                help: "What to do with likely secrets: abort, warn or ignore",
+                complete: Complete::Values(SECRET_POLICIES),
+            },
+            FlagSpec {
+                short: None,
+                long: "encrypt",
+                value: Some("pattern"),
+                multiple: true,
+                help: "Files to encrypt, by name, path or glob pattern",
+                complete: Complete::Files,
+            },
+            KEY_FILE_FLAG,
+            FlagSpec {
+                short: None,
+                long: "compress",
+                value: None,
+                multiple: false,
+                help: "Keep the cloup as a compressed archive",
+                complete: Complete::Nothing,
+            },
+        ],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "apply",
+        aliases: &[],
+        about: "Apply a cloup to the current directory",
+        long_about: "Copies every file and directory of a cloup into the current directory, keeping the directory structure of the cloup.
+
+The cloup is looked up in the active workspace first, then in the workspaces listed in the search_path of Cloup.toml, and the first one found is applied. Qualify the name as 'workspace/name', or pass --workspace, to apply the cloup of a specific workspace.
+
+Files that already exist are handled according to the conflict setting in the [settings] table of Cloup.toml, overwrite by default. Pass --conflict to use another policy for a single run: ask, skip, overwrite or abort. When nobody can answer, ask behaves like abort.
+
+Symlinks in the cloup are handled like in create, according to the symlinks setting or --symlinks. Files are never written through a symlink that leads outside of the current directory, and existing symlinks are replaced rather than written through.
+
+Files get the permissions recorded in the cloup when it was created, and their modification times with the preserve_mtime setting. Pass --no-preserve to give them default permissions and a fresh modification time instead.
+
+Encrypted files are decrypted as they're applied, with the passphrase read from --key-file or CLOUP_PASSPHRASE, or asked for. Every file is checked before anything is written, so a wrong passphrase leaves the current directory untouched.",
+        examples: &[
+            "cloup apply my-cloup",
+            "cloup apply my-workspace/my-cloup",
+            "cloup apply my-cloup -w my-workspace",
+            "cloup apply my-cloup --conflict skip",
+        ],
+        args: &[ArgSpec {
+            name: "name",
+            required: true,
+            multiple: false,
+            help: "Name of the cloup",
+            complete: Complete::Cloups,
+        }],
+        flags: &[
+            WORKSPACE_FLAG,
+            FlagSpec {
+                short: None,
+                long: "conflict",
+                value: Some("policy"),
+                multiple: false,
+                help: "What to do with existing files: ask, skip, overwrite or abort",
+                complete: Complete::Values(CONFLICT_POLICIES),
+            },
+            SYMLINKS_FLAG,
+            NO_PRESERVE_FLAG,
+            KEY_FILE_FLAG,
+        ],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "list",
+        aliases: &["ls"],
+        about: "List all cloups in the current workspace",
+        long_about: "Lists the cloups stored in the active workspace along with their size on disk.
+
+With --all, the cloups of every workspace are listed, grouped by workspace in the order they are searched by apply. A cloup hidden by a cloup with the same name earlier in the search path is marked as shadowed, apply it with its qualified name 'workspace/name'.",
+        examples: &["cloup list", "cloup list --all"],
+        args: &[],
+        flags: &[FlagSpec {
+            short: Some('a'),
+            long: "all",
+            value: None,
+            multiple: false,
+            help: "List the cloups of every workspace",
+            complete: Complete::Nothing,
+        }],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "workspace",
+        aliases: &["w"],
+        about: "Show, set or list workspaces",
+        long_about: "Workspaces are named directories where cloups are stored, registered in Cloup.toml. One workspace is active at a time and is used by every command that doesn't get a --workspace flag.
+
+Without arguments, shows the active workspace with its location, number of cloups and total size, and reports problems such as a missing or read-only location or files that aren't cloups. Given a name, the workspace with that name becomes the active workspace. --create registers the current directory as a new workspace and --list prints every workspace, marking the active one. Workspaces can be removed, renamed, moved and converted to another storage with the subcommands below.",
+        examples: &[
+            "cloup workspace",
+            "cloup workspace my-workspace",
+            "cloup workspace -l",
+            "cloup workspace -c my-workspace",
+            "cloup workspace rename my-workspace work",
+        ],
+        args: &[ArgSpec {
+            name: "name",
+            required: false,
+            multiple: false,
+            help: "Name of the workspace to set as active, or to create with --create",
+            complete: Complete::Workspaces,
+        }],
+        flags: &[
+            FlagSpec {
+                short: Some('l'),
+                long: "list",
+                value: None,
+                multiple: false,
+                help: "List all workspaces",
+                complete: Complete::Nothing,
+            },
+            FlagSpec {
+                short: Some('c'),
+                long: "create",
+                value: None,
+                multiple: false,
+                help: "Create a new workspace in current directory",
+                complete: Complete::Nothing,
+            },
+        ],
+        subcommands: WORKSPACE_SUBCOMMANDS,
+    },
+    CommandSpec {
+        name: "config",
+        aliases: &[],
+        about: "Read and change settings",
+        long_about: "Reads and changes the settings stored in Cloup.toml. Keys are dotted paths such as settings.color, and values are checked against the known settings before they are written.
+
+Without a subcommand, prints every key that is set, like 'cloup config list'.",
+        examples: &[
+            "cloup config",
+            "cloup config set settings.conflict skip",
+            "cloup config path",
+        ],
+        args: &[],
+        flags: &[],
+        subcommands: CONFIG_SUBCOMMANDS,
+    },
+    CommandSpec {
+        name: "doctor",
+        aliases: &[],
+        about: "Check the config and workspaces for problems",
+        long_about: "Checks the config directory, parses Cloup.toml strictly and checks every setting, then checks that the active workspace and the search path refer to registered workspaces, that every workspace location exists and is writable, that it only holds cl_ directories, and that no cloup contains broken symlinks or unreadable files.
+
+With --fix, problems that can be repaired without losing anything are repaired: missing directories are created, an unknown active workspace is reset, unknown workspaces are removed from the search path, an old config is migrated and leftover temporary files are removed. Everything else is only reported.
+
+Exits with a non-zero code when errors remain.",
+        examples: &["cloup doctor", "cloup doctor --fix"],
+        args: &[],
+        flags: &[FlagSpec {
+            short: None,
+            long: "fix",
+            value: None,
+            multiple: false,
+            help: "Repair the problems that can be repaired safely",
+            complete: Complete::Nothing,
+        }],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "compress",
+        aliases: &[],
+        about: "Compress or decompress cloups",
+        long_about: "Keeps cloups as a single compressed archive instead of a directory of files, which saves space for cloups with large assets such as images, fonts or vendored libraries. Compressed cloups are applied and listed like any other cloup.
+
+With --decompress, compressed cloups become directories again, or go back to the store in a workspace with dedup storage.",
+        examples: &[
+            "cloup compress react-app",
+            "cloup compress react-app vue-app -w work",
+            "cloup compress react-app --decompress",
+        ],
+        args: &[ArgSpec {
+            name: "names",
+            required: true,
+            multiple: true,
+            help: "Names of the cloups",
+            complete: Complete::Cloups,
+        }],
+        flags: &[
+            WORKSPACE_FLAG,
+            FlagSpec {
+                short: Some('d'),
+                long: "decompress",
+                value: None,
+                multiple: false,
+                help: "Turn compressed cloups back into directories",
+                complete: Complete::Nothing,
+            },
+        ],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "export",
+        aliases: &[],
+        about: "Export a cloup to an archive",
+        long_about: "Writes a cloup to a .tar.gz archive that can be shared and imported into any workspace with 'cloup import'. Next to the files, the archive holds a manifest with the name and description of the cloup, along with the checksum and mode of every file.
+
+Files are exported as they are stored, so encrypted files stay encrypted and need the same passphrase once imported.",
+        examples: &[
+            "cloup export react-app",
+            "cloup export react-app -o ~/react-app.tar.gz",
+            "cloup export react-app -w work --description \"React app with Vite\"",
+        ],
+        args: &[ArgSpec {
+            name: "name",
+            required: true,
+            multiple: false,
+            help: "Name of the cloup",
+            complete: Complete::Cloups,
+        }],
+        flags: &[
+            FlagSpec {
+                short: Some('o'),
+                long: "output",
+                value: Some("file"),
+                multiple: false,
+                help: "Archive to write, defaults to <name>.tar.gz",
+                complete: Complete::Files,
+            },
+            WORKSPACE_FLAG,
+            FlagSpec {
+                short: None,
+                long: "description",
+                value: Some("text"),
+                multiple: false,
+                help: "Description shown when the cloup is imported",
+                complete: Complete::Nothing,
+            },
+        ],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "import",
+        aliases: &[],
+        about: "Import a cloup from an archive",
+        long_about: "Adds a cloup exported with 'cloup export' to a workspace, under the name it was exported with unless --as is given. A cloup of the same name is never replaced.
+
+Nothing is imported from an archive with paths that are absolute or lead outside of the cloup, or with files that don't match the checksums of its manifest.",
+        examples: &[
+            "cloup import react-app.tar.gz",
+            "cloup import react-app.tar.gz --as react-vite",
+            "cloup import react-app.tar.gz -w work",
+        ],
+        args: &[ArgSpec {
+            name: "file",
+            required: true,
+            multiple: false,
+            help: "Archive made by cloup export",
+            complete: Complete::Files,
+        }],
+        flags: &[
+            FlagSpec {
+                short: None,
+                long: "as",
+                value: Some("name"),
+                multiple: false,
+                help: "Import the cloup under another name",
+                complete: Complete::Nothing,
+            },
+            WORKSPACE_FLAG,
+        ],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "gc",
+        aliases: &[],
+        about: "Remove stored contents no cloup refers to",
+        long_about: "In a workspace with dedup storage, removes the file contents of the store that no cloup refers to anymore, such as those of deleted cloups. In any workspace, it also removes the partial archives left behind by an interrupted compress.
+
+Contents stored in the last hour are kept, as they may belong to a cloup still being created. Nothing is removed if a manifest can't be read, as its contents can't be told apart from garbage.",
+        examples: &["cloup gc", "cloup gc -w work --dry-run"],
+        args: &[],
+        flags: &[
+            WORKSPACE_FLAG,
+            FlagSpec {
+                short: Some('n'),
+                long: "dry-run",
+                value: None,
+                multiple: false,
+                help: "Only report what would be removed",
+                complete: Complete::Nothing,
+            },
+        ],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "completions",
+        aliases: &[],
+        about: "Print a shell completion script",
+        long_about: "Prints a completion script for the given shell on stdout. Besides commands and flags, the script completes the names of cloups and workspaces by calling cloup itself, so the completions stay up to date as cloups are created.",
+        examples: &[
+            "cloup completions bash > /etc/bash_completion.d/cloup",
+            "cloup completions zsh > \"${fpath[1]}/_cloup\"",
+            "cloup completions fish > ~/.config/fish/completions/cloup.fish",
+        ],
+        args: &[ArgSpec {
+            name: "shell",
+            required: true,
+            multiple: false,
+            help: "One of bash, zsh, fish, elvish or powershell",
+            complete: Complete::Values(SHELLS),
+        }],
+        flags: &[],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "help",
+        aliases: &[],
+        about: "Print the detailed help of a command",
+        long_about: "Prints the detailed help of a command, including its description, arguments, flags and examples. Without a command, prints the help of cloup itself.",
+        examples: &["cloup help", "cloup help apply"],
+        args: &[ArgSpec {
+            name: "command",
+            required: false,
+            multiple: true,
+            help: "Command to print the help of, e.g. 'workspace remove'",
+            complete: Complete::Commands,
+        }],
+        flags: &[],
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "man",
+        aliases: &[],
+        about: "Print or write man pages",
+        long_about: "Prints the man page of cloup, or of one of its commands, in roff format on stdout.
+
+With --dir, the man pages of cloup and every command are written into the given directory as cloup.1, cloup-apply.1 and so on, ready to be installed into a man directory.",
+        examples: &[
+            "cloup man | man -l -",
+            "cloup man apply",
+            "cloup man --dir /usr/local/share/man/man1",
+        ],
+        args: &[ArgSpec {
+            name: "command",
+            required: false,
+            multiple: true,
+            help: "Command to print the man page of, e.g. 'workspace remove'",
+            complete: Complete::Commands,
+        }],
+        flags: &[FlagSpec {
+            short: Some('d'),
+            long: "dir",
+            value: Some("path"),
+            multiple: false,
+            help: "Write every man page into a directory",
+            complete: Complete::Files,
+        }],
+        subcommands: &[],
+    },
+];
+
+/// Flags and positional arguments found on the command line
+#[derive(Debug, Default)]
+pub struct Matches {
+    /// Positional arguments, in order
+    pub args: Vec<String>,
+
+    /// Every occurrence of a flag (by long name) with its value, if any
+    flags: Vec<(&'static str, Option<String>)>,
+}
+
+impl Matches {
+    /// Check if a flag was passed
+    pub fn has(&self, long: &str) -> bool {
+        self.flags.iter().any(|(name, _)| *name == long)
     }
 
-    let command = argv[0].as_str();
-    let help = get_flag_params(["-h", "--help"], &argv);
-    let version = get_flag_params(["-V", "--version"], &argv);
-
-    if version.is_some() {
-        println!("cloup {}", VERSION);
-        std::process::exit(0);
-    }
-
-    match command {
-        "init" => {
-            let usage: UsageError = UsageError {
-                message: "Set cloup storage location".to_string(),
-                usage: "$ cloup init [flags]".to_string(),
-                examples: vec![
-                    "cloup init".to_string(),
-                    "cloup init -w my-workspace".to_string(),
-                ],
-                flags: vec![
-                    (
-                        "-w, --workspace <name>".to_string(),
-                        "New workspace location for cloups".to_string(),
-                    ),
-                    (
-                        "-o, --overwrite".to_string(),
-                        "Overwrite cloup storage location".to_string(),
-                    ),
-                ],
-            };
+    /// Get the last value passed to a flag
+    pub fn value(&self, long: &str) -> Option<String> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(name, value)| *name == long && value.is_some())
+            .and_then(|(_, value)| value.clone())
+    }
 
-            if help.is_some() {
-                return Err(CommandError::BadUsage(usage));
-            }
+    /// Add the flags of another set of matches
+    fn extend(&mut self, other: Matches) {
+        self.args.extend(other.args);
+        self.flags.extend(other.flags);
+    }
 
-            let overwrite = get_flag_params(["-o", "--overwrite"], &argv).is_some();
-            let workspace = if let Some(params) = get_flag_params(["-w", "--workspace"], &argv) {
-                if params.len() > 1 || params.is_empty() {
-                    return Err(CommandError::BadUsage(usage));
-                }
-                Some(params[0].to_string_lossy().to_string())
-            } else {
-                None
-            };
+    /// Get every value passed to a flag
+    pub fn values(&self, long: &str) -> Vec<String> {
+        self.flags
+            .iter()
+            .filter(|(name, _)| *name == long)
+            .filter_map(|(_, value)| value.clone())
+            .collect()
+    }
+}
 
-            Ok(Command::Init(InitOpts {
-                overwrite,
-                workspace,
-            }))
-        }
-        "create" => {
-            let usage = UsageError {
-                message: "Create a new cloup".to_string(),
-                usage: "$ cloup create <name> [flags]".to_string(),
-                examples: vec![
-                    "cloup create my-cloup".to_string(),
-                    "cloup create my-cloup -w my-workspace".to_string(),
-                    "cloup create my-cloup -f file1 file2 -e file3 file4".to_string(),
-                ],
-                flags: vec![
-                    (
-                        "-w, --workspace <name>".to_string(),
-                        "Store cloup in a specific workspace".to_string(),
-                    ),
-                    (
-                        "-f, --files <file1> <file2>".to_string(),
-                        "Files to include in cloup".to_string(),
-                    ),
-                    (
-                        "-e, --exclude <file1> <file2>".to_string(),
-                        "Files to exclude from cloup".to_string(),
-                    ),
-                ],
-            };
+/// Find a command by name or alias
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    find_in(COMMANDS, name)
+}
 
-            if help.is_some() {
-                return Err(CommandError::BadUsage(usage));
-            }
+/// Find a command by name or alias in a list of commands
+pub fn find_in(commands: &'static [CommandSpec], word: &str) -> Option<&'static CommandSpec> {
+    commands
+        .iter()
+        .find(|c| c.word() == word || c.aliases.contains(&word))
+}
 
-            if argv.len() < 2 {
-                return Err(CommandError::BadUsage(usage));
-            }
+/// Levenshtein distance between two strings
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
 
-            let workspace = if let Some(params) = get_flag_params(["-w", "--workspace"], &argv) {
-                if params.len() > 1 || params.is_empty() {
-                    return Err(CommandError::BadUsage(usage));
-                }
-                Some(params[0].to_string_lossy().to_string())
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
             } else {
-                None
+                1 + previous.min(row[j]).min(row[j + 1])
             };
+            previous = current;
+        }
+    }
 
-            let name = argv[1].to_string();
-            let mut files = vec![];
+    row[b.len()]
+}
 
-            let files_params = get_flag_params(["-f", "--files"], &argv);
-            if let Some(f) = files_params {
-                files = f;
-            }
+/// Find the closest candidate to `input`, if any is close enough to be a typo
+pub fn suggest<'a>(input: &str, candidates: impl Iterator<Item = &'a str>) -> Option<String> {
+    candidates
+        .map(|c| (distance(input, c), c))
+        .filter(|(d, c)| *d <= 2.max(c.len() / 3))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c.to_string())
+}
 
-            let mut exclude = vec![];
-            let exclude_params = get_flag_params(["-e", "--exclude"], &argv);
-            if let Some(e) = exclude_params {
-                exclude = e;
-            }
+/// Build the "did you mean" suggestion for an unknown flag
+fn suggest_flag(flag: &str, flags: &[&'static FlagSpec]) -> Option<String> {
+    let candidates: Vec<String> = flags
+        .iter()
+        .map(|f| format!("--{}", f.long))
+        .chain(
+            flags
+                .iter()
+                .filter_map(|f| f.short.map(|s| format!("-{s}"))),
+        )
+        .collect();
 
-            Ok(Command::Create(CreateOpts {
-                name,
-                files,
-                exclude,
-                workspace,
-            }))
+    suggest(flag, candidates.iter().map(String::as_str))
+}
+
+/// Take the value(s) of a flag from the tokens following it
+fn take_values(
+    spec: &'static FlagSpec,
+    tokens: &[String],
+    i: &mut usize,
+    matches: &mut Matches,
+) -> Result<(), CommandError> {
+    let is_value = |t: &String| !t.starts_with('-') || t == "-";
+
+    match tokens.get(*i) {
+        Some(token) if is_value(token) => {
+            matches.flags.push((spec.long, Some(token.clone())));
+            *i += 1;
         }
-        "apply" => {
-            let usage = UsageError {
-                message: "Apply cloup to current directory".to_string(),
-                usage: "$ cloup apply <name> [flags]".to_string(),
-                examples: vec![
-                    "cloup apply my-cloup".to_string(),
-                    "cloup apply my-cloup -w my-workspace".to_string(),
-                ],
-                flags: vec![(
-                    "-w, --workspace <name>".to_string(),
-                    "Apply cloup from a specific workspace".to_string(),
-                )],
-            };
+        _ => return Err(CommandError::MissingValue(spec)),
+    }
 
-            if help.is_some() {
-                return Err(CommandError::BadUsage(usage));
+    while spec.multiple && tokens.get(*i).is_some_and(is_value) {
+        matches.flags.push((spec.long, Some(tokens[*i].clone())));
+        *i += 1;
+    }
+
+    Ok(())
+}
+
+/// Parse tokens against the given flags. If `stop_at_arg` is set, parsing stops
+/// at the first positional argument and the index of that argument is returned.
+fn parse_tokens(
+    tokens: &[String],
+    flags: &[&'static FlagSpec],
+    stop_at_arg: bool,
+) -> Result<(Matches, usize), CommandError> {
+    let mut matches = Matches::default();
+    let mut only_args = false;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let token = &tokens[i];
+        i += 1;
+
+        if only_args || token == "-" || !token.starts_with('-') {
+            if stop_at_arg {
+                return Ok((matches, i - 1));
             }
+            matches.args.push(token.clone());
+        } else if token == "--" {
+            only_args = true;
+        } else if let Some(long) = token.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
 
-            if argv.len() < 2 {
-                return Err(CommandError::BadUsage(usage));
+            let spec = flags.iter().find(|f| f.long == name).ok_or_else(|| {
+                CommandError::UnknownFlag(format!("--{name}"), suggest_flag(token, flags))
+            })?;
+
+            match (spec.value, inline) {
+                (None, Some(_)) => return Err(CommandError::UnexpectedValue(spec)),
+                (None, None) => matches.flags.push((spec.long, None)),
+                (Some(_), Some(value)) => matches.flags.push((spec.long, Some(value))),
+                (Some(_), None) => take_values(spec, tokens, &mut i, &mut matches)?,
             }
+        } else {
+            // One or more short flags combined, e.g. `-ow name` or `-wname`
+            let cluster = &token[1..];
+
+            for (pos, c) in cluster.char_indices() {
+                let short = format!("-{c}");
+                let spec = flags.iter().find(|f| f.short == Some(c)).ok_or_else(|| {
+                    CommandError::UnknownFlag(short.clone(), suggest_flag(&short, flags))
+                })?;
 
-            let workspace = if let Some(params) = get_flag_params(["-w", "--workspace"], &argv) {
-                if params.len() > 1 || params.is_empty() {
-                    return Err(CommandError::BadUsage(usage));
+                if spec.value.is_none() {
+                    matches.flags.push((spec.long, None));
+                    continue;
                 }
-                Some(params[0].to_string_lossy().to_string())
-            } else {
-                None
-            };
 
-            Ok(Command::Apply(ApplyOpts {
-                name: argv[1].to_string(),
-                workspace,
-            }))
+                let rest = &cluster[pos + c.len_utf8()..];
+                let rest = rest.strip_prefix('=').unwrap_or(rest);
+                if rest.is_empty() {
+                    take_values(spec, tokens, &mut i, &mut matches)?;
+                } else {
+                    matches.flags.push((spec.long, Some(rest.to_string())));
+                }
+                break;
+            }
         }
-        "list" => {
-            let usage = UsageError {
-                message: "List all cloups in the current workspace".to_string(),
-                usage: "$ cloup list [flags]".to_string(),
-                examples: vec!["cloup list".to_string()],
-                flags: vec![(
-                    "-h, --help".to_string(),
-                    "Print help information".to_string(),
-                )],
-            };
+    }
 
-            if help.is_some() {
-                return Err(CommandError::BadUsage(usage));
-            }
+    Ok((matches, tokens.len()))
+}
+
+/// Find the output format from the global `--json` and `--format` flags
+pub fn output_format(argv: &[String]) -> Result<Format, CommandError> {
+    let mut format = Format::Text;
+    let mut tokens = argv.iter();
 
-            Ok(Command::List())
+    while let Some(token) = tokens.next() {
+        if token == "--" {
+            break;
+        } else if token == "--json" {
+            format = Format::Json;
+        } else if token == "--format" {
+            let value = tokens.next().cloned().unwrap_or_default();
+            format = value.parse().map_err(CommandError::BadFormat)?;
+        } else if let Some(value) = token.strip_prefix("--format=") {
+            format = value.parse().map_err(CommandError::BadFormat)?;
         }
-        "workspace" | "w" => {
-            let usage = UsageError {
-                message: "Set the current workspace or list all workspaces".to_string(),
-                usage: "cloup workspace [flags]".to_string(),
-                examples: vec![
-                    "cloup workspace".to_string(),
-                    "cloup workspace -l".to_string(),
-                    "cloup workspace -c my-workspace".to_string(),
-                ],
-                flags: vec![
-                    (
-                        "-l, --list".to_string(),
-                        "List all workspaces in the current directory".to_string(),
-                    ),
-                    (
-                        "-c, --create <name>".to_string(),
-                        "Create a new workspace in current directory".to_string(),
-                    ),
-                ],
-            };
+    }
+
+    Ok(format)
+}
+
+pub fn command_parser(argv: Vec<String>) -> Result<Command, CommandError> {
+    // Hidden entry point used by the completion scripts, words are passed through as-is
+    if argv.first().is_some_and(|a| a == "__complete") {
+        let words = match argv.get(1) {
+            Some(separator) if separator == "--" => argv[2..].to_vec(),
+            _ => argv[1..].to_vec(),
+        };
+        return Ok(Command::Complete(words));
+    }
+
+    let globals: Vec<&'static FlagSpec> = GLOBAL_FLAGS.iter().collect();
+
+    // Global flags may come before the command
+    let (leading, index) = parse_tokens(&argv, &globals, true)?;
+    if leading.has("version") {
+        return Ok(Command::Version);
+    }
+
+    let Some(name) = argv.get(index) else {
+        if leading.has("help") {
+            return Ok(Command::Help(help()));
+        }
+        return Err(CommandError::NoArgs);
+    };
 
-            if help.is_some() {
-                return Err(CommandError::BadUsage(usage));
+    let mut spec = find_command(name).ok_or_else(|| {
+        CommandError::NotRecognized(
+            name.to_string(),
+            suggest(name, COMMANDS.iter().map(|c| c.name)),
+        )
+    })?;
+    let mut rest = &argv[index + 1..];
+    let mut leading = leading;
+
+    // Descend into subcommands, flags before the subcommand belong to the parent
+    while !spec.subcommands.is_empty() {
+        let flags: Vec<&'static FlagSpec> = spec.flags.iter().chain(GLOBAL_FLAGS).collect();
+        let (prefix, i) = parse_tokens(rest, &flags, true)?;
+
+        match rest.get(i).and_then(|word| find_in(spec.subcommands, word)) {
+            Some(subcommand) => {
+                leading.extend(prefix);
+                spec = subcommand;
+                rest = &rest[i + 1..];
             }
+            None => break,
+        }
+    }
 
-            if argv.len() < 2 {
-                return Err(CommandError::BadUsage(usage));
+    let flags: Vec<&'static FlagSpec> = spec.flags.iter().chain(GLOBAL_FLAGS).collect();
+    let (matches, _) = parse_tokens(rest, &flags, false)?;
+
+    if matches.has("version") {
+        return Ok(Command::Version);
+    }
+
+    if leading.has("help") || matches.has("help") {
+        return Ok(Command::Help(command_help(spec, false)));
+    }
+
+    validate_args(spec, &matches)?;
+
+    // Commands taking the name of another command, e.g. `help workspace remove`
+    let mut target: Option<&'static CommandSpec> = None;
+    if spec.name == "help" || spec.name == "man" {
+        for word in &matches.args {
+            let commands = target.map(|t| t.subcommands).unwrap_or(COMMANDS);
+            target = Some(find_in(commands, word).ok_or_else(|| {
+                CommandError::NotRecognized(
+                    word.to_string(),
+                    suggest(word, commands.iter().map(|c| c.word())),
+                )
+            })?);
+        }
+    }
+
+    match spec.name {
+        "init" => Ok(Command::Init(InitOpts {
+            overwrite: matches.has("overwrite"),
+            workspace: matches.value("workspace"),
+        })),
+        "create" => Ok(Command::Create(CreateOpts {
+            name: matches.args[0].clone(),
+            files: matches
+                .values("files")
+                .iter()
+                .chain(&matches.args[1..])
+                .map(PathBuf::from)
+                .collect(),
+            exclude: matches
+                .values("exclude")
+                .iter()
+                .map(PathBuf::from)
+                .collect(),
+            workspace: matches.value("workspace"),
+            symlinks: symlinks_policy(&matches, spec)?,
+            preserve: !matches.has("no-preserve"),
+            secrets: matches
+                .value("secrets")
+                .map(|value| {
+                    value.parse::<SecretPolicy>().map_err(|value| {
+                        CommandError::BadUsage(format!("Unknown secret policy '{}'", value), spec)
+                    })
+                })
+                .transpose()?,
+            encrypt: matches.values("encrypt"),
+            key_file: matches.value("key-file").map(PathBuf::from),
+            compress: matches.has("compress"),
+        })),
+        "apply" => Ok(Command::Apply(ApplyOpts {
+            name: matches.args[0].clone(),
+            workspace: matches.value("workspace"),
+            conflict: matches
+                .value("conflict")
+                .map(|value| {
+                    value.parse::<Conflict>().map_err(|value| {
+                        CommandError::BadUsage(format!("Unknown conflict policy '{}'", value), spec)
+                    })
+                })
+                .transpose()?,
+            symlinks: symlinks_policy(&matches, spec)?,
+            preserve: !matches.has("no-preserve"),
+            key_file: matches.value("key-file").map(PathBuf::from),
+        })),
+        "doctor" => Ok(Command::Doctor(DoctorOpts {
+            fix: matches.has("fix"),
+        })),
+        "compress" => Ok(Command::Compress(CompressOpts {
+            names: matches.args.clone(),
+            workspace: matches.value("workspace"),
+            decompress: matches.has("decompress"),
+        })),
+        "export" => Ok(Command::Export(ExportOpts {
+            name: matches.args[0].clone(),
+            output: matches.value("output").map(PathBuf::from),
+            workspace: matches.value("workspace"),
+            description: matches.value("description"),
+        })),
+        "import" => Ok(Command::Import(ImportOpts {
+            file: PathBuf::from(&matches.args[0]),
+            name: matches.value("as"),
+            workspace: matches.value("workspace"),
+        })),
+        "gc" => Ok(Command::Gc(GcOpts {
+            workspace: matches.value("workspace"),
+            dry_run: matches.has("dry-run"),
+        })),
+        "list" => Ok(Command::List(ListOpts {
+            all: matches.has("all"),
+        })),
+        "completions" => Ok(Command::Completions(CompletionsOpts {
+            shell: matches.args[0].clone(),
+        })),
+        "help" => Ok(Command::Help(match target {
+            Some(target) => command_help(target, true),
+            None => help(),
+        })),
+        "man" => Ok(Command::Man(ManOpts {
+            command: target,
+            dir: matches.value("dir").map(PathBuf::from),
+        })),
+        "workspace" => {
+            // the name goes before or after -c, which doesn't take it as its value
+            let create = matches.has("create");
+            if create && matches.args.is_empty() {
+                return Err(CommandError::BadUsage(
+                    "Missing the name of the workspace to create".to_string(),
+                    spec,
+                ));
             }
 
-            let name = Some(argv[1].to_string());
-            let list = get_flag_params(["-l", "--list"], &argv);
-            let create = get_flag_params(["-c", "--create"], &argv);
+            Ok(Command::Workspace(WorkspaceOpts {
+                list: matches.has("list"),
+                create,
+                name: matches.args.first().cloned(),
+                action: None,
+            }))
+        }
+        "workspace remove" | "workspace rename" | "workspace move" | "workspace storage" => {
+            let action = match spec.name {
+                "workspace remove" => WorkspaceAction::Remove {
+                    name: matches.args[0].clone(),
+                    force: matches.has("force"),
+                },
+                "workspace rename" => WorkspaceAction::Rename {
+                    name: matches.args[0].clone(),
+                    new_name: matches.args[1].clone(),
+                },
+                "workspace move" => WorkspaceAction::Move {
+                    name: matches.args[0].clone(),
+                    location: PathBuf::from(&matches.args[1]),
+                    move_cloups: matches.has("move-cloups"),
+                },
+                _ => WorkspaceAction::Storage {
+                    name: matches.args[0].clone(),
+                    storage: matches.args[1].parse::<Storage>().map_err(|value| {
+                        CommandError::BadUsage(format!("Unknown storage '{}'", value), spec)
+                    })?,
+                },
+            };
 
             Ok(Command::Workspace(WorkspaceOpts {
-                list: list.is_some(),
-                create: create.is_some(),
-                name,
+                list: false,
+                create: false,
+                name: None,
+                action: Some(action),
             }))
         }
-        _ => Err(CommandError::NotRecognized),
+        "config" | "config list" => Ok(Command::Config(ConfigOpts {
+            action: ConfigAction::List,
+        })),
+        "config get" | "config unset" | "config set" => {
+            let key = matches.args[0].clone();
+            let action = match spec.name {
+                "config get" => ConfigAction::Get { key },
+                "config unset" => ConfigAction::Unset { key },
+                _ => ConfigAction::Set {
+                    key,
+                    values: matches.args[1..].to_vec(),
+                },
+            };
+            Ok(Command::Config(ConfigOpts { action }))
+        }
+        "config edit" => Ok(Command::Config(ConfigOpts {
+            action: ConfigAction::Edit,
+        })),
+        "config path" => Ok(Command::Config(ConfigOpts {
+            action: ConfigAction::Path,
+        })),
+        _ => Err(CommandError::NotRecognized(name.to_string(), None)),
+    }
+}
+
+/// Value of --symlinks, if given
+fn symlinks_policy(
+    matches: &Matches,
+    spec: &'static CommandSpec,
+) -> Result<Option<Symlinks>, CommandError> {
+    matches
+        .value("symlinks")
+        .map(|value| {
+            value.parse::<Symlinks>().map_err(|value| {
+                CommandError::BadUsage(format!("Unknown symlink policy '{}'", value), spec)
+            })
+        })
+        .transpose()
+}
+
+/// Check the number of positional arguments against the command spec
+fn validate_args(spec: &'static CommandSpec, matches: &Matches) -> Result<(), CommandError> {
+    if let Some(missing) = spec
+        .args
+        .iter()
+        .skip(matches.args.len())
+        .find(|a| a.required)
+    {
+        return Err(CommandError::BadUsage(
+            format!("Missing argument <{}>", missing.name),
+            spec,
+        ));
     }
+
+    for (arg, value) in spec.args.iter().zip(&matches.args) {
+        if let Complete::Values(values) = arg.complete {
+            if !values.contains(&value.as_str()) {
+                return Err(CommandError::BadUsage(
+                    format!(
+                        "Invalid value '{}' for <{}>, expected one of: {}",
+                        value,
+                        arg.name,
+                        values.join(", ")
+                    ),
+                    spec,
+                ));
+            }
+        }
+    }
+
+    let unlimited = spec.args.last().is_some_and(|a| a.multiple);
+    if !unlimited && matches.args.len() > spec.args.len() {
+        return Err(CommandError::BadUsage(
+            format!("Unexpected argument '{}'", matches.args[spec.args.len()]),
+            spec,
+        ));
+    }
+
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -243,45 +1339,67 @@ pub enum Command {
     Init(InitOpts),
     Create(CreateOpts),
     Workspace(WorkspaceOpts),
+    Config(ConfigOpts),
     Apply(ApplyOpts),
-    List(),
+    List(ListOpts),
+    Doctor(DoctorOpts),
+    Compress(CompressOpts),
+    Export(ExportOpts),
+    Import(ImportOpts),
+    Gc(GcOpts),
+    Completions(CompletionsOpts),
+    Complete(Vec<String>),
+    Man(ManOpts),
+    Help(String),
+    Version,
 }
 
 #[derive(Debug)]
 pub enum CommandError {
-    NotRecognized,
     NoArgs,
-    BadUsage(UsageError),
-}
-
-#[derive(Debug)]
-pub struct UsageError {
-    message: String,
-    usage: String,
-    examples: Vec<String>,
-    flags: Vec<(String, String)>,
+    NotRecognized(String, Option<String>),
+    UnknownFlag(String, Option<String>),
+    MissingValue(&'static FlagSpec),
+    UnexpectedValue(&'static FlagSpec),
+    BadUsage(String, &'static CommandSpec),
+    BadFormat(String),
 }
 
 impl std::fmt::Display for CommandError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            CommandError::NotRecognized | CommandError::NoArgs => {
-                write!(f, "{}", no_args())
+            CommandError::NoArgs => write!(f, "{}", help()),
+            CommandError::NotRecognized(command, suggestion) => {
+                write!(f, "Unknown command '{}'", command)?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean '{}'?", suggestion)?;
+                }
+                write!(f, "\n\nRun 'cloup --help' to see all commands")
             }
-            CommandError::BadUsage(data) => {
-                let max_len = data.flags.iter().map(|(a, _)| a.len()).max().unwrap_or(0) + 2;
-
+            CommandError::UnknownFlag(flag, suggestion) => {
+                write!(f, "Unknown flag '{}'", flag)?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean '{}'?", suggestion)?;
+                }
+                Ok(())
+            }
+            CommandError::MissingValue(flag) => write!(
+                f,
+                "Flag '--{}' requires a value <{}>",
+                flag.long,
+                flag.value.unwrap_or("value")
+            ),
+            CommandError::UnexpectedValue(flag) => {
+                write!(f, "Flag '--{}' does not take a value", flag.long)
+            }
+            CommandError::BadUsage(message, spec) => {
+                write!(f, "{}\n\n{}", message, command_help(spec, false))
+            }
+            CommandError::BadFormat(value) => {
                 write!(
                     f,
-                    "{}\n\n\x1b[1mUSAGE\x1b[0m\n    {}\n\n\x1b[1mEXAMPLES\x1b[0m\n    {}\n\n\x1b[1mFLAGS\x1b[0m\n    {}\n",
-                    data.message,
-                    data.usage,
-                    data.examples.join("\n    "),
-                    data.flags
-                        .iter()
-                        .map(|(a, b)| format!("{:<width$} {}", a, b, width = max_len))
-                        .collect::<Vec<_>>()
-                        .join("\n    ")
+                    "Unknown output format '{}', expected 'text' or 'json'",
+                    value
                 )
             }
         }
@@ -289,7 +1407,156 @@ impl std::fmt::Display for CommandError {
 }
 impl std::error::Error for CommandError {}
 
-fn no_args() -> String {
+impl Report for CommandError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Usage
+    }
+}
+
+/// Format a flag as shown in help output, e.g. `-w, --workspace <name>`
+pub fn flag_usage(flag: &FlagSpec) -> String {
+    let mut usage = match flag.short {
+        Some(short) => format!("-{}, --{}", short, flag.long),
+        None => format!("--{}", flag.long),
+    };
+
+    if let Some(value) = flag.value {
+        usage.push_str(&format!(" <{}>", value));
+        if flag.multiple {
+            usage.push_str("...");
+        }
+    }
+
+    usage
+}
+
+/// Format a list of (name, description) pairs as aligned columns
+fn columns(rows: &[(String, &str)]) -> String {
+    let width = rows.iter().map(|(a, _)| a.len()).max().unwrap_or(0) + 2;
+
+    rows.iter()
+        .map(|(a, b)| format!("{:<width$} {}", a, b, width = width))
+        .collect::<Vec<_>>()
+        .join("\n    ")
+}
+
+/// Usage line of a command, e.g. `cloup apply <name> [flags]`
+pub fn command_usage(spec: &CommandSpec) -> String {
+    let mut usage = format!("cloup {}", spec.name);
+
+    for arg in spec.args {
+        let dots = if arg.multiple { "..." } else { "" };
+        if arg.required {
+            usage.push_str(&format!(" <{}>{}", arg.name, dots));
+        } else {
+            usage.push_str(&format!(" [{}]{}", arg.name, dots));
+        }
+    }
+
+    format!("{} [flags]", usage)
+}
+
+/// Help output for a single command, `long` adds the full description
+pub fn command_help(spec: &CommandSpec, long: bool) -> String {
+    let mut help = format!(
+        "{}\n\n\x1b[1mUSAGE\x1b[0m\n    $ {}\n",
+        spec.about,
+        command_usage(spec)
+    );
+
+    if long {
+        let description = wrap(spec.long_about, 76)
+            .lines()
+            .map(|l| {
+                if l.is_empty() {
+                    String::new()
+                } else {
+                    format!("    {l}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        help.push_str(&format!("\n\x1b[1mDESCRIPTION\x1b[0m\n{}\n", description));
+    }
+
+    if !spec.examples.is_empty() {
+        help.push_str(&format!(
+            "\n\x1b[1mEXAMPLES\x1b[0m\n    {}\n",
+            spec.examples.join("\n    ")
+        ));
+    }
+
+    if !spec.args.is_empty() {
+        let args: Vec<(String, &str)> = spec
+            .args
+            .iter()
+            .map(|a| (format!("<{}>", a.name), a.help))
+            .collect();
+        help.push_str(&format!(
+            "\n\x1b[1mARGUMENTS\x1b[0m\n    {}\n",
+            columns(&args)
+        ));
+    }
+
+    if !spec.subcommands.is_empty() {
+        let subcommands: Vec<(String, &str)> = spec
+            .subcommands
+            .iter()
+            .map(|c| (c.word().to_string(), c.about))
+            .collect();
+        help.push_str(&format!(
+            "\n\x1b[1mCOMMANDS\x1b[0m\n    {}\n",
+            columns(&subcommands)
+        ));
+    }
+
+    // Command flags, followed by --help
+    let flags: Vec<(String, &str)> = spec
+        .flags
+        .iter()
+        .chain(GLOBAL_FLAGS.iter().take(1))
+        .map(|f| (flag_usage(f), f.help))
+        .collect();
+    help.push_str(&format!("\n\x1b[1mFLAGS\x1b[0m\n    {}\n", columns(&flags)));
+
+    help
+}
+
+/// Wrap text at the given width, keeping blank lines between paragraphs
+fn wrap(text: &str, width: usize) -> String {
+    text.split("\n\n")
+        .map(|paragraph| {
+            let mut lines = vec![];
+            let mut line = String::new();
+
+            for word in paragraph.split_whitespace() {
+                if !line.is_empty() && line.len() + word.len() + 1 > width {
+                    lines.push(std::mem::take(&mut line));
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(word);
+            }
+
+            lines.push(line);
+            lines.join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Help output for cloup itself
+pub fn help() -> String {
+    let options: Vec<(String, &str)> = GLOBAL_FLAGS
+        .iter()
+        .map(|f| (flag_usage(f), f.help))
+        .collect();
+    let commands: Vec<(String, &str)> = COMMANDS
+        .iter()
+        .map(|c| (c.name.to_string(), c.about))
+        .collect();
+
     format!(
         "Local template manager
 
@@ -300,15 +1567,131 @@ fn no_args() -> String {
     $ cloup [command] [flags]
 
 \x1b[1mOPTIONS\x1b[0m
-    -h, --help      Print help information
-    -V, --version   Print version information
-    
+    {}
+
 \x1b[1mCOMMANDS\x1b[0m
-    init            Sets the current directory as a location for cloups
-    create          Create a new cloup
-    apply           Apply a cloup to the current directory
-    list            List all cloups in the current workspace
-    workspace       Sets the current workspace or list all workspaces
-"
+    {}
+
+Run 'cloup help <command>' for more information on a command.
+",
+        columns(&options),
+        columns(&commands),
     )
 }
+
+/// Version output for cloup itself
+pub fn version() -> String {
+    format!("cloup {}", VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, CommandError> {
+        command_parser(args.iter().map(|a| a.to_string()).collect())
+    }
+
+    #[test]
+    fn parse_flag_forms() {
+        let Ok(Command::Create(opts)) = parse(&[
+            "create",
+            "my-cloup",
+            "--workspace=work",
+            "-f",
+            "a",
+            "b",
+            "--json",
+            "-e",
+            "c",
+            "--",
+            "-d",
+        ]) else {
+            panic!("expected create command");
+        };
+
+        assert_eq!(opts.name, "my-cloup");
+        assert_eq!(opts.workspace.as_deref(), Some("work"));
+        assert_eq!(
+            opts.files,
+            vec![PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("-d")]
+        );
+        assert_eq!(opts.exclude, vec![PathBuf::from("c")]);
+    }
+
+    #[test]
+    fn parse_combined_short_flags() {
+        let Ok(Command::Init(opts)) = parse(&["--json", "init", "-ow", "work"]) else {
+            panic!("expected init command");
+        };
+        assert!(opts.overwrite);
+        assert_eq!(opts.workspace.as_deref(), Some("work"));
+
+        let Ok(Command::Workspace(opts)) = parse(&["w", "-l"]) else {
+            panic!("expected workspace command");
+        };
+        assert!(opts.list);
+        assert_eq!(opts.name, None);
+
+        let Ok(Command::Workspace(opts)) = parse(&["workspace"]) else {
+            panic!("expected workspace command");
+        };
+        assert!(!opts.list && !opts.create && opts.name.is_none());
+
+        for args in [["workspace", "-c", "work"], ["workspace", "work", "-c"]] {
+            let Ok(Command::Workspace(opts)) = parse(&args) else {
+                panic!("expected workspace command");
+            };
+            assert!(opts.create);
+            assert_eq!(opts.name.as_deref(), Some("work"));
+        }
+        assert!(matches!(
+            parse(&["workspace", "-c"]),
+            Err(CommandError::BadUsage(..))
+        ));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            parse(&["apply", "x", "--worksapce", "y"]),
+            Err(CommandError::UnknownFlag(_, Some(s))) if s == "--workspace"
+        ));
+        assert!(matches!(
+            parse(&["aply", "x"]),
+            Err(CommandError::NotRecognized(_, Some(s))) if s == "apply"
+        ));
+        assert!(matches!(
+            parse(&["apply", "-w"]),
+            Err(CommandError::MissingValue(_))
+        ));
+        assert!(matches!(
+            parse(&["list", "--json=yes"]),
+            Err(CommandError::UnexpectedValue(_))
+        ));
+        assert!(matches!(
+            parse(&["apply", "x", "y"]),
+            Err(CommandError::BadUsage(..))
+        ));
+        assert!(matches!(parse(&["apply", "--help"]), Ok(Command::Help(_))));
+    }
+
+    #[test]
+    fn workspace_subcommands_are_reserved_names() {
+        for spec in WORKSPACE_SUBCOMMANDS {
+            let name = spec.name.trim_start_matches("workspace ");
+            for word in std::iter::once(&name).chain(spec.aliases) {
+                assert!(
+                    crate::utils::name::WORKSPACE_SUBCOMMANDS.contains(word),
+                    "'{}' is not a reserved workspace name",
+                    word
+                );
+            }
+        }
+
+        let Ok(Command::Workspace(opts)) = parse(&["workspace", "mv", "work", "dir"]) else {
+            panic!("expected workspace command");
+        };
+        assert!(matches!(opts.action, Some(WorkspaceAction::Move { .. })));
+    }
+}
@@ -0,0 +1,387 @@
+use crate::{
+    toml::data::{Toml, TomlValueKind},
+    utils::{
+        error::{ErrorKind, Report},
+        file::{Conflict, Symlinks},
+        output::Color,
+        parse::suggest,
+        secrets::SecretPolicy,
+    },
+};
+
+/// Type of the value of a setting
+#[derive(Debug, PartialEq)]
+pub enum SettingKind {
+    String,
+    Bool,
+    List,
+    Choice(&'static [&'static str]),
+}
+
+/// Definition of a key that can be read and written with `cloup config`
+#[derive(Debug)]
+pub struct Setting {
+    /// Dotted path of the key, e.g. `settings.color` for `color` in `[settings]`
+    pub key: &'static str,
+    pub kind: SettingKind,
+
+    /// One line description used in help output
+    pub help: &'static str,
+
+    /// Managed by cloup itself, can only be read
+    pub read_only: bool,
+}
+
+impl Setting {
+    /// Table and key of the setting, the table being empty for top-level keys
+    pub fn path(&self) -> (&'static str, &'static str) {
+        self.key.split_once('.').unwrap_or(("", self.key))
+    }
+}
+
+pub const CONFLICT_POLICIES: &[&str] = &["ask", "skip", "overwrite", "abort"];
+pub const COLOR_MODES: &[&str] = &["auto", "always", "never"];
+pub const SYMLINK_POLICIES: &[&str] = &["preserve", "follow", "skip", "error"];
+pub const SECRET_POLICIES: &[&str] = &["abort", "warn", "ignore"];
+
+/// Dotfiles that git and package managers act on wherever they are, including
+/// inside a cloup of a versioned or published workspace
+const DEFAULT_ESCAPE: &[&str] = &[
+    ".gitignore",
+    ".gitattributes",
+    ".gitmodules",
+    ".github",
+    ".npmrc",
+    ".npmignore",
+];
+
+/// Every known key of `Cloup.toml`, except the entries of `[workspaces]`
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "version",
+        kind: SettingKind::String,
+        help: "Version of the config file format",
+        read_only: true,
+    },
+    Setting {
+        key: "active_workspace",
+        kind: SettingKind::String,
+        help: "Workspace used by commands without --workspace",
+        read_only: false,
+    },
+    Setting {
+        key: "search_path",
+        kind: SettingKind::List,
+        help: "Workspaces searched for cloups after the active one",
+        read_only: false,
+    },
+    Setting {
+        key: "settings.conflict",
+        kind: SettingKind::Choice(CONFLICT_POLICIES),
+        help: "What apply does with files that already exist",
+        read_only: false,
+    },
+    Setting {
+        key: "settings.color",
+        kind: SettingKind::Choice(COLOR_MODES),
+        help: "When to print coloured output",
+        read_only: false,
+    },
+    Setting {
+        key: "settings.editor",
+        kind: SettingKind::String,
+        help: "Editor opened by 'cloup config edit'",
+        read_only: false,
+    },
+    Setting {
+        key: "settings.exclude",
+        kind: SettingKind::List,
+        help: "Files and directories never copied into a cloup",
+        read_only: false,
+    },
+    Setting {
+        key: "settings.escape",
+        kind: SettingKind::List,
+        help: "Dotfiles stored under an escaped name in cloups",
+        read_only: false,
+    },
+    Setting {
+        key: "settings.symlinks",
+        kind: SettingKind::Choice(SYMLINK_POLICIES),
+        help: "What create and apply do with symlinks",
+        read_only: false,
+    },
+    Setting {
+        key: "settings.secrets",
+        kind: SettingKind::Choice(SECRET_POLICIES),
+        help: "What create does with files that look like secrets",
+        read_only: false,
+    },
+    Setting {
+        key: "settings.allow_secrets",
+        kind: SettingKind::List,
+        help: "Files create stores even if they look like secrets",
+        read_only: false,
+    },
+    Setting {
+        key: "settings.preserve_mtime",
+        kind: SettingKind::Bool,
+        help: "Keep the modification times of files in create and apply",
+        read_only: false,
+    },
+];
+
+/// Values of the `[settings]` table, the defaults applied to every command
+#[derive(Debug)]
+pub struct Settings {
+    /// Files and directories never copied into a cloup, as names or glob patterns
+    pub exclude: Vec<String>,
+
+    /// Dotfiles stored as `_dot_<name>` by create, as names or glob patterns
+    pub escape: Vec<String>,
+
+    /// What apply does with files that already exist, unless --conflict is given
+    pub conflict: Conflict,
+
+    /// What create and apply do with symlinks, unless --symlinks is given
+    pub symlinks: Symlinks,
+
+    /// What create does with likely secrets, unless --secrets is given
+    pub secrets: SecretPolicy,
+
+    /// Files never reported as secrets, as names, paths or glob patterns
+    pub allow_secrets: Vec<String>,
+
+    /// Keep the modification times of files, unless --no-preserve is given
+    pub preserve_mtime: bool,
+
+    /// When to print coloured text
+    pub color: Color,
+
+    /// Editor opened by `cloup config edit`, instead of $VISUAL or $EDITOR
+    pub editor: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            exclude: vec![".DS_Store".to_string()],
+            escape: DEFAULT_ESCAPE.iter().map(|s| s.to_string()).collect(),
+            conflict: Conflict::Overwrite,
+            symlinks: Symlinks::Preserve,
+            secrets: SecretPolicy::Abort,
+            allow_secrets: vec![],
+            preserve_mtime: false,
+            color: Color::Auto,
+            editor: None,
+        }
+    }
+}
+
+/// Read the `[settings]` table, every missing key taking its default
+pub fn read_settings(toml: &Toml) -> Result<Settings, SettingsError> {
+    let mut settings = Settings::default();
+
+    let Some(table) = toml.get("settings") else {
+        return Ok(settings);
+    };
+    let TomlValueKind::Table(key_values) = table else {
+        return Err(SettingsError::InvalidValue(
+            "settings".to_string(),
+            "a table".to_string(),
+        ));
+    };
+
+    for kv in key_values {
+        let setting = find_setting(&format!("settings.{}", kv.key))?;
+        check_value(setting, &kv.kind)?;
+
+        match (kv.key.as_str(), &kv.kind) {
+            ("exclude", TomlValueKind::Array(values)) => settings.exclude = strings(values),
+            ("escape", TomlValueKind::Array(values)) => settings.escape = strings(values),
+            ("allow_secrets", TomlValueKind::Array(values)) => {
+                settings.allow_secrets = strings(values)
+            }
+            ("secrets", TomlValueKind::String(value)) => {
+                settings.secrets = value.parse().unwrap_or(settings.secrets)
+            }
+            ("conflict", TomlValueKind::String(value)) => {
+                settings.conflict = value.parse().unwrap_or(settings.conflict)
+            }
+            ("color", TomlValueKind::String(value)) => {
+                settings.color = value.parse().unwrap_or(settings.color)
+            }
+            ("symlinks", TomlValueKind::String(value)) => {
+                settings.symlinks = value.parse().unwrap_or(settings.symlinks)
+            }
+            ("preserve_mtime", TomlValueKind::Boolean(value)) => settings.preserve_mtime = *value,
+            ("editor", TomlValueKind::String(value)) => settings.editor = Some(value.clone()),
+            _ => (),
+        }
+    }
+
+    Ok(settings)
+}
+
+fn strings(values: &[TomlValueKind]) -> Vec<String> {
+    values
+        .iter()
+        .filter_map(|v| match v {
+            TomlValueKind::String(v) => Some(v.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    UnknownKey(String, Option<String>),
+    ReadOnly(String),
+    InvalidValue(String, String),
+    MissingValue(String),
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SettingsError::UnknownKey(key, suggestion) => {
+                write!(f, "Unknown config key '{}'", key)?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean '{}'?", suggestion)?;
+                }
+                Ok(())
+            }
+            SettingsError::ReadOnly(key) => write!(f, "Config key '{}' can't be changed", key),
+            SettingsError::InvalidValue(key, expected) => {
+                write!(f, "Invalid value for '{}', expected {}", key, expected)
+            }
+            SettingsError::MissingValue(key) => write!(f, "Missing value for '{}'", key),
+        }
+    }
+}
+impl std::error::Error for SettingsError {}
+
+impl Report for SettingsError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SettingsError::UnknownKey(..) | SettingsError::MissingValue(_) => ErrorKind::Usage,
+            SettingsError::ReadOnly(_) | SettingsError::InvalidValue(..) => ErrorKind::Config,
+        }
+    }
+}
+
+/// Find the definition of a key
+pub fn find_setting(key: &str) -> Result<&'static Setting, SettingsError> {
+    SETTINGS.iter().find(|s| s.key == key).ok_or_else(|| {
+        SettingsError::UnknownKey(
+            key.to_string(),
+            suggest(key, SETTINGS.iter().map(|s| s.key)),
+        )
+    })
+}
+
+/// Description of the values a setting accepts, used in error messages
+fn expected(kind: &SettingKind) -> String {
+    match kind {
+        SettingKind::String => "a string".to_string(),
+        SettingKind::Bool => "true or false".to_string(),
+        SettingKind::List => "a list of strings".to_string(),
+        SettingKind::Choice(values) => format!("one of {}", values.join(", ")),
+    }
+}
+
+/// Convert command line values into the TOML value of a setting
+pub fn parse_value(setting: &Setting, values: &[String]) -> Result<TomlValueKind, SettingsError> {
+    if setting.read_only {
+        return Err(SettingsError::ReadOnly(setting.key.to_string()));
+    }
+
+    let invalid = || SettingsError::InvalidValue(setting.key.to_string(), expected(&setting.kind));
+
+    if setting.kind == SettingKind::List {
+        return Ok(TomlValueKind::Array(
+            values.iter().cloned().map(TomlValueKind::String).collect(),
+        ));
+    }
+
+    let value = match values {
+        [] => return Err(SettingsError::MissingValue(setting.key.to_string())),
+        [value] => value,
+        _ => return Err(invalid()),
+    };
+
+    match &setting.kind {
+        SettingKind::Bool => match value.as_str() {
+            "true" | "yes" | "on" | "1" => Ok(TomlValueKind::Boolean(true)),
+            "false" | "no" | "off" | "0" => Ok(TomlValueKind::Boolean(false)),
+            _ => Err(invalid()),
+        },
+        SettingKind::Choice(choices) if !choices.contains(&value.as_str()) => Err(invalid()),
+        _ => Ok(TomlValueKind::String(value.clone())),
+    }
+}
+
+/// Check the type of a value read from the config file
+fn check_value(setting: &Setting, kind: &TomlValueKind) -> Result<(), SettingsError> {
+    let valid = match (&setting.kind, kind) {
+        (SettingKind::String, TomlValueKind::String(_)) => true,
+        (SettingKind::Bool, TomlValueKind::Boolean(_)) => true,
+        (SettingKind::List, TomlValueKind::Array(values)) => {
+            values.iter().all(|v| matches!(v, TomlValueKind::String(_)))
+        }
+        (SettingKind::Choice(choices), TomlValueKind::String(value)) => {
+            choices.contains(&value.as_str())
+        }
+        _ => false,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(SettingsError::InvalidValue(
+            setting.key.to_string(),
+            expected(&setting.kind),
+        ))
+    }
+}
+
+/// Check every key of a config file against the known settings
+pub fn validate(toml: &Toml) -> Result<(), SettingsError> {
+    for value in &toml.data {
+        match &value.kind {
+            // workspaces are free-form, everything else is known
+            TomlValueKind::Table(_) if value.key == "workspaces" => (),
+            TomlValueKind::Table(key_values) => {
+                for kv in key_values {
+                    let setting = find_setting(&format!("{}.{}", value.key, kv.key))?;
+                    check_value(setting, &kv.kind)?;
+                }
+            }
+            kind => check_value(find_setting(&value.key)?, kind)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_values() {
+        let values = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let color = find_setting("settings.color").unwrap();
+
+        assert!(parse_value(color, &values(&["never"])).is_ok());
+        assert!(parse_value(color, &values(&["red"])).is_err());
+        assert!(parse_value(find_setting("version").unwrap(), &values(&["2"])).is_err());
+        assert!(matches!(
+            find_setting("settings.colour"),
+            Err(SettingsError::UnknownKey(_, Some(s))) if s == "settings.color"
+        ));
+
+        let toml = Toml::from("[settings]\nexclude = [\"target\", 1]\n".to_string());
+        assert!(validate(&toml).is_err());
+    }
+}
@@ -0,0 +1,725 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::Read,
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{
+    archive::{is_partial_archive, private_dir},
+    crypto::{hex, sha256},
+    error::{ErrorKind, Report},
+    file::symlink,
+    meta::{file_mode, set_mode},
+};
+use crate::toml::data::{Toml, TomlValue, TomlValueKind};
+
+/// Directory of a workspace holding the contents of its files once by hash.
+/// Cloups created in a workspace that has one are kept there as manifests.
+pub const STORE_DIR: &str = ".cloup-store";
+
+/// File left in a cloup kept in the store, listing its paths along with the
+/// hash of their content
+pub const MANIFEST_FILE: &str = ".cloup-manifest.toml";
+
+pub const STORAGE_MODES: &[&str] = &["plain", "dedup"];
+
+/// Blobs and partial archives younger than this may belong to a
+/// command still running
+const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// How a workspace stores the cloups created in it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Storage {
+    /// Every cloup is a directory holding its files
+    Plain,
+    /// File contents are stored once by hash, cloups are manifests
+    Dedup,
+}
+
+impl std::str::FromStr for Storage {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "plain" => Ok(Storage::Plain),
+            "dedup" => Ok(Storage::Dedup),
+            _ => Err(value.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for Storage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Storage::Plain => write!(f, "plain"),
+            Storage::Dedup => write!(f, "dedup"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Missing(PathBuf),
+    Corrupt(PathBuf),
+    Malformed(PathBuf),
+    Error(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StoreError::Missing(path) => write!(
+                f,
+                "The content of '{}' is missing from the store",
+                path.to_string_lossy()
+            ),
+            StoreError::Corrupt(path) => write!(
+                f,
+                "The content of '{}' was changed in the store",
+                path.to_string_lossy()
+            ),
+            StoreError::Malformed(path) => write!(
+                f,
+                "'{}' is not a manifest this version of cloup can read",
+                path.to_string_lossy()
+            ),
+            StoreError::Error(path, _) => {
+                write!(f, "Could not read or write '{}'", path.to_string_lossy())
+            }
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Error(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Report for StoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            StoreError::Missing(_) => ErrorKind::NotFound,
+            StoreError::Corrupt(_) | StoreError::Malformed(_) | StoreError::Error(..) => {
+                ErrorKind::Io
+            }
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            StoreError::Missing(path)
+            | StoreError::Corrupt(path)
+            | StoreError::Malformed(path)
+            | StoreError::Error(path, _) => Some(path),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    File,
+    Link,
+    Dir,
+}
+
+/// A path of a cloup kept in the store. The content of a link is its target,
+/// directories are only listed when empty and have no content.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub kind: EntryKind,
+    pub mode: u32,
+    pub hash: String,
+    pub path: PathBuf,
+}
+
+/// Cloups stored in the workspace at `location` are kept in the store
+pub fn is_dedup(location: &Path) -> bool {
+    location.join(STORE_DIR).is_dir()
+}
+
+/// The cloup is a manifest of contents kept in the store
+pub fn is_packed(cloup_path: &Path) -> bool {
+    cloup_path.join(MANIFEST_FILE).is_file()
+}
+
+/// Store of the workspace a cloup is in
+fn store_of(cloup_path: &Path) -> PathBuf {
+    cloup_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(STORE_DIR)
+}
+
+fn blob_path(store: &Path, hash: &str) -> PathBuf {
+    store.join("objects").join(&hash[..2]).join(&hash[2..])
+}
+
+/// Move the contents of a plain cloup into the store of its workspace,
+/// leaving only a manifest behind
+pub fn pack(cloup_path: &Path) -> Result<(), StoreError> {
+    let store = store_of(cloup_path);
+    let mut entries = vec![];
+    add_entries(cloup_path, cloup_path, &store, &mut entries)?;
+    write_manifest(cloup_path, &entries)?;
+
+    // everything is in the store once the manifest is written
+    let error = |e| StoreError::Error(cloup_path.to_path_buf(), e);
+    for entry in fs::read_dir(cloup_path).map_err(error)? {
+        let path = entry.map_err(error)?.path();
+        if path.file_name().is_some_and(|n| n == MANIFEST_FILE) {
+            continue;
+        }
+
+        let is_dir = fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir());
+        let removed = if is_dir {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        removed.map_err(|e| StoreError::Error(path, e))?;
+    }
+
+    Ok(())
+}
+
+/// Write the contents of a cloup kept in the store to `dest`, checking each
+/// one against its hash. Links come last, so nothing is written through them.
+pub fn unpack(cloup_path: &Path, dest: &Path) -> Result<(), StoreError> {
+    let store = store_of(cloup_path);
+    let mut entries = read_manifest(cloup_path)?;
+    entries.sort_by_key(|entry| entry.kind == EntryKind::Link);
+
+    for entry in entries {
+        let path = dest.join(&entry.path);
+        let error = |e| StoreError::Error(path.clone(), e);
+
+        let parent = path.parent().unwrap_or(dest);
+        fs::create_dir_all(parent).map_err(|e| StoreError::Error(parent.to_path_buf(), e))?;
+
+        match entry.kind {
+            EntryKind::Dir => {
+                fs::create_dir_all(&path).map_err(error)?;
+                set_mode(&path, entry.mode).map_err(error)?;
+            }
+            EntryKind::File => {
+                fs::write(&path, read_blob(&store, &entry)?).map_err(error)?;
+                set_mode(&path, entry.mode).map_err(error)?;
+            }
+            EntryKind::Link => {
+                let target =
+                    PathBuf::from(String::from_utf8_lossy(&read_blob(&store, &entry)?).to_string());
+                if fs::symlink_metadata(&path).is_ok() {
+                    fs::remove_file(&path).map_err(error)?;
+                }
+                symlink(&target, &path).map_err(error)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Turn a cloup kept in the store back into a plain directory
+pub fn unpack_in_place(cloup_path: &Path) -> Result<(), StoreError> {
+    unpack(cloup_path, cloup_path)?;
+
+    let manifest = cloup_path.join(MANIFEST_FILE);
+    fs::remove_file(&manifest).map_err(|e| StoreError::Error(manifest, e))
+}
+
+/// A plain copy of a cloup kept in the store, removed when dropped
+pub struct Checkout {
+    pub path: PathBuf,
+}
+
+impl Checkout {
+    /// Unpack a cloup to a new temporary directory only the user can read
+    pub fn new(cloup_path: &Path) -> Result<Self, StoreError> {
+        let path =
+            private_dir(cloup_path).map_err(|e| StoreError::Error(std::env::temp_dir(), e))?;
+
+        // dropped on error, taking what was written with it
+        let checkout = Checkout { path };
+        unpack(cloup_path, &checkout.path)?;
+        Ok(checkout)
+    }
+}
+
+impl Drop for Checkout {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Size of the files of a cloup kept in the store, as if it were plain
+pub fn packed_size(cloup_path: &Path) -> u64 {
+    let store = store_of(cloup_path);
+
+    read_manifest(cloup_path)
+        .unwrap_or_default()
+        .iter()
+        .filter(|entry| entry.kind == EntryKind::File)
+        .filter_map(|entry| fs::metadata(blob_path(&store, &entry.hash)).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Paths of a cloup kept in the store whose content is missing
+pub fn missing(cloup_path: &Path) -> Result<Vec<PathBuf>, StoreError> {
+    let store = store_of(cloup_path);
+
+    Ok(read_manifest(cloup_path)?
+        .into_iter()
+        .filter(|entry| entry.kind != EntryKind::Dir)
+        .filter(|entry| !blob_path(&store, &entry.hash).is_file())
+        .map(|entry| entry.path)
+        .collect())
+}
+
+/// What `collect_garbage` removed, or would remove
+#[derive(Debug, Default)]
+pub struct Garbage {
+    pub blobs: usize,
+    pub bytes: u64,
+    pub archives: usize,
+}
+
+/// Remove the contents no cloup of the workspace refers to anymore, along
+/// with partial archives left behind by a compress that was interrupted
+pub fn collect_garbage(location: &Path, dry_run: bool) -> Result<Garbage, StoreError> {
+    let store = location.join(STORE_DIR);
+    let mut garbage = Garbage::default();
+    let now = SystemTime::now();
+    let stale = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|m| m.modified())
+            .is_ok_and(|t| now.duration_since(t).unwrap_or_default() > GRACE_PERIOD)
+    };
+
+    // a manifest that can't be read stops everything, it may refer to anything
+    let mut referenced = HashSet::new();
+    let error = |e| StoreError::Error(location.to_path_buf(), e);
+    for entry in fs::read_dir(location).map_err(error)? {
+        let path = entry.map_err(error)?.path();
+        let is_cloup = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with("cl_"));
+        if !is_cloup {
+            continue;
+        }
+
+        if is_packed(&path) {
+            referenced.extend(read_manifest(&path)?.into_iter().map(|e| e.hash));
+        }
+
+        for (_, file) in entries(&path)? {
+            if !is_partial_archive(&file) || !stale(&file) {
+                continue;
+            }
+
+            garbage.archives += 1;
+            if !dry_run {
+                fs::remove_file(&file).map_err(|e| StoreError::Error(file, e))?;
+            }
+        }
+    }
+
+    if !store.is_dir() {
+        return Ok(garbage);
+    }
+
+    let objects = store.join("objects");
+    for (prefix, dir) in entries(&objects)? {
+        for (rest, blob) in entries(&dir)? {
+            // it may have been written for a manifest that isn't there yet
+            if referenced.contains(&format!("{}{}", prefix, rest)) || !stale(&blob) {
+                continue;
+            }
+
+            garbage.blobs += 1;
+            garbage.bytes += fs::metadata(&blob).map(|m| m.len()).unwrap_or(0);
+            if !dry_run {
+                fs::remove_file(&blob).map_err(|e| StoreError::Error(blob, e))?;
+            }
+        }
+
+        if !dry_run && fs::read_dir(&dir).is_ok_and(|mut d| d.next().is_none()) {
+            let _ = fs::remove_dir(&dir);
+        }
+    }
+
+    Ok(garbage)
+}
+
+/// Move the store of a workspace to another location, merging it into the
+/// store already there
+pub fn move_store(from: &Path, to: &Path) -> Result<(), StoreError> {
+    let (source, store) = (from.join(STORE_DIR), to.join(STORE_DIR));
+    if !source.is_dir() {
+        return Ok(());
+    }
+
+    if !store.exists() && fs::rename(&source, &store).is_ok() {
+        return Ok(());
+    }
+
+    for (prefix, dir) in entries(&source.join("objects"))? {
+        let dest_dir = store.join("objects").join(&prefix);
+        fs::create_dir_all(&dest_dir).map_err(|e| StoreError::Error(dest_dir.clone(), e))?;
+
+        for (rest, blob) in entries(&dir)? {
+            let dest = dest_dir.join(rest);
+            // renaming fails across filesystems, fall back to copying
+            if !dest.exists() && fs::rename(&blob, &dest).is_err() {
+                fs::copy(&blob, &dest).map_err(|e| StoreError::Error(dest, e))?;
+            }
+        }
+    }
+
+    fs::remove_dir_all(&source).map_err(|e| StoreError::Error(source, e))
+}
+
+/// Entries of a directory by name, none if it doesn't exist
+fn entries(dir: &Path) -> Result<Vec<(String, PathBuf)>, StoreError> {
+    let read = match fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(StoreError::Error(dir.to_path_buf(), e)),
+    };
+
+    read.map(|entry| {
+        let entry = entry.map_err(|e| StoreError::Error(dir.to_path_buf(), e))?;
+        Ok((
+            entry.file_name().to_string_lossy().to_string(),
+            entry.path(),
+        ))
+    })
+    .collect()
+}
+
+/// List the paths below `dir`, adding their contents to the store
+fn add_entries(
+    root: &Path,
+    dir: &Path,
+    store: &Path,
+    entries: &mut Vec<Entry>,
+) -> Result<(), StoreError> {
+    let error = |e| StoreError::Error(dir.to_path_buf(), e);
+    let mut paths = fs::read_dir(dir)
+        .map_err(error)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(error)?;
+    paths.sort();
+
+    if paths.is_empty() && dir != root {
+        let metadata = fs::metadata(dir).map_err(error)?;
+        entries.push(Entry {
+            kind: EntryKind::Dir,
+            mode: file_mode(&metadata).unwrap_or(0o755),
+            hash: "-".to_string(),
+            path: dir.strip_prefix(root).unwrap_or(dir).to_path_buf(),
+        });
+    }
+
+    for path in paths {
+        let error = |e| StoreError::Error(path.clone(), e);
+        let metadata = fs::symlink_metadata(&path).map_err(error)?;
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+
+        if metadata.is_dir() {
+            add_entries(root, &path, store, entries)?;
+        } else if metadata.file_type().is_symlink() {
+            let target = fs::read_link(&path).map_err(error)?;
+            let content = target.to_string_lossy().to_string().into_bytes();
+            let hash = hex(&sha256(&content));
+            put_blob(store, &hash, |blob| fs::write(blob, &content))?;
+
+            entries.push(Entry {
+                kind: EntryKind::Link,
+                mode: 0,
+                hash,
+                path: relative,
+            });
+        } else {
+            let hash = hash_file(&path).map_err(error)?;
+            put_blob(store, &hash, |blob| fs::copy(&path, blob).map(|_| ()))?;
+
+            entries.push(Entry {
+                kind: EntryKind::File,
+                mode: file_mode(&metadata).unwrap_or(0o644),
+                hash,
+                path: relative,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Add a content to the store unless it's already there. It's written next
+/// to its place first, so an interrupted write never leaves a partial blob.
+/// A blob already there is touched instead, so `collect_garbage` leaves it
+/// alone until the manifest referring to it is written.
+fn put_blob(
+    store: &Path,
+    hash: &str,
+    write: impl FnOnce(&Path) -> std::io::Result<()>,
+) -> Result<(), StoreError> {
+    let blob = blob_path(store, hash);
+    let error = |e| StoreError::Error(blob.clone(), e);
+    if blob.is_file() {
+        return fs::File::options()
+            .write(true)
+            .open(&blob)
+            .and_then(|f| f.set_modified(SystemTime::now()))
+            .map_err(error);
+    }
+
+    fs::create_dir_all(blob.parent().unwrap_or(store)).map_err(error)?;
+
+    let partial = blob.with_extension(format!("tmp-{}", std::process::id()));
+    write(&partial)
+        .and_then(|_| fs::rename(&partial, &blob))
+        .map_err(|e| {
+            let _ = fs::remove_file(&partial);
+            error(e)
+        })
+}
+
+fn read_blob(store: &Path, entry: &Entry) -> Result<Vec<u8>, StoreError> {
+    let blob = blob_path(store, &entry.hash);
+    let data = match fs::read(&blob) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(StoreError::Missing(entry.path.clone()))
+        }
+        Err(e) => return Err(StoreError::Error(blob, e)),
+    };
+
+    if hex(&sha256(&data)) != entry.hash {
+        return Err(StoreError::Corrupt(entry.path.clone()));
+    }
+    Ok(data)
+}
+
+fn write_manifest(cloup_path: &Path, entries: &[Entry]) -> Result<(), StoreError> {
+    let entries = entries
+        .iter()
+        .map(|entry| TomlValueKind::String(format_entry(entry)))
+        .collect();
+
+    let toml = Toml::from(Some(vec![TomlValue {
+        key: "files".to_string(),
+        kind: TomlValueKind::Table(vec![TomlValue {
+            key: "entries".to_string(),
+            kind: TomlValueKind::Array(entries),
+        }]),
+    }]));
+
+    let path = cloup_path.join(MANIFEST_FILE);
+    let partial = path.with_extension("tmp");
+    fs::write(&partial, toml.to_toml())
+        .and_then(|_| fs::rename(&partial, &path))
+        .map_err(|e| StoreError::Error(path, e))
+}
+
+/// Read the manifest of a cloup. Unlike the metadata of cloups, an entry
+/// that can't be understood is an error, as it would be a lost file, and so
+/// is an entry below a link, which would be written wherever it points.
+fn read_manifest(cloup_path: &Path) -> Result<Vec<Entry>, StoreError> {
+    let path = cloup_path.join(MANIFEST_FILE);
+    let content = fs::read_to_string(&path).map_err(|e| StoreError::Error(path.clone(), e))?;
+
+    let toml = Toml::from(content);
+    let values = match toml.get_in("files", "entries") {
+        Some(TomlValueKind::Array(values)) => values.as_slice(),
+        None => &[],
+        Some(_) => return Err(StoreError::Malformed(path)),
+    };
+
+    let entries = values
+        .iter()
+        .map(|value| match value {
+            TomlValueKind::String(entry) => parse_entry(entry),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or(StoreError::Malformed(path.clone()))?;
+
+    for link in entries.iter().filter(|e| e.kind == EntryKind::Link) {
+        let below = |e: &Entry| e.path != link.path && e.path.starts_with(&link.path);
+        if entries.iter().any(below) {
+            return Err(StoreError::Malformed(path));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Entries are written as `file 0644 <hash> path/to/file`, with `/` as
+/// separator on every platform. Directories have `-` as hash.
+pub fn format_entry(entry: &Entry) -> String {
+    let kind = match entry.kind {
+        EntryKind::File => "file",
+        EntryKind::Link => "link",
+        EntryKind::Dir => "dir",
+    };
+    let path: Vec<_> = entry
+        .path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+
+    format!(
+        "{} {:04o} {} {}",
+        kind,
+        entry.mode,
+        entry.hash,
+        path.join("/")
+    )
+}
+
+/// Read an entry written by `format_entry`, none if it can't be understood or
+/// leads outside of the cloup
+pub fn parse_entry(entry: &str) -> Option<Entry> {
+    let mut parts = entry.splitn(4, ' ');
+    let kind = match parts.next()? {
+        "file" => EntryKind::File,
+        "link" => EntryKind::Link,
+        "dir" => EntryKind::Dir,
+        _ => return None,
+    };
+    let mode = u32::from_str_radix(parts.next()?, 8)
+        .ok()
+        .filter(|m| *m <= 0o7777)?;
+    let hash = parts.next()?.to_string();
+    let segments: Vec<&str> = parts.next()?.split('/').collect();
+
+    let valid_hash = hash.len() == 64
+        && hash
+            .bytes()
+            .all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase());
+    if kind != EntryKind::Dir && !valid_hash {
+        return None;
+    }
+
+    // a manifest never leads outside of the cloup
+    let path: PathBuf = segments.iter().collect();
+    let inside = segments.iter().all(|s| !matches!(*s, "" | "." | ".."))
+        && path.components().all(|c| matches!(c, Component::Normal(_)));
+    if !inside {
+        return None;
+    }
+
+    Some(Entry {
+        kind,
+        mode,
+        hash,
+        path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{backdate, write_tree, TempDir};
+
+    #[test]
+    fn deduplication() {
+        let dir = TempDir::new("store");
+        fs::create_dir(dir.join(STORE_DIR)).unwrap();
+        write_tree(
+            &dir,
+            &[
+                ("cl_first/.prettierrc", "{}\n"),
+                ("cl_first/src/main.rs", "fn main() {}\n"),
+                ("cl_first/logs/", ""),
+                ("cl_second/.prettierrc", "{}\n"),
+                ("cl_second/src/lib.rs", ""),
+                ("cl_second/logs/", ""),
+            ],
+        );
+        let (first, second) = (dir.join("cl_first"), dir.join("cl_second"));
+
+        pack(&first).unwrap();
+        pack(&second).unwrap();
+        assert!(is_packed(&first));
+        assert_eq!(fs::read_dir(&first).unwrap().count(), 1);
+
+        // `.prettierrc` is stored once
+        let blobs = |dir: &Path| {
+            entries(&dir.join(STORE_DIR).join("objects"))
+                .unwrap()
+                .iter()
+                .map(|(_, d)| entries(d).unwrap().len())
+                .sum::<usize>()
+        };
+        assert_eq!(blobs(&dir), 3);
+
+        fs::remove_dir_all(&second).unwrap();
+        assert_eq!(collect_garbage(&dir, false).unwrap().blobs, 0);
+        for (_, prefix) in entries(&dir.join(STORE_DIR).join("objects")).unwrap() {
+            for (_, blob) in entries(&prefix).unwrap() {
+                backdate(&blob, 2 * GRACE_PERIOD);
+            }
+        }
+        assert_eq!(collect_garbage(&dir, false).unwrap().blobs, 1);
+        assert_eq!(blobs(&dir), 2);
+
+        unpack_in_place(&first).unwrap();
+        assert!(!is_packed(&first));
+        assert_eq!(
+            fs::read_to_string(first.join("src").join("main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        assert!(first.join("logs").is_dir());
+
+        assert_eq!(parse_entry("file 0644 abc ../escape"), None);
+        let hash = "0".repeat(64);
+        assert_eq!(
+            parse_entry(&format!("file 0644 {} /etc/passwd", hash)),
+            None
+        );
+        assert_eq!(parse_entry(&format!("file 0644 {} a/../b", hash)), None);
+    }
+
+    #[test]
+    fn entries_below_links() {
+        let cloup = TempDir::new("store-links");
+
+        let hash = "0".repeat(64);
+        let manifest = format!(
+            "[files]\nentries = [\"link 0000 {0} home\", \"file 0644 {0} home/.bashrc\"]\n",
+            hash
+        );
+        fs::write(cloup.join(MANIFEST_FILE), manifest).unwrap();
+        assert!(matches!(
+            read_manifest(&cloup),
+            Err(StoreError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,579 @@
+// Reading and writing of tar archives in the ustar format, with the GNU and
+// pax extensions other tools use for long paths. Only files, directories and
+// symlinks are supported, and paths are checked to stay inside the archive.
+
+use std::{
+    fs,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use super::{
+    file::symlink,
+    meta::{file_mode, set_mode},
+};
+
+const BLOCK: usize = 512;
+
+#[derive(Debug, PartialEq)]
+pub enum TarError {
+    /// Not a tar archive, or a damaged one
+    Malformed,
+    /// A path that would lead outside of the directory it's extracted to
+    UnsafePath(String),
+    /// An entry that is neither a file, a directory nor a symlink
+    Unsupported(String),
+}
+
+impl std::fmt::Display for TarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TarError::Malformed => write!(f, "not a valid tar archive"),
+            TarError::UnsafePath(path) => {
+                write!(
+                    f,
+                    "'{}' would be written outside of the archive's directory",
+                    path
+                )
+            }
+            TarError::Unsupported(path) => {
+                write!(f, "'{}' is not a file, a directory or a symlink", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TarError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TarKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+/// An entry of an archive, with `/` as separator on every platform
+#[derive(Debug, PartialEq)]
+pub struct TarEntry {
+    pub path: String,
+    pub kind: TarKind,
+    pub mode: u32,
+    pub mtime: u64,
+
+    /// Content of a file
+    pub data: Vec<u8>,
+
+    /// Target of a symlink
+    pub link: String,
+}
+
+/// Entries for everything below `root`, ordered by path so the same files
+/// always give the same archive
+pub fn entries_of(root: &Path) -> std::io::Result<Vec<TarEntry>> {
+    let mut entries = vec![];
+    visit(root, root, &mut |path, mut entry| {
+        if entry.kind == TarKind::File {
+            entry.data = fs::read(path)?;
+        }
+        entries.push(entry);
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+/// Write an archive of everything below `root` but what `skip` is true for
+/// to `out`, in the order of `entries_of` but reading a single file at a time
+pub fn write_tar_of(
+    root: &Path,
+    out: &mut impl Write,
+    skip: impl Fn(&Path) -> bool,
+) -> std::io::Result<()> {
+    visit(root, root, &mut |path, entry| {
+        if skip(path) {
+            return Ok(());
+        }
+        if entry.kind != TarKind::File {
+            return out.write_all(&entry_header(&entry, 0));
+        }
+
+        let file = fs::File::open(path)?;
+        let size = file.metadata()?.len();
+        out.write_all(&entry_header(&entry, size))?;
+
+        // the size in the header is the one read, whatever the file does meanwhile
+        let copied = std::io::copy(&mut file.take(size), out)?;
+        if copied != size {
+            return Err(std::io::Error::other(format!(
+                "'{}' changed while being archived",
+                path.to_string_lossy()
+            )));
+        }
+        out.write_all(&vec![0; padding(size as usize)])
+    })?;
+
+    out.write_all(&[0; BLOCK * 2])
+}
+
+/// Call `f` with every path below `dir` and its entry, files without
+/// their content
+fn visit(
+    root: &Path,
+    dir: &Path,
+    f: &mut impl FnMut(&Path, TarEntry) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    for path in paths {
+        let metadata = fs::symlink_metadata(&path)?;
+        let relative: Vec<_> = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+
+        let mut entry = TarEntry {
+            path: relative.join("/"),
+            kind: TarKind::File,
+            mode: file_mode(&metadata).unwrap_or(0o644),
+            mtime,
+            data: vec![],
+            link: String::new(),
+        };
+
+        if metadata.file_type().is_symlink() {
+            entry.kind = TarKind::Symlink;
+            entry.mode = 0o777;
+            entry.link = fs::read_link(&path)?.to_string_lossy().to_string();
+            f(&path, entry)?;
+        } else if metadata.is_dir() {
+            entry.kind = TarKind::Dir;
+            entry.mode = file_mode(&metadata).unwrap_or(0o755);
+            f(&path, entry)?;
+            visit(root, &path, f)?;
+        } else {
+            f(&path, entry)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write entries into `dest`, symlinks last so nothing is written through
+/// them. Entries come from `read_tar`, which checked their paths.
+pub fn extract(entries: &[TarEntry], dest: &Path) -> std::io::Result<()> {
+    let mut ordered: Vec<&TarEntry> = entries.iter().collect();
+    ordered.sort_by_key(|entry| entry.kind == TarKind::Symlink);
+
+    for entry in ordered {
+        let path: PathBuf = dest.join(entry.path.split('/').collect::<PathBuf>());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        match entry.kind {
+            TarKind::Dir => {
+                fs::create_dir_all(&path)?;
+                set_mode(&path, entry.mode)?;
+            }
+            TarKind::File => {
+                let file = fs::File::create(&path)?;
+                std::io::Write::write_all(&mut &file, &entry.data)?;
+                if entry.mtime > 0 {
+                    file.set_modified(UNIX_EPOCH + Duration::from_secs(entry.mtime))?;
+                }
+                set_mode(&path, entry.mode)?;
+            }
+            TarKind::Symlink => {
+                if fs::symlink_metadata(&path).is_ok() {
+                    fs::remove_file(&path)?;
+                }
+                symlink(Path::new(&entry.link), &path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_tar(entries: &[TarEntry]) -> Vec<u8> {
+    let mut out = vec![];
+
+    for entry in entries {
+        out.extend(entry_header(entry, entry.data.len() as u64));
+        if entry.kind == TarKind::File {
+            write_data(&mut out, &entry.data);
+        }
+    }
+
+    out.extend([0; BLOCK * 2]);
+    out
+}
+
+/// Header of an entry whose content is `size` bytes long, after the entries
+/// holding a path or a link too long for it
+fn entry_header(entry: &TarEntry, size: u64) -> Vec<u8> {
+    let mut out = vec![];
+    let mut path = entry.path.clone();
+    if entry.kind == TarKind::Dir {
+        path.push('/');
+    }
+
+    // paths that don't fit the header come in an entry of their own before it
+    let (prefix, name) = split_path(&path).unwrap_or_else(|| {
+        write_long(&mut out, b'L', &path);
+        ("", truncate(&path, 100))
+    });
+    if entry.link.len() > 100 {
+        write_long(&mut out, b'K', &entry.link);
+    }
+
+    let typeflag = match entry.kind {
+        TarKind::File => b'0',
+        TarKind::Dir => b'5',
+        TarKind::Symlink => b'2',
+    };
+    let mut header = header(name, entry.mode, size, entry.mtime, typeflag);
+    put(&mut header[157..257], truncate(&entry.link, 100));
+    put(&mut header[345..500], prefix);
+    checksum(&mut header);
+
+    out.extend(header);
+    out
+}
+
+fn truncate(value: &str, len: usize) -> &str {
+    let mut end = value.len().min(len);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+/// Split a path into the prefix and name fields of a header, if it fits
+fn split_path(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some(("", path));
+    }
+
+    // directories end with `/`, which can't be where the path is split
+    let trimmed = path.trim_end_matches('/');
+    trimmed
+        .match_indices('/')
+        .map(|(i, _)| (&path[..i], &path[i + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && name.len() <= 100 && !name.is_empty())
+}
+
+fn write_long(out: &mut Vec<u8>, typeflag: u8, value: &str) {
+    let mut data = value.as_bytes().to_vec();
+    data.push(0);
+
+    let mut header = header("././@LongLink", 0o644, data.len() as u64, 0, typeflag);
+    checksum(&mut header);
+    out.extend(header);
+    write_data(out, &data);
+}
+
+fn write_data(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend(data);
+    out.extend(std::iter::repeat_n(0, padding(data.len())));
+}
+
+/// Zeros filling the last block of content `len` bytes long
+fn padding(len: usize) -> usize {
+    len.next_multiple_of(BLOCK) - len
+}
+
+fn header(name: &str, mode: u32, size: u64, mtime: u64, typeflag: u8) -> [u8; BLOCK] {
+    let mut header = [0; BLOCK];
+    put(&mut header[0..100], name);
+    put(&mut header[100..108], &format!("{:07o}", mode & 0o7777));
+    put(&mut header[108..116], "0000000");
+    put(&mut header[116..124], "0000000");
+    put(&mut header[124..136], &format!("{:011o}", size));
+    put(
+        &mut header[136..148],
+        &format!("{:011o}", mtime.min(0o77777777777)),
+    );
+    header[156] = typeflag;
+    put(&mut header[257..263], "ustar");
+    put(&mut header[263..265], "00");
+    header
+}
+
+fn put(field: &mut [u8], value: &str) {
+    let len = value.len().min(field.len());
+    field[..len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+fn checksum(header: &mut [u8; BLOCK]) {
+    header[148..156].fill(b' ');
+    let sum: u32 = header.iter().map(|b| *b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+}
+
+/// Read the entries of an archive, refusing paths that would lead outside
+/// of the directory it's extracted to
+pub fn read_tar(data: &[u8]) -> Result<Vec<TarEntry>, TarError> {
+    let mut entries: Vec<TarEntry> = vec![];
+    let mut pos = 0;
+    let (mut long_path, mut long_link): (Option<String>, Option<String>) = (None, None);
+
+    while pos + BLOCK <= data.len() {
+        let header = &data[pos..pos + BLOCK];
+        pos += BLOCK;
+
+        // the archive ends with empty blocks
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+        check_checksum(header)?;
+
+        let size = octal(&header[124..136])? as usize;
+        let content = data.get(pos..pos + size).ok_or(TarError::Malformed)?;
+        pos += size.next_multiple_of(BLOCK);
+
+        let has_prefix = &header[257..262] == b"ustar" && header[345] != 0;
+        let mut path = if has_prefix {
+            format!("{}/{}", text(&header[345..500]), text(&header[0..100]))
+        } else {
+            text(&header[0..100])
+        };
+        let mut link = text(&header[157..257]);
+
+        match header[156] {
+            b'L' => {
+                long_path = Some(text(content));
+                continue;
+            }
+            b'K' => {
+                long_link = Some(text(content));
+                continue;
+            }
+            b'x' => {
+                for (key, value) in pax_records(content)? {
+                    match key.as_str() {
+                        "path" => long_path = Some(value),
+                        "linkpath" => long_link = Some(value),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            b'g' => continue,
+            _ => {}
+        }
+
+        if let Some(long) = long_path.take() {
+            path = long;
+        }
+        if let Some(long) = long_link.take() {
+            link = long;
+        }
+
+        let kind = match header[156] {
+            b'0' | 0 | b'7' => TarKind::File,
+            b'5' => TarKind::Dir,
+            b'2' => TarKind::Symlink,
+            _ => return Err(TarError::Unsupported(path)),
+        };
+        let Some(path) = safe_path(&path)? else {
+            // the directory itself, as in `./`
+            continue;
+        };
+
+        entries.push(TarEntry {
+            path,
+            kind,
+            mode: octal(&header[100..108])? as u32 & 0o7777,
+            mtime: octal(&header[136..148])?,
+            data: if kind == TarKind::File {
+                content.to_vec()
+            } else {
+                vec![]
+            },
+            link,
+        });
+    }
+
+    // a file below a symlink would be written wherever the symlink points
+    for link in entries.iter().filter(|e| e.kind == TarKind::Symlink) {
+        let below = format!("{}/", link.path);
+        if let Some(entry) = entries.iter().find(|e| e.path.starts_with(&below)) {
+            return Err(TarError::UnsafePath(entry.path.clone()));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// A path of an archive without `./` and trailing `/`, none for the
+/// directory of the archive itself
+fn safe_path(path: &str) -> Result<Option<String>, TarError> {
+    let unsafe_path = || TarError::UnsafePath(path.to_string());
+    if path.starts_with('/') || path.contains('\\') {
+        return Err(unsafe_path());
+    }
+
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|s| !s.is_empty() && *s != ".")
+        .collect();
+    if segments.is_empty() {
+        return Ok(None);
+    }
+
+    let as_path: PathBuf = segments.iter().collect();
+    let inside = segments.iter().all(|s| *s != "..")
+        && as_path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+    if !inside {
+        return Err(unsafe_path());
+    }
+
+    Ok(Some(segments.join("/")))
+}
+
+fn check_checksum(header: &[u8]) -> Result<(), TarError> {
+    let expected = octal(&header[148..156])?;
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, b)| if (148..156).contains(&i) { b' ' } else { *b } as u64)
+        .sum();
+
+    if sum == expected {
+        Ok(())
+    } else {
+        Err(TarError::Malformed)
+    }
+}
+
+fn octal(field: &[u8]) -> Result<u64, TarError> {
+    let value = text(field);
+    let value = value.trim_matches([' ', '\0']);
+    if value.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(value, 8).map_err(|_| TarError::Malformed)
+}
+
+/// A NUL terminated field
+fn text(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/// Records of a pax header, each one written as `<length> <key>=<value>\n`
+fn pax_records(content: &[u8]) -> Result<Vec<(String, String)>, TarError> {
+    let mut records = vec![];
+    let mut rest = content;
+
+    while !rest.is_empty() {
+        let space = rest
+            .iter()
+            .position(|b| *b == b' ')
+            .ok_or(TarError::Malformed)?;
+        let len: usize = std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|l| l.parse().ok())
+            .filter(|l| *l > space && *l <= rest.len())
+            .ok_or(TarError::Malformed)?;
+
+        let record = String::from_utf8_lossy(&rest[space + 1..len]).to_string();
+        let (key, value) = record
+            .trim_end_matches('\n')
+            .split_once('=')
+            .ok_or(TarError::Malformed)?;
+        records.push((key.to_string(), value.to_string()));
+        rest = &rest[len..];
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, mode: u32, data: Vec<u8>) -> TarEntry {
+        TarEntry {
+            path: path.to_string(),
+            kind: TarKind::File,
+            mode,
+            mtime: 0,
+            data,
+            link: String::new(),
+        }
+    }
+
+    #[test]
+    fn archives() {
+        let long = format!("{}/{}", "deep".repeat(40), "file.txt");
+        let longer = "x".repeat(300);
+        let entries = vec![
+            TarEntry {
+                path: "src".to_string(),
+                kind: TarKind::Dir,
+                mode: 0o755,
+                mtime: 1_700_000_000,
+                data: vec![],
+                link: String::new(),
+            },
+            file("src/main.rs", 0o644, b"fn main() {}\n".to_vec()),
+            file(&long, 0o600, vec![]),
+            file(&longer, 0o755, vec![1; 1000]),
+            TarEntry {
+                path: "link".to_string(),
+                kind: TarKind::Symlink,
+                mode: 0o777,
+                mtime: 0,
+                data: vec![],
+                link: "src/main.rs".to_string(),
+            },
+        ];
+        assert_eq!(read_tar(&write_tar(&entries)).unwrap(), entries);
+
+        let unsafe_archive = |path: &str| read_tar(&write_tar(&[file(path, 0o644, vec![])]));
+        assert!(matches!(
+            unsafe_archive("../evil"),
+            Err(TarError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            unsafe_archive("/etc/passwd"),
+            Err(TarError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            unsafe_archive("a/../../b"),
+            Err(TarError::UnsafePath(_))
+        ));
+        assert_eq!(unsafe_archive("./a").unwrap()[0].path, "a");
+
+        // a symlink to elsewhere followed by a file written through it
+        let through_link = write_tar(&[
+            TarEntry {
+                path: "dir".to_string(),
+                kind: TarKind::Symlink,
+                mode: 0o777,
+                mtime: 0,
+                data: vec![],
+                link: "/tmp".to_string(),
+            },
+            file("dir/evil", 0o644, vec![]),
+        ]);
+        assert!(matches!(
+            read_tar(&through_link),
+            Err(TarError::UnsafePath(_))
+        ));
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Directory of the temporary directory for a single test, removed with
+/// everything in it once dropped, even when the test fails
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a new directory, its name never shared with another test of
+    /// this process or of any other one
+    pub fn new(name: &str) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos();
+        let path = std::env::temp_dir().join(format!(
+            "cloup-test-{}-{}-{}-{:08x}",
+            name,
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed),
+            nanos
+        ));
+
+        // fails rather than sharing a directory left behind by anyone else
+        fs::create_dir(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Create the files of `tree` in `dir` along with their parents, a path
+/// ending with `/` being an empty directory
+pub fn write_tree(dir: &Path, tree: &[(&str, &str)]) {
+    for (path, contents) in tree {
+        let full = dir.join(path);
+        if path.ends_with('/') {
+            fs::create_dir_all(&full).unwrap();
+        } else {
+            fs::create_dir_all(full.parent().unwrap()).unwrap();
+            fs::write(&full, contents).unwrap();
+        }
+    }
+}
+
+/// Move the modification time of the file at `path` back by `age`
+pub fn backdate(path: &Path, age: Duration) {
+    let file = fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(SystemTime::now() - age).unwrap();
+}